@@ -0,0 +1,151 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::cli_error::CliError;
+use crate::network_helper::{self, RestApi};
+use produce_consume_core::codec;
+use sawtooth_sdk::messages::batch::BatchList;
+use std::fs;
+
+/// Seconds to wait for the submitted batches by default
+pub(crate) const DEFAULT_WAIT: u64 = 30;
+
+/// Exit code when a batch is invalid
+pub(crate) const INVALID_EXIT_CODE: i32 = 2;
+
+/// Exit code when a batch is neither committed nor invalid at the end of the wait
+pub(crate) const PENDING_EXIT_CODE: i32 = 3;
+
+/// Submits the batches serialized in the file to the REST API. The file holds a
+/// ```BatchList```, as written by the Sawtooth tools, it is checked before it is sent.
+pub(crate) fn submit_file(path: &str, rest_api: &RestApi, wait: u64) -> Result<(), CliError> {
+    let bytes = fs::read(path)
+        .map_err(|err| CliError::from(format!("Unable to read {}: {}", path, err)))?;
+    let batch_list: BatchList = codec::decode(&bytes)
+        .map_err(|err| CliError::from(format!("{} is not a batch list: {}", path, err)))?;
+    if batch_list.get_batches().is_empty() {
+        return Err(CliError::from(format!("{} does not hold any batch", path)));
+    }
+    let transactions: usize = batch_list
+        .get_batches()
+        .iter()
+        .map(|batch| batch.get_transactions().len())
+        .sum();
+    println!(
+        "Submitting {} batches with {} transactions",
+        batch_list.get_batches().len(),
+        transactions
+    );
+    submit(rest_api, &bytes, wait)
+}
+
+/// Submits the serialized batch list to the REST API and waits for the outcome of its
+/// batches for up to ```wait``` seconds. An invalid or pending batch is an error with its
+/// own exit code.
+pub(crate) fn submit(rest_api: &RestApi, batch_list: &[u8], wait: u64) -> Result<(), CliError> {
+    let statuses = submit_and_wait(rest_api, batch_list, wait)?;
+    for status in &statuses {
+        println!("Batch {}: {}", status.id, status.status);
+        for (_, message) in &status.invalid_transactions {
+            println!("  {}", message);
+        }
+    }
+    outcome(&statuses)
+}
+
+/// Outcome of the batches, an invalid batch outweighs one that is not committed yet
+fn outcome(statuses: &[network_helper::BatchStatus]) -> Result<(), CliError> {
+    if statuses.iter().any(|status| status.status == "INVALID") {
+        return Err(CliError::with_exit_code(
+            "A batch is invalid".to_string(),
+            INVALID_EXIT_CODE,
+        ));
+    }
+    if statuses.iter().any(|status| status.status != "COMMITTED") {
+        return Err(CliError::with_exit_code(
+            "A batch is not committed yet".to_string(),
+            PENDING_EXIT_CODE,
+        ));
+    }
+    Ok(())
+}
+
+/// Submits the serialized batch list to the REST API, the statuses of its batches are
+/// returned once they are all committed or invalid, or after ```wait``` seconds.
+pub(crate) fn submit_and_wait(
+    rest_api: &RestApi,
+    batch_list: &[u8],
+    wait: u64,
+) -> Result<Vec<network_helper::BatchStatus>, CliError> {
+    let ids = network_helper::submit_to_rest_api(rest_api, "batches", batch_list)?;
+    network_helper::get_batch_statuses(rest_api, &ids, wait)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Response of the REST API to ```/batch_statuses``` for the three batches
+    const STATUSES: &str = r#"{
+        "data": [
+            {"id": "1a2b", "invalid_transactions": [], "status": "COMMITTED"},
+            {"id": "3c4d", "invalid_transactions": [], "status": "PENDING"},
+            {
+                "id": "5e6f",
+                "invalid_transactions": [
+                    {"id": "7a8b", "message": "Not enough quantity", "extended_data": ""}
+                ],
+                "status": "INVALID"
+            }
+        ],
+        "link": "http://rest-api:8008/batch_statuses?id=1a2b,3c4d,5e6f"
+    }"#;
+
+    fn parsed(ids: &[&str]) -> Vec<network_helper::BatchStatus> {
+        network_helper::parse_batch_statuses(STATUSES)
+            .unwrap()
+            .into_iter()
+            .filter(|status| ids.contains(&status.id.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn statuses_are_read_from_the_response() {
+        let statuses = network_helper::parse_batch_statuses(STATUSES).unwrap();
+        let ids: Vec<&str> = statuses.iter().map(|status| status.id.as_str()).collect();
+        assert_eq!(ids, vec!["1a2b", "3c4d", "5e6f"]);
+        assert_eq!(
+            statuses[2].invalid_transactions,
+            vec![("7a8b".to_string(), "Not enough quantity".to_string())]
+        );
+        assert!(network_helper::parse_batch_statuses(r#"{"error": {}}"#).is_err());
+    }
+
+    #[test]
+    fn committed_batches_succeed() {
+        assert!(outcome(&parsed(&["1a2b"])).is_ok());
+    }
+
+    #[test]
+    fn invalid_batch_exits_with_2() {
+        let err = outcome(&parsed(&["1a2b", "3c4d", "5e6f"])).unwrap_err();
+        assert_eq!(err.exit_code(), 2);
+    }
+
+    #[test]
+    fn uncommitted_batch_exits_with_3() {
+        let err = outcome(&parsed(&["1a2b", "3c4d"])).unwrap_err();
+        assert_eq!(err.exit_code(), 3);
+    }
+}
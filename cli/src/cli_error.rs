@@ -16,9 +16,25 @@ use std::error::Error;
 use std::fmt;
 use std::string::ToString;
 
+/// Exit code of the CLI for a failed command
+const FAILURE_EXIT_CODE: i32 = 1;
+
 #[derive(Debug)]
 pub(crate) struct CliError {
     inner: String,
+    exit_code: i32,
+}
+
+impl CliError {
+    /// Error that ends the CLI with its own exit code, the scripts can tell the outcome
+    /// of a submitted batch apart from a failed command
+    pub(crate) fn with_exit_code(inner: String, exit_code: i32) -> CliError {
+        CliError { inner, exit_code }
+    }
+
+    pub(crate) fn exit_code(&self) -> i32 {
+        self.exit_code
+    }
 }
 
 impl std::fmt::Display for CliError {
@@ -29,7 +45,10 @@ impl std::fmt::Display for CliError {
 
 impl From<String> for CliError {
     fn from(inner: String) -> CliError {
-        CliError { inner }
+        CliError {
+            inner,
+            exit_code: FAILURE_EXIT_CODE,
+        }
     }
 }
 
@@ -37,6 +56,7 @@ impl From<&'static str> for CliError {
     fn from(inner: &'static str) -> CliError {
         CliError {
             inner: inner.to_string(),
+            exit_code: FAILURE_EXIT_CODE,
         }
     }
 }
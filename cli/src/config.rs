@@ -0,0 +1,206 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Profile used when none is selected and the file does not name a default one
+const DEFAULT_PROFILE: &str = "default";
+
+/// Profiles of the CLI, read from ```~/.config/pc-cli/config.toml```.
+///
+/// The precedence, from the lowest to the highest, is: the selected profile, the
+/// ```PC_CLI_*``` environment variables and the command line arguments.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct CliConfig {
+    /// Profile used when neither ```--profile``` nor ```PC_CLI_PROFILE``` is given
+    pub(crate) default_profile: Option<String>,
+    pub(crate) profiles: BTreeMap<String, Profile>,
+}
+
+/// Settings repeated by every invocation against the same deployment
+#[derive(Debug, Default, Clone, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Profile {
+    /// URL of the REST API, ```PC_CLI_URL```
+    pub(crate) url: Option<String>,
+    /// Private key file to sign with, ```PC_CLI_KEY```
+    pub(crate) key: Option<String>,
+    /// Name of a key of ```~/.sawtooth/keys``` to sign with, ```PC_CLI_KEY_NAME```
+    pub(crate) key_name: Option<String>,
+    /// Family name the processor is registered for, ```PC_CLI_FAMILY_NAME```
+    pub(crate) family_name: Option<String>,
+    /// Family version the processor is registered for, ```PC_CLI_FAMILY_VERSION```
+    pub(crate) family_version: Option<String>,
+    /// Either table or json, ```PC_CLI_OUTPUT```
+    pub(crate) output: Option<String>,
+}
+
+/// Location of the config file, ```$XDG_CONFIG_HOME``` is used if set
+pub(crate) fn config_file() -> Option<PathBuf> {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(config_dir) => PathBuf::from(config_dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_dir.join("pc-cli").join("config.toml"))
+}
+
+impl CliConfig {
+    /// Reads the config file, it is fine for the file to be missing
+    pub(crate) fn load() -> Result<CliConfig, String> {
+        let path = match config_file() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(CliConfig::default()),
+        };
+        let content = fs::read_to_string(&path)
+            .map_err(|err| format!("Unable to read the config file {}: {}", path.display(), err))?;
+        CliConfig::parse(&content)
+            .map_err(|err| format!("Invalid config file {}: {}", path.display(), err))
+    }
+
+    pub(crate) fn parse(content: &str) -> Result<CliConfig, String> {
+        toml::from_str(content).map_err(|err| err.to_string())
+    }
+
+    /// The profile of the given name, an unknown name is an error. Without a name the
+    /// default profile is used if there is one, otherwise every setting is left unset.
+    pub(crate) fn profile(&self, name: Option<&str>) -> Result<Profile, String> {
+        if let Some(name) = name {
+            return self
+                .profiles
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("Unknown profile {}", name));
+        }
+        match &self.default_profile {
+            Some(name) => self.profile(Some(name)),
+            None => Ok(self
+                .profiles
+                .get(DEFAULT_PROFILE)
+                .cloned()
+                .unwrap_or_default()),
+        }
+    }
+}
+
+impl Profile {
+    /// Overrides the settings with the ```PC_CLI_*``` environment variables that are set
+    pub(crate) fn apply_env(&mut self) -> Result<(), String> {
+        self.apply_overrides(|name| env::var(name).ok())
+    }
+
+    fn apply_overrides<F>(&mut self, lookup: F) -> Result<(), String>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if let Some(url) = lookup("PC_CLI_URL") {
+            self.url = Some(url);
+        }
+        // A key file and a key name exclude each other, the one set last is kept
+        if let Some(key) = lookup("PC_CLI_KEY") {
+            self.key = Some(key);
+            self.key_name = None;
+        }
+        if let Some(key_name) = lookup("PC_CLI_KEY_NAME") {
+            self.key_name = Some(key_name);
+            self.key = None;
+        }
+        if let Some(family_name) = lookup("PC_CLI_FAMILY_NAME") {
+            self.family_name = Some(family_name);
+        }
+        if let Some(family_version) = lookup("PC_CLI_FAMILY_VERSION") {
+            self.family_version = Some(family_version);
+        }
+        if let Some(output) = lookup("PC_CLI_OUTPUT") {
+            self.output = Some(output);
+        }
+        match self.output.as_ref().map(String::as_str) {
+            None | Some("table") | Some("json") => Ok(()),
+            Some(output) => Err(format!("Invalid output {}, expected table or json", output)),
+        }
+    }
+
+    pub(crate) fn url(&self) -> Option<&str> {
+        self.url.as_ref().map(String::as_str)
+    }
+
+    pub(crate) fn output(&self) -> Option<&str> {
+        self.output.as_ref().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "default_profile = \"local\"\n\
+                          [profiles.local]\n\
+                          url = \"http://localhost:8008\"\n\
+                          key_name = \"alice\"\n\
+                          [profiles.staging]\n\
+                          url = \"http://rest-api:8008\"\n\
+                          key = \"/keys/validator.priv\"\n\
+                          family_version = \"1.0\"\n\
+                          output = \"json\"\n";
+
+    #[test]
+    fn profiles_are_selected_by_name_or_default() {
+        let config = CliConfig::parse(CONFIG).unwrap();
+        let local = config.profile(None).unwrap();
+        assert_eq!(local.url(), Some("http://localhost:8008"));
+        assert_eq!(local.key_name, Some("alice".to_string()));
+        let staging = config.profile(Some("staging")).unwrap();
+        assert_eq!(staging.key, Some("/keys/validator.priv".to_string()));
+        assert_eq!(staging.output(), Some("json"));
+        assert!(config.profile(Some("production")).is_err());
+        assert_eq!(
+            CliConfig::default().profile(None).unwrap(),
+            Profile::default()
+        );
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        assert!(CliConfig::parse("[profiles.local]\nurll = \"http://localhost:8008\"\n").is_err());
+    }
+
+    #[test]
+    fn environment_overrides_the_profile() {
+        let mut profile = CliConfig::parse(CONFIG)
+            .unwrap()
+            .profile(Some("staging"))
+            .unwrap();
+        profile
+            .apply_overrides(|name| match name {
+                "PC_CLI_URL" => Some("http://env:8008".to_string()),
+                "PC_CLI_KEY_NAME" => Some("bob".to_string()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(profile.url(), Some("http://env:8008"));
+        assert_eq!(profile.key, None);
+        assert_eq!(profile.key_name, Some("bob".to_string()));
+        assert_eq!(profile.family_version, Some("1.0".to_string()));
+        assert!(profile
+            .apply_overrides(|name| match name {
+                "PC_CLI_OUTPUT" => Some("yaml".to_string()),
+                _ => None,
+            })
+            .is_err());
+    }
+}
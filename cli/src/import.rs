@@ -0,0 +1,480 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::batch;
+use crate::cli_error::CliError;
+use crate::keys;
+use crate::network_helper::RestApi;
+use crate::produce_consume::{self, Deployment, Submitter};
+use crate::proto::action::Action_Command;
+use crate::sawtooth_helper;
+use protobuf::Message;
+use sawtooth_sdk::messages::batch::Batch;
+use sawtooth_sdk::messages::transaction::Transaction;
+use sawtooth_sdk::signing::{create_context, PrivateKey, Signer};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Most transactions put in a single batch, the limit of the validator
+pub(crate) const MAX_BATCH_SIZE: usize = 100;
+
+/// Transactions per batch unless another size is given. An invalid transaction fails
+/// its whole batch, small batches keep the other rows apart.
+pub(crate) const DEFAULT_BATCH_SIZE: usize = 10;
+
+/// Batches sent in a single request to the REST API, the next request is sent once
+/// they are committed so that the validator is not flooded
+const BATCHES_PER_REQUEST: usize = 10;
+
+/// Layout of the rows in the imported file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum RowFormat {
+    /// ```command,identifier,quantity``` lines, the header line is optional
+    Csv,
+    /// A JSON object with the same fields on each line
+    Ndjson,
+}
+
+impl RowFormat {
+    /// The format is told by the extension of the file, CSV unless it is a JSON one
+    fn of_path(path: &str) -> RowFormat {
+        match Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("ndjson") | Some("jsonl") | Some("json") => RowFormat::Ndjson,
+            _ => RowFormat::Csv,
+        }
+    }
+}
+
+/// A PRODUCE or a CONSUME read from the file
+struct Row {
+    command: String,
+    identifier: String,
+    quantity: String,
+}
+
+/// What became of a row once its batch was submitted
+enum Outcome {
+    Committed,
+    /// The handler rejected the transaction of the row
+    Invalid(String),
+    /// Another transaction of the same batch is invalid
+    NotCommitted,
+    Pending,
+    /// The row could not be turned into a transaction, it is not submitted
+    Skipped(String),
+    /// The batch could not be submitted
+    Failed(String),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Committed => write!(f, "COMMITTED"),
+            Outcome::Invalid(message) => write!(f, "INVALID {}", message),
+            Outcome::NotCommitted => write!(f, "NOT COMMITTED, its batch is invalid"),
+            Outcome::Pending => write!(f, "PENDING"),
+            Outcome::Skipped(reason) => write!(f, "SKIPPED {}", reason),
+            Outcome::Failed(reason) => write!(f, "FAILED {}", reason),
+        }
+    }
+}
+
+/// Imports the PRODUCE and CONSUME rows of the file. Every row is a transaction signed
+/// with the key, the transactions are packed in batches of ```batch_size``` and a result
+/// is printed for each row. Rows that are not committed make it an error.
+pub(crate) fn import_file(
+    path: &str,
+    format: Option<RowFormat>,
+    batch_size: usize,
+    submitter: &Submitter,
+    deployment: &Deployment,
+) -> Result<(), CliError> {
+    if batch_size == 0 || batch_size > MAX_BATCH_SIZE {
+        return Err(CliError::from(format!(
+            "The batch size must be between 1 and {}",
+            MAX_BATCH_SIZE
+        )));
+    }
+    let contents = fs::read_to_string(path)
+        .map_err(|err| CliError::from(format!("Unable to read {}: {}", path, err)))?;
+    let format = format.unwrap_or_else(|| RowFormat::of_path(path));
+    let rows = parse_rows(&contents, format);
+    if rows.is_empty() {
+        return Err(CliError::from(format!("{} does not hold any row", path)));
+    }
+
+    let private_key: Box<dyn PrivateKey> = Box::new(keys::read_private_key(&submitter.key)?);
+    let context = create_context("secp256k1").expect("Unable to create a secp256k1 context");
+    let signer = Signer::new(context.as_ref(), private_key.as_ref());
+    let public_key = signer
+        .get_public_key()
+        .map_err(|err| CliError::from(err.to_string()))?
+        .as_hex();
+
+    let mut outcomes: Vec<Option<Outcome>> = rows.iter().map(|_| None).collect();
+    let mut transactions = Vec::new();
+    for (index, (_, row)) in rows.iter().enumerate() {
+        let transaction = row.as_ref().map_err(|err| err.clone()).and_then(|row| {
+            create_transaction(row, &public_key, &signer, deployment).map_err(|err| err.to_string())
+        });
+        match transaction {
+            Ok(transaction) => transactions.push((index, transaction)),
+            Err(reason) => outcomes[index] = Some(Outcome::Skipped(reason)),
+        }
+    }
+
+    let batches: Vec<(Batch, Vec<usize>)> = split_in_batches(&transactions, batch_size)
+        .into_iter()
+        .map(|(chunk, indexes)| (sawtooth_helper::create_batch(&signer, chunk), indexes))
+        .collect();
+    println!(
+        "Submitting {} transactions in {} batches",
+        transactions.len(),
+        batches.len()
+    );
+    for group in batches.chunks(BATCHES_PER_REQUEST) {
+        submit_group(group, &submitter.rest_api, deployment.wait, &mut outcomes);
+    }
+
+    print_report(&rows, &outcomes);
+    let outcomes: Vec<&Outcome> = outcomes.iter().filter_map(Option::as_ref).collect();
+    if outcomes.iter().any(|outcome| match outcome {
+        Outcome::Failed(_) => true,
+        _ => false,
+    }) {
+        return Err(CliError::from("Some batches could not be submitted"));
+    }
+    if outcomes.iter().any(|outcome| match outcome {
+        Outcome::Invalid(_) | Outcome::NotCommitted | Outcome::Skipped(_) => true,
+        _ => false,
+    }) {
+        return Err(CliError::with_exit_code(
+            "Some rows were not imported".to_string(),
+            batch::INVALID_EXIT_CODE,
+        ));
+    }
+    if outcomes.iter().any(|outcome| match outcome {
+        Outcome::Pending => true,
+        _ => false,
+    }) {
+        return Err(CliError::with_exit_code(
+            "Some rows are not committed yet".to_string(),
+            batch::PENDING_EXIT_CODE,
+        ));
+    }
+    Ok(())
+}
+
+/// Transactions split in batches of at most ```batch_size```, each batch keeps the
+/// indexes of its rows in the order of its transactions
+fn split_in_batches<T: Clone>(
+    transactions: &[(usize, T)],
+    batch_size: usize,
+) -> Vec<(Vec<T>, Vec<usize>)> {
+    transactions
+        .chunks(batch_size)
+        .map(|chunk| {
+            (
+                chunk
+                    .iter()
+                    .map(|(_, transaction)| transaction.clone())
+                    .collect(),
+                chunk.iter().map(|(index, _)| *index).collect(),
+            )
+        })
+        .collect()
+}
+
+/// Submits the batches in one request and sets the outcome of their rows
+fn submit_group(
+    group: &[(Batch, Vec<usize>)],
+    rest_api: &RestApi,
+    wait: u64,
+    outcomes: &mut [Option<Outcome>],
+) {
+    let batch_list =
+        sawtooth_helper::create_batch_list(group.iter().map(|(batch, _)| batch.clone()).collect());
+    let statuses = batch_list
+        .write_to_bytes()
+        .map_err(|err| CliError::from(err.to_string()))
+        .and_then(|bytes| batch::submit_and_wait(rest_api, &bytes, wait));
+    let statuses = match statuses {
+        Ok(statuses) => statuses,
+        Err(err) => {
+            for (_, indexes) in group {
+                for index in indexes {
+                    outcomes[*index] = Some(Outcome::Failed(err.to_string()));
+                }
+            }
+            return;
+        }
+    };
+    let statuses: HashMap<&str, _> = statuses
+        .iter()
+        .map(|status| (status.id.as_str(), status))
+        .collect();
+    for (batch, indexes) in group {
+        let status = statuses.get(batch.get_header_signature());
+        for (transaction, index) in batch.get_transactions().iter().zip(indexes) {
+            let outcome = match status {
+                Some(status) if status.status == "COMMITTED" => Outcome::Committed,
+                Some(status) if status.status == "INVALID" => status
+                    .invalid_transactions
+                    .iter()
+                    .find(|(id, _)| id == transaction.get_header_signature())
+                    .map(|(_, message)| Outcome::Invalid(message.clone()))
+                    .unwrap_or(Outcome::NotCommitted),
+                _ => Outcome::Pending,
+            };
+            outcomes[*index] = Some(outcome);
+        }
+    }
+}
+
+fn create_transaction(
+    row: &Row,
+    public_key: &str,
+    signer: &Signer,
+    deployment: &Deployment,
+) -> Result<Transaction, CliError> {
+    let command = match row.command.to_uppercase().as_str() {
+        "PRODUCE" => Action_Command::PRODUCE,
+        "CONSUME" => Action_Command::CONSUME,
+        _ => {
+            return Err(CliError::from(format!(
+                "Unknown command {}, expected PRODUCE or CONSUME",
+                row.command
+            )))
+        }
+    };
+    let (action, input_addresses, output_addresses) = produce_consume::item_action(
+        command,
+        &row.identifier,
+        &row.quantity,
+        None,
+        None,
+        None,
+        None,
+        Some(public_key),
+        deployment,
+    )?;
+    produce_consume::create_transaction(
+        &action,
+        &input_addresses,
+        &output_addresses,
+        signer,
+        deployment,
+    )
+}
+
+/// Rows of the file with their line number, empty lines and ```#``` comments are left out
+fn parse_rows(contents: &str, format: RowFormat) -> Vec<(usize, Result<Row, String>)> {
+    match format {
+        RowFormat::Csv => parse_csv_rows(contents),
+        RowFormat::Ndjson => contents
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(number, line)| (number, parse_json_row(line)))
+            .collect(),
+    }
+}
+
+/// Reads the CSV records, fields may be quoted to hold a comma. The header line is
+/// optional, a first record that is not a header is the first row.
+fn parse_csv_rows(contents: &str) -> Vec<(usize, Result<Row, String>)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .comment(Some(b'#'))
+        .from_reader(contents.as_bytes());
+    let mut rows = Vec::new();
+    match reader.headers() {
+        Ok(first) if is_csv_header(first) || is_blank(first) => (),
+        Ok(first) => rows.push(parse_csv_record(first)),
+        Err(err) => rows.push(csv_error(&err)),
+    }
+    for record in reader.records() {
+        match record {
+            Ok(record) if is_blank(&record) => (),
+            Ok(record) => rows.push(parse_csv_record(&record)),
+            Err(err) => rows.push(csv_error(&err)),
+        }
+    }
+    rows
+}
+
+/// The header names the columns, no row has a command of that name
+fn is_csv_header(record: &csv::StringRecord) -> bool {
+    record
+        .get(0)
+        .map(|field| field.eq_ignore_ascii_case("command"))
+        .unwrap_or(false)
+}
+
+/// A line of spaces only
+fn is_blank(record: &csv::StringRecord) -> bool {
+    record.iter().all(str::is_empty)
+}
+
+fn csv_line(position: Option<&csv::Position>) -> usize {
+    position
+        .map(|position| position.line() as usize)
+        .unwrap_or(0)
+}
+
+fn csv_error(err: &csv::Error) -> (usize, Result<Row, String>) {
+    (csv_line(err.position()), Err(err.to_string()))
+}
+
+fn parse_csv_record(record: &csv::StringRecord) -> (usize, Result<Row, String>) {
+    let row = match (record.len(), record.get(0), record.get(1), record.get(2)) {
+        (3, Some(command), Some(identifier), Some(quantity)) => Ok(Row {
+            command: command.to_string(),
+            identifier: identifier.to_string(),
+            quantity: quantity.to_string(),
+        }),
+        (fields, ..) => Err(format!(
+            "Expected command,identifier,quantity, got {} fields",
+            fields
+        )),
+    };
+    (csv_line(record.position()), row)
+}
+
+fn parse_json_row(line: &str) -> Result<Row, String> {
+    let value: Value = serde_json::from_str(line).map_err(|err| err.to_string())?;
+    let field = |name: &str| match &value[name] {
+        Value::String(text) => Ok(text.clone()),
+        Value::Number(number) => Ok(number.to_string()),
+        _ => Err(format!("Missing {}", name)),
+    };
+    Ok(Row {
+        command: field("command")?,
+        identifier: field("identifier")?,
+        quantity: field("quantity")?,
+    })
+}
+
+fn print_report(rows: &[(usize, Result<Row, String>)], outcomes: &[Option<Outcome>]) {
+    println!(
+        "{:>6} {:<8} {:<20} {:>10} {}",
+        "LINE", "COMMAND", "IDENTIFIER", "QUANTITY", "RESULT"
+    );
+    for ((number, row), outcome) in rows.iter().zip(outcomes) {
+        let (command, identifier, quantity) = match row {
+            Ok(row) => (
+                row.command.as_str(),
+                row.identifier.as_str(),
+                row.quantity.as_str(),
+            ),
+            Err(_) => ("-", "-", "-"),
+        };
+        let outcome = match outcome {
+            Some(outcome) => outcome.to_string(),
+            None => Outcome::Pending.to_string(),
+        };
+        println!(
+            "{:>6} {:<8} {:<20} {:>10} {}",
+            number, command, identifier, quantity, outcome
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_rows_have_three_fields() {
+        let rows = parse_rows(
+            " PRODUCE , Bread ,12\nPRODUCE,Bread\nPRODUCE,Bread,12,Bakery\n",
+            RowFormat::Csv,
+        );
+        let numbers: Vec<usize> = rows.iter().map(|(number, _)| *number).collect();
+        assert_eq!(numbers, vec![1, 2, 3]);
+        let row = rows[0].1.as_ref().unwrap();
+        assert_eq!(row.command, "PRODUCE");
+        assert_eq!(row.identifier, "Bread");
+        assert_eq!(row.quantity, "12");
+        assert!(rows[1].1.is_err());
+        assert!(rows[2].1.is_err());
+    }
+
+    #[test]
+    fn quoted_csv_field_keeps_its_comma() {
+        let rows = parse_rows("CONSUME,\"Bread, sliced\",3\n", RowFormat::Csv);
+        assert_eq!(rows.len(), 1);
+        let row = rows[0].1.as_ref().unwrap();
+        assert_eq!(row.identifier, "Bread, sliced");
+        assert_eq!(row.quantity, "3");
+    }
+
+    #[test]
+    fn csv_header_is_told_by_its_first_column() {
+        let rows = parse_rows(
+            "command,identifier,quantity\n\n# morning\nPRODUCE,Bread,12\n",
+            RowFormat::Csv,
+        );
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].0, 4);
+        let rows = parse_rows(
+            "\"Command\",\"Identifier\",\"Quantity\"\nPRODUCE,Bread,12\n",
+            RowFormat::Csv,
+        );
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].0, 2);
+        let rows = parse_rows("PRODUCE,Bread,12\nCONSUME,Bread,2\n", RowFormat::Csv);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].0, 1);
+    }
+
+    #[test]
+    fn json_rows_take_text_or_numbers() {
+        let row = parse_json_row(
+            "{\"command\": \"CONSUME\", \"identifier\": \"Bread\", \"quantity\": 3}",
+        )
+        .unwrap();
+        assert_eq!(row.command, "CONSUME");
+        assert_eq!(row.identifier, "Bread");
+        assert_eq!(row.quantity, "3");
+        let row = parse_json_row(
+            "{\"command\": \"PRODUCE\", \"identifier\": \"Bread\", \"quantity\": \"12\"}",
+        )
+        .unwrap();
+        assert_eq!(row.quantity, "12");
+        assert!(parse_json_row("{\"command\": \"PRODUCE\", \"identifier\": \"Bread\"}").is_err());
+        assert!(parse_json_row("PRODUCE,Bread,12").is_err());
+    }
+
+    #[test]
+    fn batches_are_cut_at_the_size() {
+        let transactions: Vec<(usize, &str)> = (0..25).map(|index| (index * 2, "tx")).collect();
+        let batches = split_in_batches(&transactions, 10);
+        let sizes: Vec<usize> = batches.iter().map(|(chunk, _)| chunk.len()).collect();
+        assert_eq!(sizes, vec![10, 10, 5]);
+        assert_eq!(batches[2].1, vec![40, 42, 44, 46, 48]);
+        assert_eq!(split_in_batches(&transactions, MAX_BATCH_SIZE).len(), 1);
+        assert_eq!(split_in_batches(&transactions[..20], 10).len(), 2);
+        assert!(split_in_batches::<&str>(&[], 10).is_empty());
+    }
+}
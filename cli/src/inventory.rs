@@ -0,0 +1,180 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::cli_error::CliError;
+use crate::network_helper::{self, RestApi};
+use crate::produce_consume::Deployment;
+use crate::proto::state::Item;
+use produce_consume_core::addressing::{compute_address, get_item_prefix};
+use produce_consume_core::codec;
+use serde_json::Value;
+
+/// Order of the listed items
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum SortKey {
+    Identifier,
+    /// Largest quantity first
+    Quantity,
+}
+
+/// How the listed items are printed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum OutputFormat {
+    Table,
+    Json,
+}
+
+/// Prints the quantity of the item in the store as of the head block, the current one
+/// if none is given. An item that was never produced is reported as not found.
+pub(crate) fn show_item(
+    rest_api: &RestApi,
+    identifier: &str,
+    head: Option<&str>,
+    deployment: &Deployment,
+) -> Result<(), CliError> {
+    let address = compute_address(&deployment.namespace_prefix(), identifier);
+    let entry = network_helper::get_state_entry_at(rest_api, &address, head)?;
+    match head {
+        Some(head) => println!("Item {} at block {}", identifier, head),
+        None => println!("Item {}", identifier),
+    }
+    println!("  address:  {}", address);
+    match quantity_of(identifier, &address, entry.as_ref().map(Vec::as_slice))? {
+        Some(quantity) => println!("  quantity: {}", quantity),
+        None => println!("  quantity: 0 (not found, the item was never produced)"),
+    }
+    Ok(())
+}
+
+/// Quantity in the entry read at the address of the item, none if there is no entry
+fn quantity_of(
+    identifier: &str,
+    address: &str,
+    entry: Option<&[u8]>,
+) -> Result<Option<i32>, CliError> {
+    match entry {
+        Some(data) => {
+            let item = codec::decode_item(identifier, data).map_err(|err| {
+                CliError::from(format!("Unable to decode the item at {}: {}", address, err))
+            })?;
+            Ok(Some(item.get_quantity()))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Prints every item of the namespace, the entries are read a page at a time. The items
+/// written before the identifier was stored are listed by their address only.
+pub(crate) fn list_items(
+    rest_api: &RestApi,
+    sort: SortKey,
+    format: OutputFormat,
+    page_size: Option<usize>,
+    deployment: &Deployment,
+) -> Result<(), CliError> {
+    let prefix = deployment.namespace_prefix();
+    let entries =
+        network_helper::get_state_entries_paged(rest_api, &get_item_prefix(&prefix), page_size)?;
+    let mut items = Vec::new();
+    for (address, data) in entries {
+        let item = codec::decode_item_at(&prefix, &address, &data).map_err(|err| {
+            CliError::from(format!("Unable to decode the item at {}: {}", address, err))
+        })?;
+        items.push((address, item));
+    }
+    match sort {
+        SortKey::Identifier => items.sort_by(|(a_address, a), (b_address, b)| {
+            (a.get_identifier(), a_address).cmp(&(b.get_identifier(), b_address))
+        }),
+        SortKey::Quantity => items.sort_by(|(_, a), (_, b)| {
+            (b.get_quantity(), a.get_identifier()).cmp(&(a.get_quantity(), b.get_identifier()))
+        }),
+    }
+
+    match format {
+        OutputFormat::Table => print_table(&items),
+        OutputFormat::Json => print_json(&items)?,
+    }
+    Ok(())
+}
+
+fn print_table(items: &[(String, Item)]) {
+    println!("{:<20} {:>12} {}", "IDENTIFIER", "QUANTITY", "ADDRESS");
+    for (address, item) in items {
+        let identifier = if item.get_identifier().is_empty() {
+            "(unknown)"
+        } else {
+            item.get_identifier()
+        };
+        println!("{:<20} {:>12} {}", identifier, item.get_quantity(), address);
+    }
+}
+
+fn print_json(items: &[(String, Item)]) -> Result<(), CliError> {
+    let rows: Vec<Value> = items
+        .iter()
+        .map(|(address, item)| {
+            let identifier = if item.get_identifier().is_empty() {
+                Value::Null
+            } else {
+                Value::from(item.get_identifier())
+            };
+            serde_json::json!({
+                "identifier": identifier,
+                "quantity": item.get_quantity(),
+                "address": address,
+            })
+        })
+        .collect();
+    let output =
+        serde_json::to_string_pretty(&rows).map_err(|err| CliError::from(err.to_string()))?;
+    println!("{}", output);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "ce2292d27d94aaf72ae1699aa78dbc3323a48a42903ce05e5c48ba280cc8a9c1da9d2a";
+
+    #[test]
+    fn missing_entry_is_not_found() {
+        assert_eq!(quantity_of("Bread", ADDRESS, None).unwrap(), None);
+    }
+
+    #[test]
+    fn consumed_item_is_found_with_zero() {
+        let data = codec::encode_item("Bread", 0).unwrap();
+        assert_eq!(
+            quantity_of("Bread", ADDRESS, Some(data.as_slice())).unwrap(),
+            Some(0)
+        );
+        let data = codec::encode_item("Bread", 12).unwrap();
+        assert_eq!(
+            quantity_of("Bread", ADDRESS, Some(data.as_slice())).unwrap(),
+            Some(12)
+        );
+    }
+
+    #[test]
+    fn former_entries_are_decoded() {
+        let data = codec::encode_quantity(7);
+        assert_eq!(
+            quantity_of("Bread", ADDRESS, Some(data.as_slice())).unwrap(),
+            Some(7)
+        );
+        assert!(quantity_of("Bread", ADDRESS, Some(&[1, 2, 3][..])).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::cli_error::CliError;
+use sawtooth_sdk::signing::{create_context, secp256k1::Secp256k1PrivateKey};
+use std::env;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+
+/// Length in hex of a secp256k1 private key
+const PRIVATE_KEY_HEX_LENGTH: usize = 64;
+
+/// Directory of the keys, the same one as the ```sawtooth keygen``` command
+pub(crate) fn default_key_dir() -> Result<PathBuf, CliError> {
+    match env::var_os("HOME") {
+        Some(home) => Ok(PathBuf::from(home).join(".sawtooth").join("keys")),
+        None => Err(CliError::from(
+            "HOME is not set, give the directory of the keys with --key-dir",
+        )),
+    }
+}
+
+/// Path of the private key to sign with. The file given with ```--key``` is used as is,
+/// a name given with ```--key-name``` is looked up in the key directory. Without either
+/// the key named after the user is used, as the sawtooth tools do. There is no fallback
+/// to the key of the validator, it would sign everything as the validator.
+pub(crate) fn private_key_path(
+    key: Option<&str>,
+    key_name: Option<&str>,
+) -> Result<String, CliError> {
+    if let Some(key) = key {
+        return Ok(key.to_string());
+    }
+    if let Some(key_name) = key_name {
+        let path = default_key_dir()?.join(format!("{}.priv", key_name));
+        if !path.exists() {
+            return Err(CliError::from(format!(
+                "No key named {} in {}, create it with pc-cli keygen {}",
+                key_name,
+                path.parent().unwrap_or(&path).display(),
+                key_name
+            )));
+        }
+        return Ok(path.to_string_lossy().into_owned());
+    }
+    let user = env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .map_err(|_| CliError::from("No signing key, give one with --key or --key-name"))?;
+    let path = default_key_dir()?.join(format!("{}.priv", user));
+    if !path.exists() {
+        return Err(CliError::from(format!(
+            "No signing key, create {} with pc-cli keygen {} or give one with --key or \
+             --key-name",
+            path.display(),
+            user
+        )));
+    }
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// Reads the private key in hex stored in the file, whitespace around it is ignored
+pub(crate) fn read_private_key(path: &str) -> Result<Secp256k1PrivateKey, CliError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|err| CliError::from(format!("Unable to read the key {}: {}", path, err)))?;
+    parse_private_key(&contents)
+        .map_err(|err| CliError::from(format!("Invalid private key in {}: {}", path, err)))
+}
+
+fn parse_private_key(contents: &str) -> Result<Secp256k1PrivateKey, String> {
+    let key = contents.trim();
+    if let Some(c) = key.chars().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(format!("{:?} is not a hex digit", c));
+    }
+    if key.len() != PRIVATE_KEY_HEX_LENGTH {
+        return Err(format!(
+            "expected {} hex digits, found {}",
+            PRIVATE_KEY_HEX_LENGTH,
+            key.len()
+        ));
+    }
+    Secp256k1PrivateKey::from_hex(key).map_err(|err| err.to_string())
+}
+
+/// Creates a secp256k1 key pair, ```<name>.priv``` and ```<name>.pub``` in hex. Existing
+/// keys are only replaced if ```force``` is set.
+pub(crate) fn generate(name: &str, key_dir: Option<&str>, force: bool) -> Result<(), CliError> {
+    if name.is_empty() || name.contains(|c| c == '/' || c == '\\') || name.starts_with('.') {
+        return Err(CliError::from(format!("Invalid key name {:?}", name)));
+    }
+    let key_dir = match key_dir {
+        Some(key_dir) => PathBuf::from(key_dir),
+        None => default_key_dir()?,
+    };
+    let private_path = key_dir.join(format!("{}.priv", name));
+    let public_path = key_dir.join(format!("{}.pub", name));
+    if !force {
+        for path in &[&private_path, &public_path] {
+            if path.exists() {
+                return Err(CliError::from(format!(
+                    "{} already exists, use --force to replace it",
+                    path.display()
+                )));
+            }
+        }
+    }
+
+    let context = create_context("secp256k1").map_err(|err| CliError::from(err.to_string()))?;
+    let private_key = context
+        .new_random_private_key()
+        .map_err(|err| CliError::from(err.to_string()))?;
+    let public_key = context
+        .get_public_key(private_key.as_ref())
+        .map_err(|err| CliError::from(err.to_string()))?;
+
+    fs::create_dir_all(&key_dir).map_err(|err| {
+        CliError::from(format!("Unable to create {}: {}", key_dir.display(), err))
+    })?;
+    // Only the owner can read the private key
+    write_key(&private_path, &private_key.as_hex(), 0o600)?;
+    write_key(&public_path, &public_key.as_hex(), 0o644)?;
+    println!("Wrote the private key to {}", private_path.display());
+    println!("Wrote the public key to {}", public_path.display());
+    Ok(())
+}
+
+fn write_key(path: &Path, key: &str, mode: u32) -> Result<(), CliError> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(mode);
+    #[cfg(not(unix))]
+    let _ = mode;
+    options
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{}", key))
+        .map_err(|err| CliError::from(format!("Unable to write {}: {}", path.display(), err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sawtooth_sdk::signing::PrivateKey;
+
+    const KEY: &str = "2f1e7b7a130d7ba9da0068b3bb0ba1d79e7e77110302c9f746c3c2a63fe40088";
+
+    #[test]
+    fn whitespace_around_the_key_is_ignored() {
+        let key = parse_private_key(&format!("  {}\n", KEY)).unwrap();
+        assert_eq!(key.as_hex(), KEY);
+        let key = parse_private_key(&format!("{}\r\n", KEY)).unwrap();
+        assert_eq!(key.as_hex(), KEY);
+    }
+
+    #[test]
+    fn keys_are_hex_only() {
+        assert!(parse_private_key(&KEY.replace("2f", "zz")).is_err());
+        assert!(parse_private_key(&format!("{} {}", &KEY[..32], &KEY[32..])).is_err());
+        assert!(parse_private_key("").is_err());
+    }
+
+    #[test]
+    fn keys_have_64_hex_digits() {
+        assert!(parse_private_key(&KEY[2..]).is_err());
+        assert!(parse_private_key(&format!("{}00", KEY)).is_err());
+    }
+
+    #[test]
+    fn key_file_is_used_as_given() {
+        assert_eq!(
+            private_key_path(Some("/keys/alice.priv"), None).unwrap(),
+            "/keys/alice.priv"
+        );
+    }
+}
@@ -12,34 +12,245 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+extern crate base64;
 extern crate clap;
+extern crate csv;
 extern crate futures;
 extern crate hex;
 extern crate hyper;
+extern crate produce_consume_core;
 extern crate rand;
 extern crate sawtooth_sdk;
+extern crate serde;
+extern crate serde_json;
 extern crate tokio;
+extern crate toml;
 
+mod batch;
 mod cli_error;
+mod config;
+mod import;
+mod inventory;
+mod keys;
 mod network_helper;
 mod produce_consume;
-mod proto;
+mod proposal;
+mod rate_limit;
 mod sawtooth_helper;
+mod summary;
+mod transfer;
+mod valuation;
 
+use crate::config::{CliConfig, Profile};
+use crate::network_helper::RestApi;
+use crate::produce_consume::{Deployment, Submitter};
+use crate::proto::action::Action_Command;
 use clap::App;
+use clap::AppSettings;
 use clap::Arg;
+use clap::ArgMatches;
+use clap::SubCommand;
+use produce_consume_core::proto;
 
 fn main() {
-    let matches = App::new("pc-cli")
+    let matches = app().get_matches();
+
+    let profile = load_profile(&matches);
+    let profile = &profile;
+    let deployment = &deployment(&matches, profile);
+
+    if let Some(produce_matches) = matches.subcommand_matches("produce") {
+        submit(
+            Action_Command::PRODUCE,
+            produce_matches,
+            profile,
+            deployment,
+        );
+        return;
+    }
+    if let Some(consume_matches) = matches.subcommand_matches("consume") {
+        submit(
+            Action_Command::CONSUME,
+            consume_matches,
+            profile,
+            deployment,
+        );
+        return;
+    }
+    if let Some(show_matches) = matches.subcommand_matches("show") {
+        let result = match show_matches.value_of("url").or(profile.url()) {
+            Some(url) => RestApi::new(url).and_then(|rest_api| {
+                inventory::show_item(
+                    &rest_api,
+                    show_matches.value_of("identifier").unwrap(),
+                    show_matches.value_of("head"),
+                    deployment,
+                )
+            }),
+            None => Err(cli_error::CliError::from(
+                "The REST API URL is required to show an item",
+            )),
+        };
+        if let Err(err) = result {
+            println!("Unable to show the item {}", err);
+            std::process::exit(err.exit_code());
+        }
+        return;
+    }
+    if let Some(list_matches) = matches.subcommand_matches("list") {
+        let result = match list_matches.value_of("url").or(profile.url()) {
+            Some(url) => {
+                let sort = match list_matches.value_of("sort") {
+                    Some("quantity") => inventory::SortKey::Quantity,
+                    _ => inventory::SortKey::Identifier,
+                };
+                let format = match list_matches.value_of("format").or(profile.output()) {
+                    Some("json") => inventory::OutputFormat::Json,
+                    _ => inventory::OutputFormat::Table,
+                };
+                let page_size = list_matches
+                    .value_of("page_size")
+                    .map(|value| value.parse().unwrap());
+                RestApi::new(url).and_then(|rest_api| {
+                    inventory::list_items(&rest_api, sort, format, page_size, deployment)
+                })
+            }
+            None => Err(cli_error::CliError::from(
+                "The REST API URL is required to list the items",
+            )),
+        };
+        if let Err(err) = result {
+            println!("Unable to list the items {}", err);
+            std::process::exit(err.exit_code());
+        }
+        return;
+    }
+    if let Some(import_matches) = matches.subcommand_matches("import") {
+        let format = match import_matches.value_of("format") {
+            Some("csv") => Some(import::RowFormat::Csv),
+            Some("ndjson") => Some(import::RowFormat::Ndjson),
+            _ => None,
+        };
+        let batch_size = import_matches
+            .value_of("batch_size")
+            .map(|value| value.parse().unwrap())
+            .unwrap_or(import::DEFAULT_BATCH_SIZE);
+        let result = match import_matches.value_of("url").or(profile.url()) {
+            Some(url) => RestApi::new(url).and_then(|rest_api| {
+                import::import_file(
+                    import_matches.value_of("file").unwrap(),
+                    format,
+                    batch_size,
+                    &Submitter {
+                        rest_api,
+                        key: signing_key(import_matches, profile),
+                    },
+                    deployment,
+                )
+            }),
+            None => Err(cli_error::CliError::from(
+                "The REST API URL is required to import",
+            )),
+        };
+        if let Err(err) = result {
+            println!("Unable to import the rows {}", err);
+            std::process::exit(err.exit_code());
+        }
+        return;
+    }
+    if let Some(batch_matches) = matches.subcommand_matches("batch") {
+        handle_batch(batch_matches, profile, deployment);
+        return;
+    }
+    if let Some(keygen_matches) = matches.subcommand_matches("keygen") {
+        let result = keys::generate(
+            keygen_matches.value_of("name").unwrap(),
+            keygen_matches.value_of("key_dir"),
+            keygen_matches.is_present("force"),
+        );
+        if let Err(err) = result {
+            println!("Unable to create the key {}", err);
+            std::process::exit(err.exit_code());
+        }
+        return;
+    }
+    if let Some(limit_matches) = matches.subcommand_matches("rate-limit") {
+        let url = limit_matches.value_of("url").or(profile.url());
+        let result = submitter(url, limit_matches, profile).and_then(|submitter| {
+            rate_limit::set_limit(
+                limit_matches.value_of("window_blocks").unwrap(),
+                limit_matches.value_of("max_transactions").unwrap(),
+                limit_matches.value_of("max_quantity").unwrap(),
+                submitter.as_ref(),
+                deployment,
+            )
+        });
+        if let Err(err) = result {
+            println!("Unable to set the rate limit {}", err);
+            std::process::exit(err.exit_code());
+        }
+        return;
+    }
+    if let Some(summary_matches) = matches.subcommand_matches("summary") {
+        let result = match summary_matches.value_of("url").or(profile.url()) {
+            Some(url) => RestApi::new(url).and_then(|rest_api| {
+                summary::print_summary(&rest_api, summary_matches.value_of("category"), deployment)
+            }),
+            None => Err(cli_error::CliError::from(
+                "The REST API URL is required for the summary",
+            )),
+        };
+        if let Err(err) = result {
+            println!("Unable to show the summary {}", err);
+            std::process::exit(err.exit_code());
+        }
+        return;
+    }
+    if let Some(transfer_matches) = matches.subcommand_matches("transfer") {
+        handle_transfer(transfer_matches, profile, deployment);
+        return;
+    }
+    if let Some(valuation_matches) = matches.subcommand_matches("valuation") {
+        handle_valuation(valuation_matches, profile, deployment);
+        return;
+    }
+    if let Some(proposal_matches) = matches.subcommand_matches("proposal") {
+        handle_proposal(proposal_matches, profile, deployment);
+        return;
+    }
+
+    submit(legacy_command(&matches), &matches, profile, deployment);
+}
+
+/// Command given by the command flags, clap requires them without a subcommand
+fn legacy_command(matches: &ArgMatches) -> Action_Command {
+    match matches.value_of("command") {
+        Some("PRODUCE") => Action_Command::PRODUCE,
+        Some("CONSUME") => Action_Command::CONSUME,
+        other => unreachable!("Unexpected command {:?}", other),
+    }
+}
+
+/// The arguments and subcommands, the command flags before them are the legacy form
+fn app() -> App<'static, 'static> {
+    App::new("pc-cli")
         .author("Walmart Inc.")
         .version("1.0")
         .about("Sample sawtooth-sabre smart contract produce-consume cli")
+        // The command flags are the form used before the subcommands, they are kept for
+        // the existing scripts and not required with a subcommand
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .after_help(
+            "The -C, -I and -Q flags are kept for compatibility, \
+         prefer the produce and consume subcommands.",
+        )
         .arg(
             Arg::with_name("command")
                 .short("C")
                 .long("command")
                 .help("Command either PRODUCE or CONSUME")
                 .takes_value(true)
+                .possible_values(&["PRODUCE", "CONSUME"])
                 .required(true),
         )
         .arg(
@@ -65,7 +276,8 @@ fn main() {
                 .long("url")
                 .help("URL of the validator to send the request")
                 .takes_value(true)
-                .required(false),
+                .required(false)
+                .global(true),
         )
         .arg(
             Arg::with_name("key")
@@ -73,24 +285,894 @@ fn main() {
                 .long("key")
                 .help("Key used for signing the transaction")
                 .takes_value(true)
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("key_name")
+                .long("key-name")
+                .help("Name of a key created by keygen, signs with <name>.priv of ~/.sawtooth/keys")
+                .takes_value(true)
+                .required(false)
+                .conflicts_with("key")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("wait")
+                .long("wait")
+                .help("Seconds to wait for the submitted batches to be committed, 30 by default")
+                .takes_value(true)
+                .required(false)
+                .validator(is_seconds)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("profile")
+                .long("profile")
+                .help("Profile of ~/.config/pc-cli/config.toml to take the settings from")
+                .takes_value(true)
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("family_name")
+                .long("family-name")
+                .help("Family name the processor is registered for, produce-consume by default")
+                .takes_value(true)
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("category")
+                .long("category")
+                .help("Category of the produced item, set by its first PRODUCE")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("unit_cost")
+                .short("P")
+                .long("unit-cost")
+                .help("Cost of one unit of the produced item in the smallest currency unit")
+                .takes_value(true)
                 .required(false),
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("valid_after")
+                .long("valid-after")
+                .help("Unix timestamp in seconds, the action is rejected in blocks before it")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("valid_before")
+                .long("valid-before")
+                .help("Unix timestamp in seconds, the action is rejected in blocks from it on")
+                .takes_value(true)
+                .required(false),
+        )
+        .subcommand(
+            SubCommand::with_name("produce")
+                .about("Produce a quantity of an item, it is added to the holding of the signer")
+                .arg(identifier_arg("Identifier of the produced item"))
+                .arg(quantity_arg("Quantity of the produced item"))
+                .arg(
+                    Arg::with_name("category")
+                        .long("category")
+                        .help("Category of the item, set by its first PRODUCE")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("unit_cost")
+                        .short("P")
+                        .long("unit-cost")
+                        .help("Cost of one unit in the smallest currency unit")
+                        .takes_value(true)
+                        .validator(is_unit_cost),
+                )
+                .args(&validity_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("consume")
+                .about("Consume a quantity of an item from the holding of the signer")
+                .long_about(
+                    "Consume a quantity of an item from the holding of the signer. A \
+                 consumption above the threshold of the approval policy becomes a \
+                 proposal, its identifier is printed.",
+                )
+                .arg(identifier_arg("Identifier of the consumed item"))
+                .arg(quantity_arg("Quantity of the consumed item"))
+                .args(&validity_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("show")
+                .about("Show the quantity of an item in the store")
+                .arg(identifier_arg("Identifier of the item"))
+                .arg(
+                    Arg::with_name("head")
+                        .long("head")
+                        .help("Id of the block to read the state of, the latest by default")
+                        .takes_value(true)
+                        .validator(is_block_id),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("list")
+                .about("List all the items of the namespace with their quantities")
+                .arg(
+                    Arg::with_name("sort")
+                        .long("sort")
+                        .takes_value(true)
+                        .possible_values(&["identifier", "quantity"])
+                        .default_value("identifier")
+                        .help("Order of the items, quantities are listed largest first"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["table", "json"])
+                        .help("Print a table or a JSON array, a table by default"),
+                )
+                .arg(
+                    Arg::with_name("page_size")
+                        .long("page-size")
+                        .takes_value(true)
+                        .validator(is_page_size)
+                        .help("Number of entries read per request to the REST API"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Submit the PRODUCE and CONSUME rows of a CSV or NDJSON file")
+                .arg(
+                    Arg::with_name("file")
+                        .help("File of command,identifier,quantity rows")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["csv", "ndjson"])
+                        .help("Format of the rows, told by the file extension by default"),
+                )
+                .arg(
+                    Arg::with_name("batch_size")
+                        .long("batch-size")
+                        .takes_value(true)
+                        .validator(is_batch_size)
+                        .help("Transactions per batch, 10 by default and at most 100"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("batch")
+                .about("Work with batches built outside of this command")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("submit")
+                        .about("Submit the batch list serialized in a file to the REST API")
+                        .arg(
+                            Arg::with_name("file")
+                                .help("File holding a serialized BatchList")
+                                .required(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("keygen")
+                .about("Create a secp256k1 key pair for signing")
+                .arg(
+                    Arg::with_name("name")
+                        .help("Name of the key, the files are <name>.priv and <name>.pub")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("key_dir")
+                        .long("key-dir")
+                        .help("Directory of the keys, ~/.sawtooth/keys by default")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .long("force")
+                        .help("Replace the key pair if it already exists"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("proposal")
+                .about("Manage consumptions waiting for approvals")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(SubCommand::with_name("list").about("List all the proposals"))
+                .subcommand(
+                    SubCommand::with_name("approve")
+                        .about("Approve a pending proposal")
+                        .arg(
+                            Arg::with_name("proposal_id")
+                                .help("Identifier of the proposal")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("reject")
+                        .about("Reject a pending proposal")
+                        .arg(
+                            Arg::with_name("proposal_id")
+                                .help("Identifier of the proposal")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("set-policy")
+                        .about("Set the approval policy, administrators only")
+                        .arg(
+                            Arg::with_name("threshold")
+                                .long("threshold")
+                                .help("Consumptions above this quantity need approvals, 0 disables")
+                                .takes_value(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("required_approvals")
+                                .long("required-approvals")
+                                .help("Number of distinct approvals to execute a proposal")
+                                .takes_value(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("approver")
+                                .long("approver")
+                                .help("Public key of an approver, can be repeated")
+                                .takes_value(true)
+                                .multiple(true)
+                                .number_of_values(1),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("valuation")
+                .about("Report the value of the on-hand inventory")
+                .subcommand(
+                    SubCommand::with_name("set-policy")
+                        .about("Set the valuation policy, administrators only")
+                        .arg(
+                            Arg::with_name("policy")
+                                .help("The valuation policy")
+                                .possible_values(&["FIFO", "WEIGHTED_AVERAGE"])
+                                .required(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("transfer")
+                .about("Transfer a quantity to another owner, the recipient must accept it")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("offer")
+                        .about("Offer a quantity held by the signer, it is held in escrow")
+                        .arg(identifier_arg("Identifier of the offered item"))
+                        .arg(quantity_arg("Quantity of the offered item"))
+                        .arg(
+                            Arg::with_name("recipient")
+                                .long("recipient")
+                                .help("Public key of the recipient")
+                                .takes_value(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("expires_at_block")
+                                .long("expires-at-block")
+                                .help("Block number from which the offer cannot be accepted")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("accept")
+                        .about("Accept an offered transfer, recipient only")
+                        .arg(
+                            Arg::with_name("transfer_id")
+                                .help("Identifier of the transfer")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("decline")
+                        .about("Decline an offered transfer, recipient only")
+                        .arg(
+                            Arg::with_name("transfer_id")
+                                .help("Identifier of the transfer")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("withdraw")
+                        .about("Withdraw an offered transfer, sender only")
+                        .arg(
+                            Arg::with_name("transfer_id")
+                                .help("Identifier of the transfer")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("show")
+                        .about("Show a transfer, an offer past its expiry block is expired")
+                        .arg(
+                            Arg::with_name("transfer_id")
+                                .help("Identifier of the transfer")
+                                .required(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("summary")
+                .about("Show the totals per category and across all the items")
+                .arg(
+                    Arg::with_name("category")
+                        .long("category")
+                        .help("Show only the totals of this category")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("rate-limit")
+                .about("Set the per signer limits per window of blocks, administrators only")
+                .arg(
+                    Arg::with_name("window_blocks")
+                        .long("window-blocks")
+                        .help("Size of the window in blocks, 0 disables the rate limiting")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(is_block_count),
+                )
+                .arg(
+                    Arg::with_name("max_transactions")
+                        .long("max-transactions")
+                        .help("Maximum transactions per signer in a window, 0 if unlimited")
+                        .takes_value(true)
+                        .default_value("0")
+                        .validator(is_transaction_count),
+                )
+                .arg(
+                    Arg::with_name("max_quantity")
+                        .long("max-quantity")
+                        .help("Maximum total quantity per signer in a window, 0 if unlimited")
+                        .takes_value(true)
+                        .default_value("0")
+                        .validator(is_max_quantity),
+                ),
+        )
+}
 
+/// Submits the PRODUCE or CONSUME given by the subcommand or by the command flags
+fn submit(
+    command: Action_Command,
+    matches: &ArgMatches,
+    profile: &Profile,
+    deployment: &Deployment,
+) {
     // This is a CLI application, an irrecoverable error occurs if the input is not good
-    let command = matches.value_of("command").unwrap();
-    let identifier = matches.value_of("identifier").unwrap();
-    let quantity = matches.value_of("quantity").unwrap();
-    let url = matches.value_of("url");
-    let key = matches
-        .value_of("key")
-        .unwrap_or("/etc/sawtooth/keys/validator.priv");
-
-    match produce_consume::submit_payload(command, identifier, quantity, url, key) {
+    let url = matches.value_of("url").or(profile.url());
+    let result = submitter(url, matches, profile).and_then(|submitter| {
+        produce_consume::submit_payload(
+            command,
+            matches.value_of("identifier").unwrap(),
+            matches.value_of("quantity").unwrap(),
+            matches.value_of("category"),
+            matches.value_of("unit_cost"),
+            matches.value_of("valid_after"),
+            matches.value_of("valid_before"),
+            submitter.as_ref(),
+            deployment,
+        )
+    });
+    match result {
         Ok(_) => println!("Successfully submitted the transaction"),
         Err(err) => {
             println!("Unable to submit the transaction {}", err);
+            std::process::exit(err.exit_code());
+        }
+    }
+}
+
+fn identifier_arg(help: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name("identifier")
+        .help(help)
+        .required(true)
+        .validator(is_identifier)
+}
+
+fn quantity_arg(help: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name("quantity")
+        .help(help)
+        .required(true)
+        .validator(is_quantity)
+}
+
+/// The optional validity window of a PRODUCE or a CONSUME
+fn validity_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("valid_after")
+            .long("valid-after")
+            .help("Unix timestamp in seconds, the action is rejected in blocks before it")
+            .takes_value(true)
+            .validator(is_timestamp),
+        Arg::with_name("valid_before")
+            .long("valid-before")
+            .help("Unix timestamp in seconds, the action is rejected in blocks from it on")
+            .takes_value(true)
+            .validator(is_timestamp),
+    ]
+}
+
+fn is_identifier(value: String) -> Result<(), String> {
+    if value.trim().is_empty() {
+        return Err("the identifier cannot be empty".to_string());
+    }
+    Ok(())
+}
+
+fn is_quantity(value: String) -> Result<(), String> {
+    match value.parse::<i32>() {
+        Ok(quantity) if quantity > 0 => Ok(()),
+        Ok(_) => Err("the quantity must be positive".to_string()),
+        Err(err) => Err(format!("invalid quantity: {}", err)),
+    }
+}
+
+fn is_batch_size(value: String) -> Result<(), String> {
+    match value.parse::<usize>() {
+        Ok(size) if size > 0 && size <= import::MAX_BATCH_SIZE => Ok(()),
+        Ok(_) => Err(format!(
+            "the batch size must be between 1 and {}",
+            import::MAX_BATCH_SIZE
+        )),
+        Err(err) => Err(format!("invalid batch size: {}", err)),
+    }
+}
+
+fn is_seconds(value: String) -> Result<(), String> {
+    value
+        .parse::<u64>()
+        .map(|_| ())
+        .map_err(|err| format!("invalid number of seconds: {}", err))
+}
+
+/// The REST API returns at most 1000 entries per page
+fn is_page_size(value: String) -> Result<(), String> {
+    match value.parse::<usize>() {
+        Ok(size) if size > 0 && size <= 1000 => Ok(()),
+        Ok(_) => Err("the page size must be between 1 and 1000".to_string()),
+        Err(err) => Err(format!("invalid page size: {}", err)),
+    }
+}
+
+fn is_unit_cost(value: String) -> Result<(), String> {
+    match value.parse::<i64>() {
+        Ok(cost) if cost >= 0 => Ok(()),
+        Ok(_) => Err("the unit cost cannot be negative".to_string()),
+        Err(err) => Err(format!("invalid unit cost: {}", err)),
+    }
+}
+
+/// Block ids are the 128 hex characters of the signature of the block header
+fn is_block_id(value: String) -> Result<(), String> {
+    if value.len() != 128 || !value.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("a block id is 128 hexadecimal characters".to_string());
+    }
+    Ok(())
+}
+
+fn is_block_count(value: String) -> Result<(), String> {
+    value
+        .parse::<u64>()
+        .map(|_| ())
+        .map_err(|err| format!("invalid number of blocks: {}", err))
+}
+
+fn is_transaction_count(value: String) -> Result<(), String> {
+    value
+        .parse::<u32>()
+        .map(|_| ())
+        .map_err(|err| format!("invalid number of transactions: {}", err))
+}
+
+fn is_max_quantity(value: String) -> Result<(), String> {
+    match value.parse::<i64>() {
+        Ok(quantity) if quantity >= 0 => Ok(()),
+        Ok(_) => Err("the maximum quantity cannot be negative".to_string()),
+        Err(err) => Err(format!("invalid maximum quantity: {}", err)),
+    }
+}
+
+fn is_timestamp(value: String) -> Result<(), String> {
+    value
+        .parse::<u64>()
+        .map(|_| ())
+        .map_err(|err| format!("invalid timestamp: {}", err))
+}
+
+/// The profile selected by --profile or PC_CLI_PROFILE. The PC_CLI_* variables replace
+/// its settings and the arguments of the command line replace both, where they are read.
+fn load_profile(matches: &ArgMatches) -> Profile {
+    let name = global_value_of(matches, "profile")
+        .map(|name| name.to_string())
+        .or_else(|| std::env::var("PC_CLI_PROFILE").ok());
+    let profile = CliConfig::load().and_then(|config| {
+        let mut profile = config.profile(name.as_ref().map(String::as_str))?;
+        profile.apply_env()?;
+        Ok(profile)
+    });
+    match profile {
+        Ok(profile) => profile,
+        Err(err) => {
+            println!("Unable to load the profile {}", err);
             std::process::exit(1);
         }
     }
 }
+
+/// The family named on the command line or in the profile and the wait of the command
+/// line, the defaults otherwise
+fn deployment(matches: &ArgMatches, profile: &Profile) -> Deployment {
+    let mut deployment = Deployment::default();
+    let family_name = global_value_of(matches, "family_name")
+        .or_else(|| profile.family_name.as_ref().map(String::as_str));
+    if let Some(family_name) = family_name {
+        deployment.family_name = family_name.to_string();
+    }
+    if let Some(family_version) = &profile.family_version {
+        deployment.family_version = family_version.clone();
+    }
+    if let Some(wait) = global_value_of(matches, "wait") {
+        deployment.wait = wait.parse().unwrap();
+    }
+    deployment
+}
+
+/// Path of the private key given by --key or --key-name, or the default one
+fn signing_key(matches: &ArgMatches, profile: &Profile) -> String {
+    // The key of the command line replaces the key of the profile, given by file or name
+    let key = match (
+        global_value_of(matches, "key"),
+        global_value_of(matches, "key_name"),
+    ) {
+        (None, None) => keys::private_key_path(
+            profile.key.as_ref().map(String::as_str),
+            profile.key_name.as_ref().map(String::as_str),
+        ),
+        (key, key_name) => keys::private_key_path(key, key_name),
+    };
+    match key {
+        Ok(key) => key,
+        Err(err) => {
+            println!("Unable to find the signing key {}", err);
+            std::process::exit(err.exit_code());
+        }
+    }
+}
+
+/// The REST API and the signing key of a command that writes, if a URL is given. Without
+/// one the payload is saved for Sabre unsigned, the key is then neither needed nor looked up.
+fn submitter(
+    url: Option<&str>,
+    matches: &ArgMatches,
+    profile: &Profile,
+) -> Result<Option<Submitter>, cli_error::CliError> {
+    match url {
+        Some(url) => Ok(Some(Submitter {
+            rest_api: RestApi::new(url)?,
+            key: signing_key(matches, profile),
+        })),
+        None => Ok(None),
+    }
+}
+
+/// Value of a global argument, it can be given before or after the subcommands
+fn global_value_of<'a>(matches: &'a ArgMatches, name: &str) -> Option<&'a str> {
+    let mut value = matches.value_of(name);
+    let mut current = matches;
+    while let (_, Some(sub_matches)) = current.subcommand() {
+        if let Some(sub_value) = sub_matches.value_of(name) {
+            value = Some(sub_value);
+        }
+        current = sub_matches;
+    }
+    value
+}
+
+fn handle_batch(matches: &ArgMatches, profile: &Profile, deployment: &Deployment) {
+    let (name, sub_matches) = matches.subcommand();
+    let sub_matches = sub_matches.expect("Batch subcommand is required");
+
+    let result = match (name, sub_matches.value_of("url").or(profile.url())) {
+        ("submit", Some(url)) => RestApi::new(url).and_then(|rest_api| {
+            batch::submit_file(
+                sub_matches.value_of("file").unwrap(),
+                &rest_api,
+                deployment.wait,
+            )
+        }),
+        ("submit", None) => Err(cli_error::CliError::from(
+            "The REST API URL is required to submit",
+        )),
+        _ => Err(cli_error::CliError::from("Unknown batch subcommand")),
+    };
+    if let Err(err) = result {
+        println!("Unable to complete the batch command {}", err);
+        std::process::exit(err.exit_code());
+    }
+}
+
+fn handle_proposal(matches: &ArgMatches, profile: &Profile, deployment: &Deployment) {
+    let (name, sub_matches) = matches.subcommand();
+    let sub_matches = sub_matches.expect("Proposal subcommand is required");
+    let url = sub_matches.value_of("url").or(profile.url());
+
+    let result = match name {
+        "list" => match url {
+            Some(url) => RestApi::new(url)
+                .and_then(|rest_api| proposal::list_proposals(&rest_api, deployment)),
+            None => Err(cli_error::CliError::from(
+                "The REST API URL is required to list",
+            )),
+        },
+        "approve" | "reject" => submitter(url, sub_matches, profile).and_then(|submitter| {
+            proposal::vote(
+                sub_matches.value_of("proposal_id").unwrap(),
+                name == "approve",
+                submitter.as_ref(),
+                deployment,
+            )
+        }),
+        "set-policy" => submitter(url, sub_matches, profile).and_then(|submitter| {
+            proposal::set_policy(
+                sub_matches.value_of("threshold").unwrap(),
+                sub_matches.value_of("required_approvals").unwrap(),
+                sub_matches
+                    .values_of("approver")
+                    .map(|values| values.collect())
+                    .unwrap_or_else(Vec::new),
+                submitter.as_ref(),
+                deployment,
+            )
+        }),
+        _ => Err(cli_error::CliError::from("Unknown proposal subcommand")),
+    };
+    if let Err(err) = result {
+        println!("Unable to complete the proposal command {}", err);
+        std::process::exit(err.exit_code());
+    }
+}
+
+fn handle_valuation(matches: &ArgMatches, profile: &Profile, deployment: &Deployment) {
+    let url = global_value_of(matches, "url").or(profile.url());
+
+    let result = match matches.subcommand() {
+        ("set-policy", Some(sub_matches)) => {
+            submitter(url, matches, profile).and_then(|submitter| {
+                valuation::set_policy(
+                    sub_matches.value_of("policy").unwrap(),
+                    submitter.as_ref(),
+                    deployment,
+                )
+            })
+        }
+        _ => match url {
+            Some(url) => RestApi::new(url)
+                .and_then(|rest_api| valuation::print_report(&rest_api, deployment)),
+            None => Err(cli_error::CliError::from(
+                "The REST API URL is required for the report",
+            )),
+        },
+    };
+    if let Err(err) = result {
+        println!("Unable to complete the valuation command {}", err);
+        std::process::exit(err.exit_code());
+    }
+}
+
+fn handle_transfer(matches: &ArgMatches, profile: &Profile, deployment: &Deployment) {
+    let (name, sub_matches) = matches.subcommand();
+    let sub_matches = sub_matches.expect("Transfer subcommand is required");
+    let url = sub_matches.value_of("url").or(profile.url());
+
+    let result = match name {
+        "offer" => submitter(url, sub_matches, profile).and_then(|submitter| {
+            transfer::offer(
+                sub_matches.value_of("identifier").unwrap(),
+                sub_matches.value_of("quantity").unwrap(),
+                sub_matches.value_of("recipient").unwrap(),
+                sub_matches.value_of("expires_at_block"),
+                submitter.as_ref(),
+                deployment,
+            )
+        }),
+        "accept" | "decline" | "withdraw" => {
+            let command = match name {
+                "accept" => Action_Command::ACCEPT_TRANSFER,
+                "decline" => Action_Command::DECLINE_TRANSFER,
+                _ => Action_Command::WITHDRAW_TRANSFER,
+            };
+            submitter(url, sub_matches, profile).and_then(|submitter| {
+                transfer::respond(
+                    sub_matches.value_of("transfer_id").unwrap(),
+                    command,
+                    submitter.as_ref(),
+                    deployment,
+                )
+            })
+        }
+        "show" => match url {
+            Some(url) => RestApi::new(url).and_then(|rest_api| {
+                transfer::show(
+                    sub_matches.value_of("transfer_id").unwrap(),
+                    &rest_api,
+                    deployment,
+                )
+            }),
+            None => Err(cli_error::CliError::from(
+                "The REST API URL is required to show a transfer",
+            )),
+        },
+        _ => Err(cli_error::CliError::from("Unknown transfer subcommand")),
+    };
+    if let Err(err) = result {
+        println!("Unable to complete the transfer command {}", err);
+        std::process::exit(err.exit_code());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK_ID: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef\
+                            0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    fn parse(args: &[&str]) -> Result<ArgMatches<'static>, clap::Error> {
+        app().get_matches_from_safe(std::iter::once("pc-cli").chain(args.iter().cloned()))
+    }
+
+    #[test]
+    fn validators_accept_only_usable_values() {
+        assert!(is_identifier("Bread".to_string()).is_ok());
+        assert!(is_identifier(" ".to_string()).is_err());
+        assert!(is_quantity("5".to_string()).is_ok());
+        assert!(is_quantity("0".to_string()).is_err());
+        assert!(is_quantity("-5".to_string()).is_err());
+        assert!(is_quantity("five".to_string()).is_err());
+        assert!(is_batch_size("100".to_string()).is_ok());
+        assert!(is_batch_size("0".to_string()).is_err());
+        assert!(is_batch_size("101".to_string()).is_err());
+        assert!(is_page_size("1000".to_string()).is_ok());
+        assert!(is_page_size("1001".to_string()).is_err());
+        assert!(is_unit_cost("0".to_string()).is_ok());
+        assert!(is_unit_cost("-1".to_string()).is_err());
+        assert!(is_seconds("30".to_string()).is_ok());
+        assert!(is_seconds("-30".to_string()).is_err());
+        assert!(is_timestamp("1571234567".to_string()).is_ok());
+        assert!(is_timestamp("yesterday".to_string()).is_err());
+        assert!(is_block_id(BLOCK_ID.to_string()).is_ok());
+        assert!(is_block_id(BLOCK_ID[1..].to_string()).is_err());
+        assert!(is_block_id(BLOCK_ID.replace("a", "g")).is_err());
+        assert!(is_block_count("10".to_string()).is_ok());
+        assert!(is_block_count("-10".to_string()).is_err());
+        assert!(is_transaction_count("4294967295".to_string()).is_ok());
+        assert!(is_transaction_count("4294967296".to_string()).is_err());
+        assert!(is_max_quantity("0".to_string()).is_ok());
+        assert!(is_max_quantity("-1".to_string()).is_err());
+        assert!(is_max_quantity("lots".to_string()).is_err());
+    }
+
+    #[test]
+    fn subcommands_validate_their_arguments() {
+        let matches = parse(&["produce", "Bread", "5", "--unit-cost", "250"]).unwrap();
+        let produce = matches.subcommand_matches("produce").unwrap();
+        assert_eq!(produce.value_of("identifier"), Some("Bread"));
+        assert_eq!(produce.value_of("unit_cost"), Some("250"));
+        assert!(parse(&["produce", "Bread", "0"]).is_err());
+        assert!(parse(&["consume", " ", "5"]).is_err());
+        assert!(parse(&["consume", "Bread", "5", "--valid-after", "soon"]).is_err());
+        assert!(parse(&["show", "Bread", "--head", "latest"]).is_err());
+        assert!(parse(&["import", "rows.csv", "--batch-size", "500"]).is_err());
+        assert!(parse(&[
+            "rate-limit",
+            "--window-blocks",
+            "10",
+            "--max-quantity",
+            "100"
+        ])
+        .is_ok());
+        assert!(parse(&["rate-limit", "--window-blocks", "ten"]).is_err());
+        assert!(parse(&[
+            "rate-limit",
+            "--window-blocks",
+            "10",
+            "--max-transactions",
+            "x"
+        ])
+        .is_err());
+
+        let matches = parse(&["transfer", "offer", "Bread", "5", "--recipient", "02ab"]).unwrap();
+        let offer = matches
+            .subcommand_matches("transfer")
+            .and_then(|matches| matches.subcommand_matches("offer"))
+            .unwrap();
+        assert_eq!(offer.value_of("identifier"), Some("Bread"));
+        assert_eq!(offer.value_of("quantity"), Some("5"));
+        assert!(parse(&["transfer", "offer", "Bread", "0", "--recipient", "02ab"]).is_err());
+    }
+
+    #[test]
+    fn command_flags_translate_to_the_command() {
+        let matches = parse(&["-C", "CONSUME", "-I", "Bread", "-Q", "3"]).unwrap();
+        assert_eq!(legacy_command(&matches), Action_Command::CONSUME);
+        assert_eq!(matches.value_of("identifier"), Some("Bread"));
+        assert_eq!(matches.value_of("quantity"), Some("3"));
+        let matches = parse(&["--command", "PRODUCE", "-I", "Bread", "-Q", "3"]).unwrap();
+        assert_eq!(legacy_command(&matches), Action_Command::PRODUCE);
+
+        assert!(parse(&["-C", "PRODUCE", "-I", "Bread"]).is_err());
+        assert!(parse(&["-C", "DESTROY", "-I", "Bread", "-Q", "3"]).is_err());
+    }
+
+    #[test]
+    fn deployment_is_taken_from_the_command_line_then_the_profile() {
+        let mut profile = Profile::default();
+        assert_eq!(
+            deployment(&parse(&["summary"]).unwrap(), &profile),
+            Deployment::default()
+        );
+
+        profile.family_name = Some("inventory".to_string());
+        profile.family_version = Some("1.1".to_string());
+        let deployment_of_profile = deployment(&parse(&["summary"]).unwrap(), &profile);
+        assert_eq!(deployment_of_profile.family_name, "inventory");
+        assert_eq!(deployment_of_profile.family_version, "1.1");
+
+        let matches = parse(&["summary", "--family-name", "stock", "--wait", "0"]).unwrap();
+        let deployment = deployment(&matches, &profile);
+        assert_eq!(deployment.family_name, "stock");
+        assert_eq!(deployment.wait, 0);
+        assert_ne!(
+            deployment.namespace_prefix(),
+            deployment_of_profile.namespace_prefix()
+        );
+    }
+
+    #[test]
+    fn global_arguments_are_found_after_the_subcommands() {
+        let matches = parse(&["--url", "http://a:8008", "proposal", "list"]).unwrap();
+        assert_eq!(global_value_of(&matches, "url"), Some("http://a:8008"));
+        let matches = parse(&["proposal", "list", "--url", "http://b:8008"]).unwrap();
+        assert_eq!(global_value_of(&matches, "url"), Some("http://b:8008"));
+    }
+
+    #[test]
+    fn signing_key_is_looked_up_only_to_submit() {
+        // Looking up a key name that does not exist would exit
+        let matches = parse(&[
+            "rate-limit",
+            "--window-blocks",
+            "10",
+            "--key-name",
+            "no-such-key",
+        ])
+        .unwrap();
+        let limit_matches = matches.subcommand_matches("rate-limit").unwrap();
+        let profile = Profile::default();
+        assert!(submitter(None, limit_matches, &profile).unwrap().is_none());
+
+        let matches = parse(&[
+            "rate-limit",
+            "--window-blocks",
+            "10",
+            "-K",
+            "/keys/admin.priv",
+        ])
+        .unwrap();
+        let limit_matches = matches.subcommand_matches("rate-limit").unwrap();
+        let submitter = submitter(Some("http://localhost:8008"), limit_matches, &profile);
+        assert_eq!(submitter.unwrap().unwrap().key, "/keys/admin.priv");
+    }
+}
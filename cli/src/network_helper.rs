@@ -14,8 +14,11 @@
 
 use crate::cli_error::CliError;
 use futures::{future, future::Future, stream::Stream};
-use hyper::{client::ResponseFuture, header::HeaderMap, Error, StatusCode};
-use hyper::{header, header::HeaderValue, Body, Client, Method, Request, Uri};
+use hyper::client::{HttpConnector, ResponseFuture};
+use hyper::{header, header::HeaderMap, Error, StatusCode};
+use hyper::{header::HeaderValue, Body, Client, Method, Request, Uri};
+use serde_json::Value;
+use std::cell::RefCell;
 use std::{error, fmt};
 use tokio::runtime::current_thread::Runtime;
 
@@ -43,22 +46,55 @@ impl error::Error for ClientError {
 struct ClientResponse {
     pub body: Body,
     pub header_map: HeaderMap,
+    pub status: StatusCode,
 }
 
-/// Sends the raw_bytes to the REST API
-pub(crate) fn submit_to_rest_api(url: &str, api: &str, raw_bytes: &[u8]) -> Result<(), CliError> {
+/// Status of a submitted batch, as reported by ```/batch_statuses```
+#[derive(Debug, Clone)]
+pub(crate) struct BatchStatus {
+    pub id: String,
+    /// COMMITTED, INVALID, PENDING or UNKNOWN
+    pub status: String,
+    /// Ids and messages of the invalid transactions, as set by the transaction handler
+    pub invalid_transactions: Vec<(String, String)>,
+}
+
+/// The REST API at a URL. A command creates it once, its requests share the runtime that
+/// blocks on them and the client that keeps their connections.
+pub(crate) struct RestApi {
+    url: String,
+    runtime: RefCell<Runtime>,
+    client: Client<HttpConnector>,
+}
+
+impl RestApi {
+    pub(crate) fn new(url: &str) -> Result<RestApi, CliError> {
+        let runtime = Runtime::new()
+            .map_err(|err| CliError::from(format!("Unable to create the runtime: {}", err)))?;
+        Ok(RestApi {
+            url: url.to_string(),
+            runtime: RefCell::new(runtime),
+            client: Client::new(),
+        })
+    }
+}
+
+/// Sends the raw_bytes to the REST API, returns the ids of the submitted batches found in
+/// the status link of the response
+pub(crate) fn submit_to_rest_api(
+    rest_api: &RestApi,
+    api: &str,
+    raw_bytes: &[u8],
+) -> Result<Vec<String>, CliError> {
     let body_length = raw_bytes.len();
     let bytes = Body::from(raw_bytes.to_vec());
 
     // API to call
-    let mut rest_api = String::new();
-    rest_api.push_str(url);
-    rest_api.push_str("/");
-    rest_api.push_str(api);
-    let uri = rest_api.parse::<Uri>().expect("Error constructing URI");
-
-    // Construct client to send request
-    let client = Client::new();
+    let mut endpoint = String::new();
+    endpoint.push_str(&rest_api.url);
+    endpoint.push_str("/");
+    endpoint.push_str(api);
+    let uri = endpoint.parse::<Uri>().expect("Error constructing URI");
 
     // Compose POST request, to register
     let mut request = Request::new(bytes);
@@ -73,24 +109,265 @@ pub(crate) fn submit_to_rest_api(url: &str, api: &str, raw_bytes: &[u8]) -> Resu
         .insert(header::CONTENT_LENGTH, HeaderValue::from(body_length));
 
     // Call read_response_future to block on reading the response
-    let response_future = client.request(request);
-    match read_response_future(response_future) {
+    let response_future = rest_api.client.request(request);
+    let body = match read_response_future(rest_api, response_future, false) {
         Ok(response) => {
-            let body = read_body_as_string(response.body).expect("Unable to read body as string");
+            let body = read_body_as_string(rest_api, response.body)
+                .expect("Unable to read body as string");
             println!("Received Response from the REST API {}", body);
+            body
+        }
+        Err(err) => return Err(CliError::from(err.to_string())),
+    };
+    let response: Value = parse_json(&body)?;
+    match response["link"].as_str() {
+        Some(link) => batch_ids_of_link(link),
+        None => Err(CliError::from(
+            "The REST API did not return a batch status link",
+        )),
+    }
+}
+
+/// Reads the status of the batches, the REST API holds the request until they are all
+/// committed or invalid, or until ```wait``` seconds have passed.
+pub(crate) fn get_batch_statuses(
+    rest_api: &RestApi,
+    ids: &[String],
+    wait: u64,
+) -> Result<Vec<BatchStatus>, CliError> {
+    let uri = format!(
+        "{}/batch_statuses?id={}&wait={}",
+        rest_api.url,
+        ids.join(","),
+        wait
+    );
+    match get_from_rest_api(rest_api, &uri)? {
+        Some(body) => parse_batch_statuses(&body),
+        None => Err(CliError::from("The batch statuses were not found")),
+    }
+}
+
+/// Reads the statuses out of a ```/batch_statuses``` response body
+pub(crate) fn parse_batch_statuses(body: &str) -> Result<Vec<BatchStatus>, CliError> {
+    let response: Value = parse_json(body)?;
+    let data = match response["data"].as_array() {
+        Some(data) => data,
+        None => {
+            return Err(CliError::from(
+                "Unexpected batch status response from the REST API",
+            ))
+        }
+    };
+    let mut statuses = Vec::new();
+    for entry in data {
+        let (id, status) = match (entry["id"].as_str(), entry["status"].as_str()) {
+            (Some(id), Some(status)) => (id.to_string(), status.to_string()),
+            _ => return Err(CliError::from("Unexpected batch status from the REST API")),
+        };
+        let invalid_transactions = entry["invalid_transactions"]
+            .as_array()
+            .map(|transactions| {
+                transactions
+                    .iter()
+                    .map(|transaction| {
+                        (
+                            transaction["id"].as_str().unwrap_or("").to_string(),
+                            transaction["message"].as_str().unwrap_or("").to_string(),
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_else(Vec::new);
+        statuses.push(BatchStatus {
+            id,
+            status,
+            invalid_transactions,
+        });
+    }
+    Ok(statuses)
+}
+
+/// The status link is ```<url>/batch_statuses?id=<id>,<id>...```, the host it names may
+/// not be reachable from here so only the ids are kept
+fn batch_ids_of_link(link: &str) -> Result<Vec<String>, CliError> {
+    let query = link.splitn(2, '?').nth(1).unwrap_or("");
+    let ids = query
+        .split('&')
+        .filter_map(|parameter| {
+            let mut pair = parameter.splitn(2, '=');
+            match (pair.next(), pair.next()) {
+                (Some("id"), Some(ids)) => Some(ids),
+                _ => None,
+            }
+        })
+        .flat_map(|ids| ids.split(','))
+        .filter(|id| !id.is_empty())
+        .map(|id| id.to_string())
+        .collect::<Vec<String>>();
+    if ids.is_empty() {
+        return Err(CliError::from(format!(
+            "No batch id in the status link {}",
+            link
+        )));
+    }
+    Ok(ids)
+}
+
+/// Reads a state entry through the REST API, returns ```None``` if the address is not set.
+pub(crate) fn get_state_entry(
+    rest_api: &RestApi,
+    address: &str,
+) -> Result<Option<Vec<u8>>, CliError> {
+    get_state_entry_at(rest_api, address, None)
+}
+
+/// Reads a state entry as of the given block, the current head if none is given.
+/// Returns ```None``` if the address was not set in that block.
+pub(crate) fn get_state_entry_at(
+    rest_api: &RestApi,
+    address: &str,
+    head: Option<&str>,
+) -> Result<Option<Vec<u8>>, CliError> {
+    let uri = match head {
+        Some(head) => format!("{}/state/{}?head={}", rest_api.url, address, head),
+        None => format!("{}/state/{}", rest_api.url, address),
+    };
+    let body = match get_from_rest_api(rest_api, &uri)? {
+        Some(body) => body,
+        None => return Ok(None),
+    };
+    let response: Value = parse_json(&body)?;
+    match response["data"].as_str() {
+        Some(data) => decode_base64(data).map(Some),
+        None => Err(CliError::from(
+            "Unexpected state response from the REST API",
+        )),
+    }
+}
+
+/// Reads all the state entries under the address prefix through the REST API, page after
+/// page until the last one.
+pub(crate) fn get_state_entries(
+    rest_api: &RestApi,
+    prefix: &str,
+) -> Result<Vec<(String, Vec<u8>)>, CliError> {
+    get_state_entries_paged(rest_api, prefix, None)
+}
+
+/// Same as ```get_state_entries```, with the number of entries requested per page. The
+/// REST API uses its own default if none is given.
+pub(crate) fn get_state_entries_paged(
+    rest_api: &RestApi,
+    prefix: &str,
+    page_size: Option<usize>,
+) -> Result<Vec<(String, Vec<u8>)>, CliError> {
+    let mut entries = Vec::new();
+    let mut start: Option<String> = None;
+    loop {
+        let page_url = state_page_url(
+            &rest_api.url,
+            prefix,
+            start.as_ref().map(String::as_str),
+            page_size,
+        );
+        let body = match get_from_rest_api(rest_api, &page_url)? {
+            Some(body) => body,
+            None => break,
+        };
+        let response: Value = parse_json(&body)?;
+        let data = match response["data"].as_array() {
+            Some(data) => data,
+            None => {
+                return Err(CliError::from(
+                    "Unexpected state response from the REST API",
+                ))
+            }
+        };
+        for entry in data {
+            match (entry["address"].as_str(), entry["data"].as_str()) {
+                (Some(address), Some(value)) => {
+                    entries.push((address.to_string(), decode_base64(value)?))
+                }
+                _ => return Err(CliError::from("Unexpected state entry from the REST API")),
+            }
         }
+        // The next link is absolute, built from the host the REST API knows itself by,
+        // which is not always reachable from here. Only its start cursor is kept.
+        start = match response["paging"]["next_position"].as_str() {
+            Some(position) => Some(position.to_string()),
+            None => break,
+        };
+    }
+    Ok(entries)
+}
+
+/// URL of a page of the state entries under the prefix, from the start cursor of the
+/// previous page if it is not the first
+fn state_page_url(url: &str, prefix: &str, start: Option<&str>, limit: Option<usize>) -> String {
+    let mut page_url = format!("{}/state?address={}", url, prefix);
+    if let Some(start) = start {
+        page_url.push_str(&format!("&start={}", start));
+    }
+    if let Some(limit) = limit {
+        page_url.push_str(&format!("&limit={}", limit));
+    }
+    page_url
+}
+
+/// Error code of the REST API when the block given as head does not exist
+const HEAD_NOT_FOUND: i64 = 50;
+
+/// Sends a GET request to the REST API, the body is returned as string. ```None``` is
+/// returned when the resource is not found, an unknown head block is an error.
+fn get_from_rest_api(rest_api: &RestApi, uri: &str) -> Result<Option<String>, CliError> {
+    let uri = match uri.parse::<Uri>() {
+        Ok(uri) => uri,
+        Err(err) => return Err(CliError::from(err.to_string())),
+    };
+    let response = match read_response_future(rest_api, rest_api.client.get(uri), true) {
+        Ok(response) => response,
+        Err(err) => return Err(CliError::from(err.to_string())),
+    };
+    let not_found = response.status == StatusCode::NOT_FOUND;
+    let body = match read_body_as_string(rest_api, response.body) {
+        Ok(body) => body,
         Err(err) => return Err(CliError::from(err.to_string())),
     };
-    Ok(())
+    if !not_found {
+        return Ok(Some(body));
+    }
+    let error: Value = serde_json::from_str(&body).unwrap_or(Value::Null);
+    if error["error"]["code"].as_i64() == Some(HEAD_NOT_FOUND) {
+        return Err(CliError::from(
+            error["error"]["message"]
+                .as_str()
+                .unwrap_or("The head block was not found")
+                .to_string(),
+        ));
+    }
+    Ok(None)
+}
+
+fn parse_json(body: &str) -> Result<Value, CliError> {
+    serde_json::from_str(body).map_err(|err| CliError::from(err.to_string()))
+}
+
+fn decode_base64(data: &str) -> Result<Vec<u8>, CliError> {
+    base64::decode(data).map_err(|err| CliError::from(err.to_string()))
 }
 
 /// Function to read ```hyper::client::ResponseFuture``` (return values of .request(), .get(), .post()
 /// etc functions from hyper library).
 ///
-/// Returns result ClientResponse and ClientError.
-/// This is a blocking call. A ```tokio_core``` runner instance is created to block until
-/// ```ResponseFuture``` is complete.
-fn read_response_future(response_fut: ResponseFuture) -> Result<ClientResponse, ClientError> {
+/// Returns result ClientResponse and ClientError, a not found response is returned as is
+/// when ```allow_not_found``` is set.
+/// This is a blocking call, the runtime of the REST API blocks until ```ResponseFuture```
+/// is complete.
+fn read_response_future(
+    rest_api: &RestApi,
+    response_fut: ResponseFuture,
+    allow_not_found: bool,
+) -> Result<ClientResponse, ClientError> {
     let future_response = response_fut
         // 'then' waits for future_response to be ready and calls the closure supplied here on
         // Result of evaluated future. Response object is ready when closure is called.
@@ -98,14 +375,20 @@ fn read_response_future(response_fut: ResponseFuture) -> Result<ClientResponse,
             match response_obj {
                 Ok(response) => {
                     println!("Received response result code: {}", response.status());
-                    if response.status() >= StatusCode::BAD_REQUEST {
+                    let status = response.status();
+                    let not_found = allow_not_found && status == StatusCode::NOT_FOUND;
+                    if status >= StatusCode::BAD_REQUEST && !not_found {
                         println!("Response status is not successful: {}", response.status());
                         return Err(ClientError);
                     }
                     // Borrow response headers, to be passed in ClientResponse
                     let header_map = response.headers().to_owned();
                     let body = response.into_body();
-                    let client_response = ClientResponse { body, header_map };
+                    let client_response = ClientResponse {
+                        body,
+                        header_map,
+                        status,
+                    };
                     Ok(client_response)
                 }
                 Err(error) => {
@@ -118,10 +401,8 @@ fn read_response_future(response_fut: ResponseFuture) -> Result<ClientResponse,
             }
         });
 
-    // Create a runner instance for evaluating ResponseFuture
-    let mut runner = Runtime::new().expect("Error creating runtime");
     // blocks until future is evaluated, otherwise error out
-    match runner.block_on(future_response) {
+    match rest_api.runtime.borrow_mut().block_on(future_response) {
         Ok(successful) => Ok(successful),
         Err(_) => Err(ClientError),
     }
@@ -131,25 +412,66 @@ fn read_response_future(response_fut: ResponseFuture) -> Result<ClientResponse,
 ///
 /// Returns result of ```String``` and ```ClientError```.
 /// This is a blocking call. Body is streamed and collected as vector, which later is converted to
-/// string representation.
-fn read_body_as_string(body: Body) -> Result<String, ClientError> {
-    body.fold(Vec::new(), |mut vector, chunk| {
-        vector.extend_from_slice(&chunk[..]);
-        future::ok::<_, Error>(vector)
-    })
-    // 'then' evaluates Future to Result. Note that body should be available already.
-    // Construct a Result of string to be returned when body is available.
-    .then(move |body_as_byte_vector| match body_as_byte_vector {
-        Ok(byte_vector) => {
-            let body =
-                String::from_utf8(byte_vector).expect("Error reading body byte stream as string");
-            Ok(body)
-        }
-        Err(error) => {
-            println!("Error reading body as string {}", error);
-            Err(ClientError)
-        }
-    })
+/// string representation. The runtime of the REST API drives the connection meanwhile.
+fn read_body_as_string(rest_api: &RestApi, body: Body) -> Result<String, ClientError> {
+    let body_future = body
+        .fold(Vec::new(), |mut vector, chunk| {
+            vector.extend_from_slice(&chunk[..]);
+            future::ok::<_, Error>(vector)
+        })
+        // 'then' evaluates Future to Result.
+        // Construct a Result of string to be returned when body is available.
+        .then(move |body_as_byte_vector| match body_as_byte_vector {
+            Ok(byte_vector) => {
+                let body = String::from_utf8(byte_vector)
+                    .expect("Error reading body byte stream as string");
+                Ok(body)
+            }
+            Err(error) => {
+                println!("Error reading body as string {}", error);
+                Err(ClientError)
+            }
+        });
     // Wait for completion of task assigned to then
-    .wait()
+    rest_api.runtime.borrow_mut().block_on(body_future)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_ids_are_read_from_the_status_link() {
+        assert_eq!(
+            batch_ids_of_link("http://rest-api:8008/batch_statuses?id=ab12,cd34").unwrap(),
+            vec!["ab12".to_string(), "cd34".to_string()]
+        );
+        assert_eq!(
+            batch_ids_of_link("http://127.0.0.1/batch_statuses?wait=5&id=ab12").unwrap(),
+            vec!["ab12".to_string()]
+        );
+        assert!(batch_ids_of_link("http://rest-api:8008/batch_statuses").is_err());
+        assert!(batch_ids_of_link("http://rest-api:8008/batch_statuses?id=").is_err());
+    }
+
+    #[test]
+    fn pages_are_read_from_the_given_url() {
+        assert_eq!(
+            state_page_url("http://localhost:8008", "ce2292", None, None),
+            "http://localhost:8008/state?address=ce2292"
+        );
+        assert_eq!(
+            state_page_url("http://localhost:8008", "ce2292", None, Some(100)),
+            "http://localhost:8008/state?address=ce2292&limit=100"
+        );
+        assert_eq!(
+            state_page_url(
+                "http://localhost:8008",
+                "ce2292",
+                Some("ce2292d27d94"),
+                Some(100)
+            ),
+            "http://localhost:8008/state?address=ce2292&start=ce2292d27d94&limit=100"
+        );
+    }
 }
@@ -12,115 +12,323 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::batch;
 use crate::cli_error::CliError;
-use crate::network_helper;
+use crate::keys;
+use crate::network_helper::RestApi;
 use crate::proto::action::Action;
 use crate::proto::action::Action_Command;
 use crate::sawtooth_helper;
-use crypto::digest::Digest;
-use crypto::sha2::Sha512;
 use hex;
+use produce_consume_core::addressing::{
+    self, compute_address, compute_config_address, compute_cost_layers_address,
+    compute_holding_address, compute_proposal_address, compute_signer_usage_address,
+    compute_totals_addresses, BLOCK_INFO_NAMESPACE, DEFAULT_FAMILY_NAME, VERSION,
+};
+use produce_consume_core::payload;
 use protobuf::Message;
 use rand::Rng;
-use sawtooth_sdk::signing::{create_context, secp256k1::Secp256k1PrivateKey, PrivateKey, Signer};
+use sawtooth_sdk::messages::transaction::Transaction;
+use sawtooth_sdk::signing::{create_context, PrivateKey, Signer};
 use std::env;
 use std::fs::File;
-use std::io::Read;
 use std::io::Write;
 
-pub(crate) const PRODUCE_CONSUME: &str = "produce-consume";
-pub(crate) const VERSION: &str = "1.0";
+/// Deployment the CLI talks to, the processor must be registered for the same family name
+/// and version. The transactions are sent to that family and every address is derived
+/// from its name.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Deployment {
+    pub family_name: String,
+    /// Family version set in the header of every transaction
+    pub family_version: String,
+    /// Seconds the REST API is asked to wait for the submitted batches to be committed,
+    /// 0 reads their status right away
+    pub wait: u64,
+}
+
+impl Default for Deployment {
+    fn default() -> Self {
+        Deployment {
+            family_name: DEFAULT_FAMILY_NAME.to_string(),
+            family_version: VERSION.to_string(),
+            wait: batch::DEFAULT_WAIT,
+        }
+    }
+}
+
+impl Deployment {
+    /// Namespace prefix of the family, every address starts with it
+    pub(crate) fn namespace_prefix(&self) -> String {
+        addressing::compute_namespace_prefix(&self.family_name)
+    }
+}
 
+/// The REST API the transactions of a command are submitted to and the key signing them.
+/// A command given no URL saves its payload for Sabre unsigned, it needs no key.
+pub(crate) struct Submitter {
+    pub rest_api: RestApi,
+    /// Path of the private key file
+    pub key: String,
+}
+
+/// Submits a PRODUCE or a CONSUME of the item, the arguments are parsed and checked
+/// before the transaction is signed.
 pub(crate) fn submit_payload(
-    command: &str,
+    cmd: Action_Command,
     identifier: &str,
     quantity: &str,
-    url: Option<&str>,
-    key: &str,
+    category: Option<&str>,
+    unit_cost: Option<&str>,
+    valid_after: Option<&str>,
+    valid_before: Option<&str>,
+    submitter: Option<&Submitter>,
+    deployment: &Deployment,
 ) -> Result<(), CliError> {
-    let cmd: Action_Command = if command == "PRODUCE" {
-        Action_Command::PRODUCE
-    } else if command == "CONSUME" {
-        Action_Command::CONSUME
-    } else {
-        panic!("Unexpected scenario");
+    println!("Command is {:?}", cmd);
+    // The holding of the signer is updated, the payload saved for Sabre is not signed here
+    let signer_public_key = match submitter {
+        Some(submitter) => Some(read_public_key(&submitter.key)?),
+        None => None,
     };
+    let (action, input_addresses, output_addresses) = item_action(
+        cmd,
+        identifier,
+        quantity,
+        category,
+        unit_cost,
+        valid_after,
+        valid_before,
+        signer_public_key.as_ref().map(String::as_str),
+        deployment,
+    )?;
+    if cmd == Action_Command::CONSUME {
+        println!(
+            "Proposal id if approvals are required: {}",
+            action.get_proposal_id()
+        );
+    }
+    submit_action(
+        &action,
+        &input_addresses,
+        &output_addresses,
+        submitter,
+        deployment,
+    )
+}
 
-    let address = compute_address(identifier);
+/// Builds the action of a PRODUCE or a CONSUME with the addresses its transaction reads
+/// and writes. The holding of the signer is among them when its public key is given.
+pub(crate) fn item_action(
+    cmd: Action_Command,
+    identifier: &str,
+    quantity: &str,
+    category: Option<&str>,
+    unit_cost: Option<&str>,
+    valid_after: Option<&str>,
+    valid_before: Option<&str>,
+    signer_public_key: Option<&str>,
+    deployment: &Deployment,
+) -> Result<(Action, Vec<String>, Vec<String>), CliError> {
+    if cmd != Action_Command::PRODUCE && cmd != Action_Command::CONSUME {
+        return Err(CliError::from(format!(
+            "Expected PRODUCE or CONSUME, got {:?}",
+            cmd
+        )));
+    }
+
+    let prefix = deployment.namespace_prefix();
+    let address = compute_address(&prefix, identifier);
 
     let qty: i32 = match quantity.parse() {
         Ok(value) => value,
         Err(err) => return Err(CliError::from(err.to_string())),
     };
 
-    let mut action: Action = Action::new();
-    println!("Command is {:?}", cmd.clone());
-    action.set_command(cmd);
-    action.set_identifier(identifier.to_string());
-    action.set_quantity(qty);
-    let payload = action
-        .write_to_bytes()
-        .expect("Couldn't create a command to send to the validator");
+    let cost: i64 = match unit_cost {
+        Some(value) => match value.parse() {
+            Ok(value) => value,
+            Err(err) => return Err(CliError::from(format!("Invalid unit cost: {}", err))),
+        },
+        None => 0,
+    };
+    if cost != 0 && cmd != Action_Command::PRODUCE {
+        return Err(CliError::from("The unit cost is only valid for PRODUCE"));
+    }
+    if category.is_some() && cmd != Action_Command::PRODUCE {
+        return Err(CliError::from("The category is only valid for PRODUCE"));
+    }
 
-    println!("Payload in raw is {:?}", payload.to_vec());
-    let parsed_payload: Action = parse_from(&payload).expect("Cannot");
-    println!("Payload is {:?} {:?} {:?}", parsed_payload.get_command(), parsed_payload.get_identifier(), parsed_payload.get_quantity());
+    let after: u64 = parse_timestamp(valid_after)?;
+    let before: u64 = parse_timestamp(valid_before)?;
+    if after != 0 && before != 0 && after >= before {
+        return Err(CliError::from(
+            "The valid-after timestamp must be earlier than valid-before",
+        ));
+    }
 
-    if url.is_none() {
-        save_to_file(&payload);
-        return Ok(());
+    // A large consumption becomes a proposal, the id is used only in that case
+    let mut action = if cmd == Action_Command::PRODUCE {
+        payload::produce(identifier, qty, cost, category.unwrap_or(""))
+    } else {
+        payload::consume(identifier, qty, &random_id())
+    };
+    action.set_valid_after(after);
+    action.set_valid_before(before);
+
+    // Both commands update the cost layers of the item
+    let cost_layers_address = compute_cost_layers_address(&prefix, identifier);
+    let mut output_addresses = vec![address.clone(), cost_layers_address.clone()];
+    let mut input_addresses = vec![address.clone(), cost_layers_address];
+    input_addresses.extend(compute_totals_addresses(&prefix, identifier));
+    output_addresses.extend(compute_totals_addresses(&prefix, identifier));
+    if let Some(public_key) = signer_public_key {
+        let holding_address = compute_holding_address(&prefix, public_key, identifier);
+        input_addresses.push(holding_address.clone());
+        output_addresses.push(holding_address);
+    }
+    // The handler reads the current block timestamp from the BlockInfo state
+    if after != 0 || before != 0 {
+        input_addresses.push(BLOCK_INFO_NAMESPACE.to_string());
+    }
+    if cmd == Action_Command::CONSUME {
+        let proposal_address = compute_proposal_address(&prefix, action.get_proposal_id());
+        input_addresses.push(compute_config_address(&prefix));
+        input_addresses.push(proposal_address.clone());
+        output_addresses.push(proposal_address);
     }
 
-    let read_key = read_file(key);
-    let private_key: Box<dyn PrivateKey> =
-        Box::new(Secp256k1PrivateKey::from_hex(&read_key).expect("Unable to load context"));
+    Ok((action, input_addresses, output_addresses))
+}
+
+/// Serializes the action, signs it into a transaction and submits it in a batch of its
+/// own to the REST API. The raw payload is saved to a file for Sabre if no URL is given.
+pub(crate) fn submit_action(
+    action: &Action,
+    input_addresses: &[String],
+    output_addresses: &[String],
+    submitter: Option<&Submitter>,
+    deployment: &Deployment,
+) -> Result<(), CliError> {
+    let submitter = match submitter {
+        Some(submitter) => submitter,
+        None => {
+            // The processor would reject the transaction, there is no point in saving it
+            payload::validate(action).map_err(|err| CliError::from(err.to_string()))?;
+            let payload =
+                payload::encode_action(action).map_err(|err| CliError::from(err.to_string()))?;
+            println!("Payload in raw is {:?}", payload.to_vec());
+            save_to_file(&payload);
+            return Ok(());
+        }
+    };
+
+    let private_key: Box<dyn PrivateKey> = Box::new(keys::read_private_key(&submitter.key)?);
     let context = create_context("secp256k1").expect("Unable to create a secp256k1 context");
     let signer = Signer::new(context.as_ref(), private_key.as_ref());
+    let transaction = create_transaction(
+        action,
+        input_addresses,
+        output_addresses,
+        &signer,
+        deployment,
+    )?;
+    println!("Payload in raw is {:?}", transaction.get_payload());
+    // Create batch header, batch
+    let batch = sawtooth_helper::create_batch(&signer, vec![transaction]);
+    let batches = vec![batch];
+    let batch_list = sawtooth_helper::create_batch_list(batches);
+
+    let raw_bytes = batch_list
+        .write_to_bytes()
+        .expect("Unable to write batch list as bytes");
+
+    batch::submit(&submitter.rest_api, &raw_bytes, deployment.wait)
+}
+
+/// Serializes the action and signs it into a transaction. The addresses of the rate
+/// limits are added to the given ones.
+pub(crate) fn create_transaction(
+    action: &Action,
+    input_addresses: &[String],
+    output_addresses: &[String],
+    signer: &Signer,
+    deployment: &Deployment,
+) -> Result<Transaction, CliError> {
+    // The processor would reject the transaction, there is no point in signing it
+    payload::validate(action).map_err(|err| CliError::from(err.to_string()))?;
+    let payload = payload::encode_action(action).map_err(|err| CliError::from(err.to_string()))?;
+
     // get signer and public key from signer in hex
     let public_key = signer.get_public_key().expect("Unable to get public key");
 
-    let output_addresses = [address.clone()];
-    let input_addresses = [address.clone()];
+    // Any transaction can be rate limited, it reads the limits and the current block and
+    // updates the usage of the signer
+    let prefix = deployment.namespace_prefix();
+    let signer_usage_address = compute_signer_usage_address(&prefix, &public_key.as_hex());
+    let mut inputs = input_addresses.to_vec();
+    let mut outputs = output_addresses.to_vec();
+    for address in vec![
+        compute_config_address(&prefix),
+        BLOCK_INFO_NAMESPACE.to_string(),
+        signer_usage_address.clone(),
+    ] {
+        if !inputs.contains(&address) {
+            inputs.push(address);
+        }
+    }
+    if !outputs.contains(&signer_usage_address) {
+        outputs.push(signer_usage_address);
+    }
 
-    let nonce_bytes = rand::thread_rng()
-        .gen_iter::<u8>()
-        .take(64)
-        .collect::<Vec<u8>>();
-    let nonce = to_hex_string(&nonce_bytes);
+    let nonce = to_hex_string(&random_bytes(64));
 
     // Create transaction header
     let transaction_header = sawtooth_helper::create_transaction_header(
-        &input_addresses,
-        &output_addresses,
+        &inputs,
+        &outputs,
         &payload,
         &public_key,
         nonce.to_string(),
+        deployment,
     );
     // Create transaction
-    let transaction =
-        sawtooth_helper::create_transaction(&signer, &transaction_header, payload.to_vec());
-    // Create batch header, batch
-    let batch = sawtooth_helper::create_batch(&signer, transaction);
-    let batches = vec![batch];
-    let batch_list = sawtooth_helper::create_batch_list(batches);
-
-    let raw_bytes = batch_list
-        .write_to_bytes()
-        .expect("Unable to write batch list as bytes");
+    Ok(sawtooth_helper::create_transaction(
+        signer,
+        &transaction_header,
+        payload.to_vec(),
+    ))
+}
 
-    if url.is_some() {
-        return network_helper::submit_to_rest_api(url.unwrap(), "batches", &raw_bytes);
+/// Public key in hex of the private key stored in the file
+pub(crate) fn read_public_key(key: &str) -> Result<String, CliError> {
+    let private_key = keys::read_private_key(key)?;
+    let context = create_context("secp256k1").expect("Unable to create a secp256k1 context");
+    match context.get_public_key(&private_key) {
+        Ok(public_key) => Ok(public_key.as_hex()),
+        Err(err) => Err(CliError::from(err.to_string())),
     }
-    Ok(())
 }
 
-fn parse_from<T>(data: &[u8]) -> Result<T, ()>
-    where
-        T: protobuf::Message,
-{
-    protobuf::parse_from_bytes(&data).map_err(|err| {
-        println!("Invalid error: Failed to parse the payload: {:?}", err);
-    })
+/// Random identifier in hex, used for the proposals and the transfers
+pub(crate) fn random_id() -> String {
+    to_hex_string(&random_bytes(16))
+}
+
+fn random_bytes(count: usize) -> Vec<u8> {
+    rand::thread_rng()
+        .gen_iter::<u8>()
+        .take(count)
+        .collect::<Vec<u8>>()
+}
+
+fn parse_timestamp(timestamp: Option<&str>) -> Result<u64, CliError> {
+    match timestamp {
+        Some(value) => value
+            .parse()
+            .map_err(|err: std::num::ParseIntError| CliError::from(err.to_string())),
+        None => Ok(0),
+    }
 }
 
 /// Saves the byte stream to a file
@@ -138,44 +346,6 @@ pub fn write_binary_file(data: &[u8], filename: &str) {
     file.write_all(data).expect("Write binary file failed");
 }
 
-fn compute_address(identifier: &str) -> String {
-    let prefix = get_produce_consume_prefix();
-    let mut sha = Sha512::new();
-    sha.input_str(identifier);
-    let remaining = sha.result_str()[..64].to_string();
-    prefix + &remaining
-}
-
-fn get_produce_consume_prefix() -> String {
-    let mut sha = Sha512::new();
-    sha.input_str(PRODUCE_CONSUME);
-    sha.result_str()[..6].to_string()
-}
-
-/// Reads the given file as string
-///
-/// Note: This method will panic if file is not found or error occurs when reading file as string.
-pub fn read_file_as_string(filename: &str) -> String {
-    let mut file_handler = match File::open(filename) {
-        Ok(file_open_successful) => file_open_successful,
-        Err(error) => panic!("Error opening file! {} : {}", error, filename),
-    };
-    let mut read_contents = String::new();
-    file_handler
-        .read_to_string(&mut read_contents)
-        .expect("Read operation failed");
-    read_contents
-}
-
-/// Reads the given file as string, ignore the new line character at end
-///
-/// Note: This method will panic if file is not found or error occurs when reading file as string.
-pub fn read_file(filename: &str) -> String {
-    let mut read_contents = read_file_as_string(filename);
-    read_contents.pop();
-    read_contents
-}
-
 pub fn to_hex_string(bytes: &[u8]) -> String {
     hex::encode(bytes)
 }
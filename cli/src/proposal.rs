@@ -0,0 +1,167 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::cli_error::CliError;
+use crate::network_helper::{self, RestApi};
+use crate::produce_consume::{self, Deployment, Submitter};
+use crate::proto::state::ApprovalPolicy;
+use crate::proto::state::Proposal;
+use produce_consume_core::addressing::{
+    compute_address, compute_config_address, compute_cost_layers_address, compute_holding_address,
+    compute_proposal_address, compute_setting_address, compute_totals_addresses,
+    get_proposal_prefix, ADMINS_SETTING,
+};
+use produce_consume_core::{codec, payload};
+use protobuf::RepeatedField;
+
+/// Prints all the proposals stored in the namespace
+pub(crate) fn list_proposals(rest_api: &RestApi, deployment: &Deployment) -> Result<(), CliError> {
+    let prefix = get_proposal_prefix(&deployment.namespace_prefix());
+    let entries = network_helper::get_state_entries(rest_api, &prefix)?;
+    let mut proposals = Vec::new();
+    for (address, data) in entries {
+        match codec::decode::<Proposal>(&data) {
+            Ok(proposal) => proposals.push(proposal),
+            Err(err) => {
+                return Err(CliError::from(format!(
+                    "Unable to decode the proposal at {}: {}",
+                    address, err
+                )))
+            }
+        }
+    }
+    proposals.sort_by(|a, b| a.get_proposal_id().cmp(b.get_proposal_id()));
+
+    println!(
+        "{:<32} {:<20} {:>10} {:<8} {:>9}",
+        "PROPOSAL", "IDENTIFIER", "QUANTITY", "STATUS", "APPROVALS"
+    );
+    for proposal in proposals {
+        println!(
+            "{:<32} {:<20} {:>10} {:<8} {:>9}",
+            proposal.get_proposal_id(),
+            proposal.get_identifier(),
+            proposal.get_quantity(),
+            format!("{:?}", proposal.get_status()),
+            proposal.get_approvals().len()
+        );
+    }
+    Ok(())
+}
+
+/// Approves or rejects the pending proposal. The proposal is read first, the item it
+/// consumes is part of the transaction inputs and outputs.
+pub(crate) fn vote(
+    proposal_id: &str,
+    approve: bool,
+    submitter: Option<&Submitter>,
+    deployment: &Deployment,
+) -> Result<(), CliError> {
+    let submitter = match submitter {
+        Some(submitter) => submitter,
+        None => return Err(CliError::from("The REST API URL is required to vote")),
+    };
+    let prefix = deployment.namespace_prefix();
+    let proposal_address = compute_proposal_address(&prefix, proposal_id);
+    let proposal: Proposal =
+        match network_helper::get_state_entry(&submitter.rest_api, &proposal_address)? {
+            Some(data) => codec::decode(&data)
+                .map_err(|err| CliError::from(format!("Unable to decode the proposal: {}", err)))?,
+            None => {
+                return Err(CliError::from(format!(
+                    "Proposal {} does not exist",
+                    proposal_id
+                )))
+            }
+        };
+
+    let action = if approve {
+        payload::approve(proposal_id)
+    } else {
+        payload::reject(proposal_id)
+    };
+
+    let item_address = compute_address(&prefix, proposal.get_identifier());
+    let cost_layers_address = compute_cost_layers_address(&prefix, proposal.get_identifier());
+    // The consumption is executed from the holding of the proposer
+    let holding_address =
+        compute_holding_address(&prefix, proposal.get_proposer(), proposal.get_identifier());
+    let totals_addresses = compute_totals_addresses(&prefix, proposal.get_identifier());
+    let mut input_addresses = vec![
+        proposal_address.clone(),
+        compute_config_address(&prefix),
+        item_address.clone(),
+        cost_layers_address.clone(),
+        holding_address.clone(),
+    ];
+    input_addresses.extend(totals_addresses.clone());
+    let mut output_addresses = vec![
+        proposal_address,
+        item_address,
+        cost_layers_address,
+        holding_address,
+    ];
+    output_addresses.extend(totals_addresses);
+    produce_consume::submit_action(
+        &action,
+        &input_addresses,
+        &output_addresses,
+        Some(submitter),
+        deployment,
+    )
+}
+
+/// Replaces the approval policy, the signer must be one of the administrators
+pub(crate) fn set_policy(
+    threshold: &str,
+    required_approvals: &str,
+    approvers: Vec<&str>,
+    submitter: Option<&Submitter>,
+    deployment: &Deployment,
+) -> Result<(), CliError> {
+    let threshold: i32 = match threshold.parse() {
+        Ok(value) => value,
+        Err(err) => return Err(CliError::from(err.to_string())),
+    };
+    let required_approvals: u32 = match required_approvals.parse() {
+        Ok(value) => value,
+        Err(err) => return Err(CliError::from(err.to_string())),
+    };
+
+    let mut policy = ApprovalPolicy::new();
+    policy.set_threshold(threshold);
+    policy.set_required_approvals(required_approvals);
+    policy.set_approvers(RepeatedField::from_vec(
+        approvers
+            .iter()
+            .map(|approver| approver.to_string())
+            .collect(),
+    ));
+
+    let action = payload::set_approval_policy(policy);
+
+    let config_address = compute_config_address(&deployment.namespace_prefix());
+    let input_addresses = vec![
+        compute_setting_address(ADMINS_SETTING),
+        config_address.clone(),
+    ];
+    let output_addresses = vec![config_address];
+    produce_consume::submit_action(
+        &action,
+        &input_addresses,
+        &output_addresses,
+        submitter,
+        deployment,
+    )
+}
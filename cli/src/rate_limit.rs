@@ -0,0 +1,61 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::cli_error::CliError;
+use crate::produce_consume::{self, Deployment, Submitter};
+use crate::proto::state::RateLimit;
+use produce_consume_core::addressing::{
+    compute_config_address, compute_setting_address, ADMINS_SETTING,
+};
+use produce_consume_core::payload;
+
+/// Replaces the per signer rate limits, the signer must be one of the administrators
+pub(crate) fn set_limit(
+    window_blocks: &str,
+    max_transactions: &str,
+    max_quantity: &str,
+    submitter: Option<&Submitter>,
+    deployment: &Deployment,
+) -> Result<(), CliError> {
+    let mut limit = RateLimit::new();
+    limit.set_window_blocks(parse_number(window_blocks, "window blocks")?);
+    limit.set_max_transactions(parse_number(max_transactions, "maximum transactions")?);
+    limit.set_max_quantity(parse_number(max_quantity, "maximum quantity")?);
+
+    let action = payload::set_rate_limit(limit);
+
+    let config_address = compute_config_address(&deployment.namespace_prefix());
+    let input_addresses = vec![
+        compute_setting_address(ADMINS_SETTING),
+        config_address.clone(),
+    ];
+    let output_addresses = vec![config_address];
+    produce_consume::submit_action(
+        &action,
+        &input_addresses,
+        &output_addresses,
+        submitter,
+        deployment,
+    )
+}
+
+fn parse_number<T>(value: &str, name: &str) -> Result<T, CliError>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|err| CliError::from(format!("Invalid {}: {}", name, err)))
+}
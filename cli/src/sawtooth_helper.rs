@@ -12,8 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::produce_consume::PRODUCE_CONSUME;
-use crate::produce_consume::VERSION;
+use crate::produce_consume::Deployment;
 use crypto::digest::Digest;
 use crypto::sha2::Sha512;
 use protobuf::{Message, RepeatedField};
@@ -37,9 +36,9 @@ pub(crate) fn create_batch_list(batches: Vec<Batch>) -> BatchList {
 }
 
 /// Function to create the ```Batch``` object, this is then added to ```BatchList```. Accepts
-/// signer object and ```Transaction``` as input parameters. Constructs ```BatchHeader``` , adds
-/// signature of it to ```Batch```.
-pub(crate) fn create_batch(signer: &Signer, transaction: Transaction) -> Batch {
+/// signer object and the ```Transaction```s as input parameters, they are committed or
+/// rejected together. Constructs ```BatchHeader``` , adds signature of it to ```Batch```.
+pub(crate) fn create_batch(signer: &Signer, transactions: Vec<Transaction>) -> Batch {
     // Construct BatchHeader
     let mut batch_header = BatchHeader::new();
     // set signer public key
@@ -47,7 +46,7 @@ pub(crate) fn create_batch(signer: &Signer, transaction: Transaction) -> Batch {
         .get_public_key()
         .expect("Unable to get public key")
         .as_hex();
-    let transaction_ids = vec![transaction.clone()]
+    let transaction_ids = transactions
         .iter()
         .map(|trans| String::from(trans.get_header_signature()))
         .collect();
@@ -64,7 +63,7 @@ pub(crate) fn create_batch(signer: &Signer, transaction: Transaction) -> Batch {
     let mut batch = Batch::new();
     batch.set_header_signature(signature);
     batch.set_header(batch_header_bytes);
-    batch.set_transactions(RepeatedField::from_vec(vec![transaction]));
+    batch.set_transactions(RepeatedField::from_vec(transactions));
     batch
 }
 
@@ -97,11 +96,12 @@ pub(crate) fn create_transaction_header(
     payload: &[u8],
     public_key: &Box<dyn PublicKey>,
     nonce: String,
+    deployment: &Deployment,
 ) -> TransactionHeader {
     // Construct transaction header
     let mut transaction_header = TransactionHeader::new();
-    transaction_header.set_family_name(PRODUCE_CONSUME.to_string());
-    transaction_header.set_family_version(VERSION.to_string());
+    transaction_header.set_family_name(deployment.family_name.clone());
+    transaction_header.set_family_version(deployment.family_version.clone());
     transaction_header.set_nonce(nonce);
     transaction_header.set_payload_sha512(sha512_of_bytes(payload));
     transaction_header.set_signer_public_key(public_key.as_hex());
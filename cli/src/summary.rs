@@ -0,0 +1,98 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::cli_error::CliError;
+use crate::network_helper::{self, RestApi};
+use crate::produce_consume::Deployment;
+use crate::proto::state::CategoryTotals;
+use produce_consume_core::addressing::{
+    compute_category_totals_address, compute_global_totals_address, get_category_totals_prefix,
+};
+use produce_consume_core::codec;
+
+/// Prints the totals of the category, or of all the categories followed by the global
+/// totals if no category is given. Each category is a single state entry.
+pub(crate) fn print_summary(
+    rest_api: &RestApi,
+    category: Option<&str>,
+    deployment: &Deployment,
+) -> Result<(), CliError> {
+    let prefix = deployment.namespace_prefix();
+    let mut rows = Vec::new();
+    match category {
+        Some(category) => {
+            let address = compute_category_totals_address(&prefix, category);
+            match network_helper::get_state_entry(rest_api, &address)? {
+                Some(data) => rows.push(decode_totals(&address, &data)?),
+                None => {
+                    let mut totals = CategoryTotals::new();
+                    totals.set_category(category.to_string());
+                    rows.push(totals);
+                }
+            }
+        }
+        None => {
+            let entries =
+                network_helper::get_state_entries(rest_api, &get_category_totals_prefix(&prefix))?;
+            for (address, data) in entries {
+                rows.push(decode_totals(&address, &data)?);
+            }
+            rows.sort_by(|a, b| a.get_category().cmp(b.get_category()));
+        }
+    }
+
+    println!(
+        "{:<20} {:>12} {:>12} {:>12}",
+        "CATEGORY", "ON HAND", "PRODUCED", "CONSUMED"
+    );
+    for totals in &rows {
+        print_row(display_category(totals.get_category()), totals);
+    }
+    if category.is_none() {
+        let address = compute_global_totals_address(&prefix);
+        let global = match network_helper::get_state_entry(rest_api, &address)? {
+            Some(data) => decode_totals(&address, &data)?,
+            None => CategoryTotals::new(),
+        };
+        print_row("TOTAL", &global);
+    }
+    Ok(())
+}
+
+fn print_row(label: &str, totals: &CategoryTotals) {
+    println!(
+        "{:<20} {:>12} {:>12} {:>12}",
+        label,
+        totals.get_on_hand(),
+        totals.get_produced(),
+        totals.get_consumed()
+    );
+}
+
+fn display_category(category: &str) -> &str {
+    if category.is_empty() {
+        "(uncategorized)"
+    } else {
+        category
+    }
+}
+
+fn decode_totals(address: &str, data: &[u8]) -> Result<CategoryTotals, CliError> {
+    codec::decode(data).map_err(|err| {
+        CliError::from(format!(
+            "Unable to decode the totals at {}: {}",
+            address, err
+        ))
+    })
+}
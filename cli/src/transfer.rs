@@ -0,0 +1,199 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::cli_error::CliError;
+use crate::network_helper::{self, RestApi};
+use crate::produce_consume::{self, Deployment, Submitter};
+use crate::proto::action::Action_Command;
+use crate::proto::block_info::BlockInfoConfig;
+use crate::proto::state::{Escrow, Escrow_Status};
+use produce_consume_core::addressing::{
+    block_info_config_address, compute_address, compute_escrow_address, compute_holding_address,
+    BLOCK_INFO_NAMESPACE,
+};
+use produce_consume_core::{codec, payload};
+
+/// Offers the quantity of the item held by the signer to the recipient, the quantity is
+/// held in escrow until the transfer is accepted, declined, withdrawn or expires.
+pub(crate) fn offer(
+    identifier: &str,
+    quantity: &str,
+    recipient: &str,
+    expires_at_block: Option<&str>,
+    submitter: Option<&Submitter>,
+    deployment: &Deployment,
+) -> Result<(), CliError> {
+    let qty: i32 = match quantity.parse() {
+        Ok(value) => value,
+        Err(err) => return Err(CliError::from(err.to_string())),
+    };
+    let expires: u64 = match expires_at_block {
+        Some(value) => match value.parse() {
+            Ok(value) => value,
+            Err(err) => return Err(CliError::from(format!("Invalid block number: {}", err))),
+        },
+        None => 0,
+    };
+
+    let transfer_id = produce_consume::random_id();
+    println!("Transfer id: {}", transfer_id);
+
+    let action = payload::offer_transfer(&transfer_id, identifier, qty, recipient, expires);
+
+    let prefix = deployment.namespace_prefix();
+    let escrow_address = compute_escrow_address(&prefix, &transfer_id);
+    // What the holding lacks is taken from the quantity of the item held by no one
+    let item_address = compute_address(&prefix, identifier);
+    let mut input_addresses = vec![escrow_address.clone(), item_address.clone()];
+    let mut output_addresses = vec![escrow_address, item_address];
+    // The holding of the signer is updated, the payload saved for Sabre is not signed here
+    if let Some(submitter) = submitter {
+        let sender = produce_consume::read_public_key(&submitter.key)?;
+        let holding_address = compute_holding_address(&prefix, &sender, identifier);
+        input_addresses.push(holding_address.clone());
+        output_addresses.push(holding_address);
+    }
+    if expires != 0 {
+        input_addresses.push(BLOCK_INFO_NAMESPACE.to_string());
+    }
+    produce_consume::submit_action(
+        &action,
+        &input_addresses,
+        &output_addresses,
+        submitter,
+        deployment,
+    )
+}
+
+/// Accepts, declines or withdraws the offered transfer. The escrow is read first, the
+/// holdings of the sender and the recipient are part of the transaction.
+pub(crate) fn respond(
+    transfer_id: &str,
+    command: Action_Command,
+    submitter: Option<&Submitter>,
+    deployment: &Deployment,
+) -> Result<(), CliError> {
+    let submitter = match submitter {
+        Some(submitter) => submitter,
+        None => {
+            return Err(CliError::from(
+                "The REST API URL is required to respond to a transfer",
+            ))
+        }
+    };
+    let action = match command {
+        Action_Command::ACCEPT_TRANSFER => payload::accept_transfer(transfer_id),
+        Action_Command::DECLINE_TRANSFER => payload::decline_transfer(transfer_id),
+        Action_Command::WITHDRAW_TRANSFER => payload::withdraw_transfer(transfer_id),
+        other => {
+            return Err(CliError::from(format!(
+                "{:?} is not a response to a transfer",
+                other
+            )))
+        }
+    };
+
+    let prefix = deployment.namespace_prefix();
+    let escrow_address = compute_escrow_address(&prefix, transfer_id);
+    let escrow = read_escrow(&submitter.rest_api, transfer_id, deployment)?;
+    if effective_status(&escrow, current_block_num(&submitter.rest_api)?) == Escrow_Status::EXPIRED
+    {
+        println!(
+            "Transfer {} expired at block {}, it is returned to the sender",
+            transfer_id,
+            escrow.get_expires_at_block()
+        );
+    }
+
+    let sender_address =
+        compute_holding_address(&prefix, escrow.get_sender(), escrow.get_identifier());
+    let recipient_address =
+        compute_holding_address(&prefix, escrow.get_recipient(), escrow.get_identifier());
+    let mut input_addresses = vec![
+        escrow_address.clone(),
+        sender_address.clone(),
+        recipient_address.clone(),
+    ];
+    // Any response returns an expired offer to the sender
+    if escrow.get_expires_at_block() != 0 {
+        input_addresses.push(BLOCK_INFO_NAMESPACE.to_string());
+    }
+    let output_addresses = vec![escrow_address, sender_address, recipient_address];
+    produce_consume::submit_action(
+        &action,
+        &input_addresses,
+        &output_addresses,
+        Some(submitter),
+        deployment,
+    )
+}
+
+/// Prints the transfer. An offer past its expiry block is shown as expired, the escrow
+/// is back with the sender even if no response recorded it yet.
+pub(crate) fn show(
+    transfer_id: &str,
+    rest_api: &RestApi,
+    deployment: &Deployment,
+) -> Result<(), CliError> {
+    let escrow = read_escrow(rest_api, transfer_id, deployment)?;
+    let status = effective_status(&escrow, current_block_num(rest_api)?);
+    println!("Transfer {}", transfer_id);
+    println!("  item:      {}", escrow.get_identifier());
+    println!("  quantity:  {}", escrow.get_quantity());
+    println!("  sender:    {}", escrow.get_sender());
+    println!("  recipient: {}", escrow.get_recipient());
+    if escrow.get_expires_at_block() != 0 {
+        println!("  expires:   at block {}", escrow.get_expires_at_block());
+    }
+    println!("  status:    {:?}", status);
+    Ok(())
+}
+
+fn read_escrow(
+    rest_api: &RestApi,
+    transfer_id: &str,
+    deployment: &Deployment,
+) -> Result<Escrow, CliError> {
+    let escrow_address = compute_escrow_address(&deployment.namespace_prefix(), transfer_id);
+    match network_helper::get_state_entry(rest_api, &escrow_address)? {
+        Some(data) => codec::decode(&data)
+            .map_err(|err| CliError::from(format!("Unable to decode the transfer: {}", err))),
+        None => Err(CliError::from(format!(
+            "Transfer {} does not exist",
+            transfer_id
+        ))),
+    }
+}
+
+/// Number of the latest block recorded by the BlockInfo family, if it is enabled
+fn current_block_num(rest_api: &RestApi) -> Result<Option<u64>, CliError> {
+    match network_helper::get_state_entry(rest_api, &block_info_config_address())? {
+        Some(data) => codec::decode::<BlockInfoConfig>(&data)
+            .map(|config| Some(config.get_latest_block()))
+            .map_err(|err| CliError::from(format!("Unable to decode the BlockInfo: {}", err))),
+        None => Ok(None),
+    }
+}
+
+/// Status of the transfer as of the block, an offer is expired from its expiry block on
+fn effective_status(escrow: &Escrow, block_num: Option<u64>) -> Escrow_Status {
+    match (escrow.get_status(), block_num) {
+        (Escrow_Status::OFFERED, Some(block_num))
+            if escrow.get_expires_at_block() != 0 && block_num >= escrow.get_expires_at_block() =>
+        {
+            Escrow_Status::EXPIRED
+        }
+        (status, _) => status,
+    }
+}
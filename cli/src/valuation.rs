@@ -0,0 +1,100 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::cli_error::CliError;
+use crate::network_helper::{self, RestApi};
+use crate::produce_consume::{self, Deployment, Submitter};
+use crate::proto::state::Config;
+use crate::proto::state::CostLayers;
+use crate::proto::state::ValuationPolicy;
+use produce_consume_core::addressing::{
+    compute_config_address, compute_setting_address, get_cost_layers_prefix, ADMINS_SETTING,
+};
+use produce_consume_core::{codec, payload};
+
+/// Prints the on-hand quantity and value of every item with cost layers, followed by
+/// the totals across all the items.
+pub(crate) fn print_report(rest_api: &RestApi, deployment: &Deployment) -> Result<(), CliError> {
+    let prefix = deployment.namespace_prefix();
+    let policy = match network_helper::get_state_entry(rest_api, &compute_config_address(&prefix))?
+    {
+        Some(data) => codec::decode::<Config>(&data)
+            .map_err(|err| CliError::from(format!("Unable to decode the config: {}", err)))?
+            .get_valuation_policy(),
+        None => ValuationPolicy::FIFO,
+    };
+
+    let entries = network_helper::get_state_entries(rest_api, &get_cost_layers_prefix(&prefix))?;
+    let mut items = Vec::new();
+    for (address, data) in entries {
+        let layers: CostLayers = codec::decode(&data).map_err(|err| {
+            CliError::from(format!(
+                "Unable to decode the cost layers at {}: {}",
+                address, err
+            ))
+        })?;
+        let quantity: i64 = layers
+            .get_lots()
+            .iter()
+            .map(|lot| lot.get_quantity() as i64)
+            .sum();
+        let value: i64 = layers.get_lots().iter().map(|lot| lot.get_value()).sum();
+        items.push((layers.get_identifier().to_string(), quantity, value));
+    }
+    items.sort();
+
+    println!("Valuation policy: {:?}", policy);
+    println!("{:<20} {:>12} {:>16}", "IDENTIFIER", "QUANTITY", "VALUE");
+    for (identifier, quantity, value) in &items {
+        println!("{:<20} {:>12} {:>16}", identifier, quantity, value);
+    }
+    let total_quantity: i64 = items.iter().map(|item| item.1).sum();
+    let total_value: i64 = items.iter().map(|item| item.2).sum();
+    println!("{:<20} {:>12} {:>16}", "TOTAL", total_quantity, total_value);
+    Ok(())
+}
+
+/// Replaces the valuation policy, the signer must be one of the administrators
+pub(crate) fn set_policy(
+    policy: &str,
+    submitter: Option<&Submitter>,
+    deployment: &Deployment,
+) -> Result<(), CliError> {
+    let valuation_policy = match policy {
+        "FIFO" => ValuationPolicy::FIFO,
+        "WEIGHTED_AVERAGE" => ValuationPolicy::WEIGHTED_AVERAGE,
+        _ => {
+            return Err(CliError::from(format!(
+                "Unknown valuation policy {}, expected FIFO or WEIGHTED_AVERAGE",
+                policy
+            )))
+        }
+    };
+
+    let action = payload::set_valuation_policy(valuation_policy);
+
+    let config_address = compute_config_address(&deployment.namespace_prefix());
+    let input_addresses = vec![
+        compute_setting_address(ADMINS_SETTING),
+        config_address.clone(),
+    ];
+    let output_addresses = vec![config_address];
+    produce_consume::submit_action(
+        &action,
+        &input_addresses,
+        &output_addresses,
+        submitter,
+        deployment,
+    )
+}
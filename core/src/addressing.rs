@@ -0,0 +1,275 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Addresses of the state entries. An item lives under the namespace prefix derived
+//! from the family name, followed by the first 64 characters of the SHA-512 of its
+//! identifier, as it always did. Every other entry lives under the records namespace,
+//! derived from the namespace prefix, followed by two characters for the type of the
+//! entry and 62 characters derived from its key. An item address can take any value, so
+//! the records are kept out of the namespace of the items.
+
+use crypto::digest::Digest;
+use crypto::sha2::{Sha256, Sha512};
+
+/// Family name registered when none is configured. Sabre builds cannot be configured at
+/// runtime, they take it from the ```PC_FAMILY_NAME``` environment variable at compile
+/// time instead.
+pub const DEFAULT_FAMILY_NAME: &str = "produce-consume";
+/// Family version of the transactions and of the handler
+pub const VERSION: &str = "1.0";
+
+/// Address types, the two characters following the records namespace prefix
+pub const PROPOSAL_ADDRESS_TYPE: &str = "01";
+pub const CONFIG_ADDRESS_TYPE: &str = "02";
+pub const COST_LAYERS_ADDRESS_TYPE: &str = "03";
+pub const HOLDING_ADDRESS_TYPE: &str = "04";
+pub const ESCROW_ADDRESS_TYPE: &str = "05";
+pub const ITEM_TOTALS_ADDRESS_TYPE: &str = "06";
+pub const CATEGORY_TOTALS_ADDRESS_TYPE: &str = "07";
+pub const GLOBAL_TOTALS_ADDRESS_TYPE: &str = "08";
+pub const SIGNER_USAGE_ADDRESS_TYPE: &str = "09";
+
+/// Namespace of the Sawtooth Settings transaction family
+pub const SETTINGS_NAMESPACE: &str = "000000";
+/// Setting holding the comma separated public keys of the administrators
+pub const ADMINS_SETTING: &str = "produce_consume.admins";
+
+/// Namespace of the Sawtooth BlockInfo transaction family
+pub const BLOCK_INFO_NAMESPACE: &str = "00b10c";
+
+/// Family name the build was compiled with, ```PC_FAMILY_NAME``` if it was set
+pub fn default_family_name() -> &'static str {
+    option_env!("PC_FAMILY_NAME").unwrap_or(DEFAULT_FAMILY_NAME)
+}
+
+/// Namespace prefix of a family, the first 6 characters of the SHA-512 of its name.
+/// Every address of the family starts with it.
+pub fn compute_namespace_prefix(family_name: &str) -> String {
+    let mut sha = Sha512::new();
+    sha.input_str(family_name);
+    sha.result_str()[..6].to_string()
+}
+
+/// Namespace prefix of the records of a family, derived from its namespace prefix so
+/// that every address can still be computed from the latter. It never equals the
+/// namespace prefix of the items.
+pub fn compute_records_prefix(prefix: &str) -> String {
+    let mut sha = Sha512::new();
+    sha.input_str(prefix);
+    sha.input_str("/records");
+    let hash = sha.result_str();
+    hash.as_bytes()
+        .chunks_exact(6)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .find(|candidate| candidate != prefix)
+        .expect("The hash cannot repeat the prefix in every chunk")
+}
+
+/// Address of an item, the namespace prefix and the hash of the identifier
+pub fn compute_address(prefix: &str, identifier: &str) -> String {
+    let mut sha = Sha512::new();
+    sha.input_str(identifier);
+    prefix.to_string() + &sha.result_str()[..64]
+}
+
+pub fn compute_proposal_address(prefix: &str, proposal_id: &str) -> String {
+    compute_typed_address(prefix, PROPOSAL_ADDRESS_TYPE, proposal_id)
+}
+
+pub fn compute_cost_layers_address(prefix: &str, identifier: &str) -> String {
+    compute_typed_address(prefix, COST_LAYERS_ADDRESS_TYPE, identifier)
+}
+
+/// Address of the holding, the hash of the owner comes first so that all the holdings
+/// of an owner share a prefix
+pub fn compute_holding_address(prefix: &str, owner: &str, identifier: &str) -> String {
+    let mut owner_sha = Sha512::new();
+    owner_sha.input_str(owner);
+    let mut identifier_sha = Sha512::new();
+    identifier_sha.input_str(identifier);
+    compute_records_prefix(prefix)
+        + HOLDING_ADDRESS_TYPE
+        + &owner_sha.result_str()[..30]
+        + &identifier_sha.result_str()[..32]
+}
+
+pub fn compute_escrow_address(prefix: &str, transfer_id: &str) -> String {
+    compute_typed_address(prefix, ESCROW_ADDRESS_TYPE, transfer_id)
+}
+
+pub fn compute_item_totals_address(prefix: &str, identifier: &str) -> String {
+    compute_typed_address(prefix, ITEM_TOTALS_ADDRESS_TYPE, identifier)
+}
+
+pub fn compute_category_totals_address(prefix: &str, category: &str) -> String {
+    compute_typed_address(prefix, CATEGORY_TOTALS_ADDRESS_TYPE, category)
+}
+
+pub fn compute_signer_usage_address(prefix: &str, signer: &str) -> String {
+    compute_typed_address(prefix, SIGNER_USAGE_ADDRESS_TYPE, signer)
+}
+
+/// There is only one entry for the global totals in the namespace
+pub fn compute_global_totals_address(prefix: &str) -> String {
+    compute_records_prefix(prefix) + GLOBAL_TOTALS_ADDRESS_TYPE + &"0".repeat(62)
+}
+
+/// There is only one configuration entry in the namespace
+pub fn compute_config_address(prefix: &str) -> String {
+    compute_records_prefix(prefix) + CONFIG_ADDRESS_TYPE + &"0".repeat(62)
+}
+
+/// Addresses of the running totals updated by a PRODUCE or a CONSUME of the item. The
+/// category of the item is not known to a client, all the category totals are included.
+pub fn compute_totals_addresses(prefix: &str, identifier: &str) -> Vec<String> {
+    vec![
+        compute_item_totals_address(prefix, identifier),
+        get_category_totals_prefix(prefix),
+        compute_global_totals_address(prefix),
+    ]
+}
+
+/// Prefix shared by the quantities of all the items, the whole namespace
+pub fn get_item_prefix(prefix: &str) -> String {
+    prefix.to_string()
+}
+
+/// Prefix shared by all the proposals in the namespace
+pub fn get_proposal_prefix(prefix: &str) -> String {
+    compute_records_prefix(prefix) + PROPOSAL_ADDRESS_TYPE
+}
+
+/// Prefix shared by the cost layers of all the items
+pub fn get_cost_layers_prefix(prefix: &str) -> String {
+    compute_records_prefix(prefix) + COST_LAYERS_ADDRESS_TYPE
+}
+
+/// Prefix shared by the totals of all the categories
+pub fn get_category_totals_prefix(prefix: &str) -> String {
+    compute_records_prefix(prefix) + CATEGORY_TOTALS_ADDRESS_TYPE
+}
+
+fn compute_typed_address(prefix: &str, address_type: &str, key: &str) -> String {
+    let mut sha = Sha512::new();
+    sha.input_str(key);
+    let remaining = sha.result_str()[..62].to_string();
+    compute_records_prefix(prefix) + address_type + &remaining
+}
+
+/// Address of a Sawtooth setting, the key is split in at most four parts on '.' and
+/// the first 16 characters of the SHA-256 of each part are concatenated.
+pub fn compute_setting_address(key: &str) -> String {
+    let mut parts: Vec<&str> = key.splitn(4, '.').collect();
+    while parts.len() < 4 {
+        parts.push("");
+    }
+    let hashed: Vec<String> = parts
+        .iter()
+        .map(|part| {
+            let mut sha = Sha256::new();
+            sha.input_str(part);
+            sha.result_str()[..16].to_string()
+        })
+        .collect();
+    SETTINGS_NAMESPACE.to_string() + &hashed.join("")
+}
+
+/// Address of the ```BlockInfoConfig``` entry, the namespace followed by "01" and zeros
+pub fn block_info_config_address() -> String {
+    format!("{}01{}", BLOCK_INFO_NAMESPACE, "0".repeat(62))
+}
+
+/// Address of the ```BlockInfo``` entry for the given block number, the namespace followed
+/// by "00" and the zero padded block number in hex
+pub fn block_info_address(block_num: u64) -> String {
+    format!("{}00{:062x}", BLOCK_INFO_NAMESPACE, block_num)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OWNER: &str = "02b4d3a9f6e1c7d8e5a2b0c9f8e7d6c5b4a3928170f6e5d4c3b2a1908f7e6d5c4b";
+
+    #[test]
+    fn default_namespace_prefix_is_stable() {
+        assert_eq!(compute_namespace_prefix(DEFAULT_FAMILY_NAME), "ce2292");
+    }
+
+    #[test]
+    fn default_records_prefix_is_stable() {
+        assert_eq!(compute_records_prefix("ce2292"), "62dd1e");
+    }
+
+    #[test]
+    fn item_address_is_the_hash_of_the_identifier() {
+        // The address items were always stored at, existing entries stay readable
+        assert_eq!(
+            compute_address("ce2292", "Bread"),
+            "ce2292d27d94aaf72ae1699aa78dbc3323a48a42903ce05e5c48ba280cc8a9c1da9d2a"
+        );
+        assert_eq!(get_item_prefix("ce2292"), "ce2292");
+    }
+
+    #[test]
+    fn records_are_70_characters_under_the_records_prefix() {
+        let prefix = compute_namespace_prefix(DEFAULT_FAMILY_NAME);
+        let records_prefix = compute_records_prefix(&prefix);
+        assert_ne!(records_prefix, prefix);
+        let addresses = vec![
+            compute_proposal_address(&prefix, "proposal"),
+            compute_config_address(&prefix),
+            compute_cost_layers_address(&prefix, "Bread"),
+            compute_holding_address(&prefix, OWNER, "Bread"),
+            compute_escrow_address(&prefix, "transfer"),
+            compute_item_totals_address(&prefix, "Bread"),
+            compute_category_totals_address(&prefix, "Bakery"),
+            compute_global_totals_address(&prefix),
+            compute_signer_usage_address(&prefix, OWNER),
+        ];
+        for (index, address) in addresses.iter().enumerate() {
+            assert_eq!(address.len(), 70);
+            assert!(address.starts_with(&records_prefix));
+            assert_eq!(&address[6..8], format!("{:02}", index + 1));
+        }
+    }
+
+    #[test]
+    fn holdings_of_an_owner_share_a_prefix() {
+        let prefix = compute_namespace_prefix(DEFAULT_FAMILY_NAME);
+        let bread = compute_holding_address(&prefix, OWNER, "Bread");
+        let milk = compute_holding_address(&prefix, OWNER, "Milk");
+        assert_eq!(bread[..38], milk[..38]);
+        assert_ne!(bread, milk);
+    }
+
+    #[test]
+    fn families_have_distinct_addresses() {
+        let default = compute_namespace_prefix(DEFAULT_FAMILY_NAME);
+        let staging = compute_namespace_prefix("produce-consume-staging");
+        assert_ne!(
+            compute_address(&default, "Bread"),
+            compute_address(&staging, "Bread")
+        );
+    }
+
+    #[test]
+    fn settings_and_block_info_addresses_are_outside_the_namespace() {
+        let admins = compute_setting_address(ADMINS_SETTING);
+        assert_eq!(admins.len(), 70);
+        assert!(admins.starts_with(SETTINGS_NAMESPACE));
+        assert_eq!(block_info_config_address().len(), 70);
+        assert!(block_info_address(255).ends_with("ff"));
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Encoding of the state entries, protobuf messages from the ```state``` proto. The
+//! items written before the ```Item``` message was introduced hold the raw bytes of
+//! their quantity, they are still read.
+
+use crate::addressing::compute_address;
+use crate::proto::state::Item;
+use std::error::Error;
+use std::fmt;
+
+/// A state entry or a payload that cannot be encoded or decoded
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodecError(pub String);
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for CodecError {}
+
+/// Bytes stored at the address of an item
+pub fn encode_item(identifier: &str, quantity: i32) -> Result<Vec<u8>, CodecError> {
+    let mut item = Item::new();
+    item.set_identifier(identifier.to_string());
+    item.set_quantity(quantity);
+    encode(&item)
+}
+
+/// Item stored at the address of the identifier. A former entry, only the raw bytes of
+/// the quantity, is returned with the identifier filled in.
+pub fn decode_item(identifier: &str, data: &[u8]) -> Result<Item, CodecError> {
+    if let Ok(item) = decode::<Item>(data) {
+        if item.get_identifier() == identifier {
+            return Ok(item);
+        }
+    }
+    let mut item = decode_former_item(data)?;
+    item.set_identifier(identifier.to_string());
+    Ok(item)
+}
+
+/// Item stored at the address, when the identifier is not known beforehand. A former
+/// entry is returned with an empty identifier, it cannot be recovered from the address.
+pub fn decode_item_at(prefix: &str, address: &str, data: &[u8]) -> Result<Item, CodecError> {
+    if let Ok(item) = decode::<Item>(data) {
+        // Also tells a message apart from a quantity whose bytes happen to parse
+        if compute_address(prefix, item.get_identifier()) == address {
+            return Ok(item);
+        }
+    }
+    decode_former_item(data)
+}
+
+fn decode_former_item(data: &[u8]) -> Result<Item, CodecError> {
+    if data.len() != 4 {
+        return Err(CodecError(format!(
+            "Malformed item of {} bytes",
+            data.len()
+        )));
+    }
+    let mut item = Item::new();
+    item.set_quantity(decode_quantity(data)?);
+    Ok(item)
+}
+
+/// Bytes of the quantity as stored at the address of an item before the ```Item```
+/// message, kept to write the former entries in tests
+pub fn encode_quantity(quantity: i32) -> Vec<u8> {
+    quantity.to_ne_bytes().to_vec()
+}
+
+/// Quantity stored in a former item entry, fails if there are less than 4 bytes
+pub fn decode_quantity(data: &[u8]) -> Result<i32, CodecError> {
+    if data.len() < 4 {
+        return Err(CodecError(format!(
+            "Malformed quantity of {} bytes",
+            data.len()
+        )));
+    }
+    let mut array: [u8; 4] = [0; 4];
+    array.copy_from_slice(&data[..4]);
+    Ok(i32::from_ne_bytes(array))
+}
+
+pub fn encode<T>(message: &T) -> Result<Vec<u8>, CodecError>
+where
+    T: protobuf::Message,
+{
+    message
+        .write_to_bytes()
+        .map_err(|err| CodecError(format!("Failed to serialize: {:?}", err)))
+}
+
+pub fn decode<T>(data: &[u8]) -> Result<T, CodecError>
+where
+    T: protobuf::Message,
+{
+    protobuf::parse_from_bytes(data)
+        .map_err(|err| CodecError(format!("Failed to unmarshal: {:?}", err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::addressing::{compute_namespace_prefix, DEFAULT_FAMILY_NAME};
+    use crate::proto::state::Holding;
+
+    #[test]
+    fn quantity_round_trips() {
+        for quantity in &[0, 1, -5, i32::max_value()] {
+            assert_eq!(decode_quantity(&encode_quantity(*quantity)), Ok(*quantity));
+        }
+    }
+
+    #[test]
+    fn short_quantity_is_rejected() {
+        assert!(decode_quantity(&[1, 2, 3]).is_err());
+        assert!(decode_quantity(&[]).is_err());
+    }
+
+    #[test]
+    fn item_round_trips() {
+        let data = encode_item("Bread", 10).unwrap();
+        let item = decode_item("Bread", &data).unwrap();
+        assert_eq!(item.get_identifier(), "Bread");
+        assert_eq!(item.get_quantity(), 10);
+    }
+
+    #[test]
+    fn former_items_are_read() {
+        let data = encode_quantity(522);
+        let item = decode_item("Bread", &data).unwrap();
+        assert_eq!(item.get_identifier(), "Bread");
+        assert_eq!(item.get_quantity(), 522);
+        assert!(decode_item("Bread", &[1, 2, 3, 4, 5]).is_err());
+    }
+
+    #[test]
+    fn listed_items_are_checked_against_their_address() {
+        let prefix = compute_namespace_prefix(DEFAULT_FAMILY_NAME);
+        let address = compute_address(&prefix, "Bread");
+        let item = decode_item_at(&prefix, &address, &encode_item("Bread", 3).unwrap()).unwrap();
+        assert_eq!(item.get_identifier(), "Bread");
+        assert_eq!(item.get_quantity(), 3);
+
+        // 522 is 0a 02 00 00, a message with a two characters identifier
+        let former = decode_item_at(&prefix, &address, &encode_quantity(522)).unwrap();
+        assert_eq!(former.get_identifier(), "");
+        assert_eq!(former.get_quantity(), 522);
+    }
+
+    #[test]
+    fn message_round_trips() {
+        let mut holding = Holding::new();
+        holding.set_owner("owner".to_string());
+        holding.set_identifier("Bread".to_string());
+        holding.set_quantity(7);
+        let decoded: Holding = decode(&encode(&holding).unwrap()).unwrap();
+        assert_eq!(decoded, holding);
+    }
+
+    #[test]
+    fn garbage_is_not_a_message() {
+        assert!(decode::<Holding>(&[0xff, 0xff, 0xff]).is_err());
+    }
+}
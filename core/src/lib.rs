@@ -12,4 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-include!(concat!(env!("OUT_DIR"), "/protos/mod.rs"));
+//! Parts of produce-consume shared by the transaction processor and its clients: the
+//! generated protos, the state addresses, the payloads and the encoding of the state
+//! entries. The processor and the CLI must agree on all of them, a change here is seen
+//! by both.
+
+extern crate crypto;
+extern crate protobuf;
+
+pub mod addressing;
+pub mod codec;
+pub mod payload;
+pub mod proto;
@@ -0,0 +1,252 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Builders of the ```Action``` payloads and the checks the processor applies to them
+//! before anything is read from the state.
+
+use crate::codec::{self, CodecError};
+use crate::proto::action::Action;
+use crate::proto::action::Action_Command;
+use crate::proto::state::ApprovalPolicy;
+use crate::proto::state::RateLimit;
+use crate::proto::state::ValuationPolicy;
+use std::error::Error;
+use std::fmt;
+
+/// An action missing a field required by its command, or with an out of range value
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidAction(pub String);
+
+impl fmt::Display for InvalidAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for InvalidAction {}
+
+/// Adds the quantity of the item to the store and the holding of the signer. A zero
+/// unit cost and an empty category leave them unset.
+pub fn produce(identifier: &str, quantity: i32, unit_cost: i64, category: &str) -> Action {
+    let mut action = item_action(Action_Command::PRODUCE, identifier, quantity);
+    action.set_unit_cost(unit_cost);
+    action.set_category(category.to_string());
+    action
+}
+
+/// Removes the quantity of the item, the proposal id is used only if the consumption
+/// requires approvals
+pub fn consume(identifier: &str, quantity: i32, proposal_id: &str) -> Action {
+    let mut action = item_action(Action_Command::CONSUME, identifier, quantity);
+    action.set_proposal_id(proposal_id.to_string());
+    action
+}
+
+pub fn approve(proposal_id: &str) -> Action {
+    proposal_action(Action_Command::APPROVE, proposal_id)
+}
+
+pub fn reject(proposal_id: &str) -> Action {
+    proposal_action(Action_Command::REJECT, proposal_id)
+}
+
+pub fn set_approval_policy(policy: ApprovalPolicy) -> Action {
+    let mut action = Action::new();
+    action.set_command(Action_Command::SET_APPROVAL_POLICY);
+    action.set_approval_policy(policy);
+    action
+}
+
+pub fn set_valuation_policy(policy: ValuationPolicy) -> Action {
+    let mut action = Action::new();
+    action.set_command(Action_Command::SET_VALUATION_POLICY);
+    action.set_valuation_policy(policy);
+    action
+}
+
+pub fn set_rate_limit(limit: RateLimit) -> Action {
+    let mut action = Action::new();
+    action.set_command(Action_Command::SET_RATE_LIMIT);
+    action.set_rate_limit(limit);
+    action
+}
+
+/// Offers the quantity of the item held by the signer to the recipient, an expiry of
+/// zero means the offer does not expire
+pub fn offer_transfer(
+    transfer_id: &str,
+    identifier: &str,
+    quantity: i32,
+    recipient: &str,
+    expires_at_block: u64,
+) -> Action {
+    let mut action = item_action(Action_Command::OFFER_TRANSFER, identifier, quantity);
+    action.set_transfer_id(transfer_id.to_string());
+    action.set_recipient(recipient.to_string());
+    action.set_expires_at_block(expires_at_block);
+    action
+}
+
+pub fn accept_transfer(transfer_id: &str) -> Action {
+    transfer_action(Action_Command::ACCEPT_TRANSFER, transfer_id)
+}
+
+pub fn decline_transfer(transfer_id: &str) -> Action {
+    transfer_action(Action_Command::DECLINE_TRANSFER, transfer_id)
+}
+
+pub fn withdraw_transfer(transfer_id: &str) -> Action {
+    transfer_action(Action_Command::WITHDRAW_TRANSFER, transfer_id)
+}
+
+fn item_action(command: Action_Command, identifier: &str, quantity: i32) -> Action {
+    let mut action = Action::new();
+    action.set_command(command);
+    action.set_identifier(identifier.to_string());
+    action.set_quantity(quantity);
+    action
+}
+
+fn proposal_action(command: Action_Command, proposal_id: &str) -> Action {
+    let mut action = Action::new();
+    action.set_command(command);
+    action.set_proposal_id(proposal_id.to_string());
+    action
+}
+
+fn transfer_action(command: Action_Command, transfer_id: &str) -> Action {
+    let mut action = Action::new();
+    action.set_command(command);
+    action.set_transfer_id(transfer_id.to_string());
+    action
+}
+
+pub fn encode_action(action: &Action) -> Result<Vec<u8>, CodecError> {
+    codec::encode(action)
+}
+
+pub fn decode_action(data: &[u8]) -> Result<Action, CodecError> {
+    codec::decode(data)
+}
+
+/// Checks that the fields required by the command are present
+pub fn validate(action: &Action) -> Result<(), InvalidAction> {
+    match action.get_command() {
+        Action_Command::PRODUCE | Action_Command::CONSUME => {
+            if action.get_identifier().is_empty() {
+                return Err(InvalidAction("Identifier is required".to_string()));
+            }
+            if action.get_quantity() <= 0 {
+                return Err(InvalidAction("Quantity must be positive".to_string()));
+            }
+            if action.get_unit_cost() < 0 {
+                return Err(InvalidAction("Unit cost cannot be negative".to_string()));
+            }
+        }
+        Action_Command::APPROVE | Action_Command::REJECT => {
+            if action.get_proposal_id().is_empty() {
+                return Err(InvalidAction("Proposal id is required".to_string()));
+            }
+        }
+        Action_Command::SET_APPROVAL_POLICY => {
+            if !action.has_approval_policy() {
+                return Err(InvalidAction("Approval policy is required".to_string()));
+            }
+        }
+        Action_Command::SET_VALUATION_POLICY => (),
+        Action_Command::SET_RATE_LIMIT => {
+            if !action.has_rate_limit() {
+                return Err(InvalidAction("Rate limit is required".to_string()));
+            }
+            if action.get_rate_limit().get_max_quantity() < 0 {
+                return Err(InvalidAction(
+                    "Maximum quantity cannot be negative".to_string(),
+                ));
+            }
+        }
+        Action_Command::OFFER_TRANSFER => {
+            if action.get_identifier().is_empty() || action.get_recipient().is_empty() {
+                return Err(InvalidAction(
+                    "Identifier and recipient are required".to_string(),
+                ));
+            }
+            if action.get_quantity() <= 0 {
+                return Err(InvalidAction("Quantity must be positive".to_string()));
+            }
+            if action.get_transfer_id().is_empty() {
+                return Err(InvalidAction("Transfer id is required".to_string()));
+            }
+        }
+        Action_Command::ACCEPT_TRANSFER
+        | Action_Command::DECLINE_TRANSFER
+        | Action_Command::WITHDRAW_TRANSFER => {
+            if action.get_transfer_id().is_empty() {
+                return Err(InvalidAction("Transfer id is required".to_string()));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_actions_are_valid() {
+        let actions = vec![
+            produce("Bread", 10, 250, "Bakery"),
+            consume("Bread", 2, "proposal"),
+            approve("proposal"),
+            reject("proposal"),
+            set_approval_policy(ApprovalPolicy::new()),
+            set_valuation_policy(ValuationPolicy::WEIGHTED_AVERAGE),
+            set_rate_limit(RateLimit::new()),
+            offer_transfer("transfer", "Bread", 5, "recipient", 0),
+            accept_transfer("transfer"),
+            decline_transfer("transfer"),
+            withdraw_transfer("transfer"),
+        ];
+        for action in actions {
+            assert_eq!(validate(&action), Ok(()), "{:?}", action.get_command());
+        }
+    }
+
+    #[test]
+    fn action_round_trips() {
+        let mut action = produce("Bread", 10, 250, "Bakery");
+        action.set_valid_before(1_700_000_000);
+        let decoded = decode_action(&encode_action(&action).unwrap()).unwrap();
+        assert_eq!(decoded, action);
+    }
+
+    #[test]
+    fn missing_fields_are_reported() {
+        assert_eq!(
+            validate(&produce("", 10, 0, "")),
+            Err(InvalidAction("Identifier is required".to_string()))
+        );
+        assert_eq!(
+            validate(&consume("Bread", 0, "")),
+            Err(InvalidAction("Quantity must be positive".to_string()))
+        );
+        assert_eq!(
+            validate(&offer_transfer("", "Bread", 5, "recipient", 0)),
+            Err(InvalidAction("Transfer id is required".to_string()))
+        );
+        let mut action = Action::new();
+        action.set_command(Action_Command::SET_RATE_LIMIT);
+        assert!(validate(&action).is_err());
+    }
+}
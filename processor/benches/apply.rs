@@ -0,0 +1,206 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cost of applying produce-consume transactions, without a validator.
+//!
+//! Every iteration applies its transactions to a fresh copy of a prepared in-memory
+//! state, so the state does not grow with the number of iterations. Run with
+//! ```cargo bench``` from the processor directory, criterion reports the time per
+//! transaction and compares it with the previous run.
+
+#[macro_use]
+extern crate criterion;
+extern crate produce_consume;
+extern crate produce_consume_core;
+extern crate protobuf;
+extern crate sawtooth_sdk;
+
+use criterion::{BatchSize, Criterion, Throughput};
+use produce_consume::produce_consume::handler::ProduceConsumeHandler;
+use produce_consume::produce_consume::memory_context::InMemoryTransactionContext;
+use produce_consume::produce_consume::payload::ProduceConsumePayload;
+use produce_consume_core::addressing::{
+    compute_address, compute_holding_address, compute_namespace_prefix, DEFAULT_FAMILY_NAME,
+};
+use produce_consume_core::proto::action::{Action, Action_Command};
+use protobuf::Message;
+use sawtooth_sdk::messages::processor::TpProcessRequest;
+use sawtooth_sdk::messages::transaction::TransactionHeader;
+use sawtooth_sdk::processor::handler::TransactionHandler;
+
+const SIGNER: &str = "02b4d3a9f6e1c7d8e5a2b0c9f8e7d6c5b4a3928170f6e5d4c3b2a1908f7e6d5c4b";
+const RECIPIENT: &str = "03a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f90";
+const ITEMS: usize = 1000;
+
+fn action(command: Action_Command, identifier: &str, quantity: i32) -> Action {
+    let mut action = Action::new();
+    action.set_command(command);
+    action.set_identifier(identifier.to_string());
+    action.set_quantity(quantity);
+    action
+}
+
+/// A PRODUCE with every optional field a client would usually set
+fn full_produce(identifier: &str) -> Action {
+    let mut produce = action(Action_Command::PRODUCE, identifier, 10);
+    produce.set_category("Bakery".to_string());
+    produce.set_unit_cost(250);
+    produce
+}
+
+fn request(action: &Action, signer: &str) -> TpProcessRequest {
+    let mut header = TransactionHeader::new();
+    header.set_signer_public_key(signer.to_string());
+    let mut request = TpProcessRequest::new();
+    request.set_header(header);
+    request.set_payload(action.write_to_bytes().unwrap());
+    request
+}
+
+fn apply_all(context: &mut InMemoryTransactionContext, requests: &[TpProcessRequest]) {
+    let handler = ProduceConsumeHandler::new();
+    for request in requests {
+        handler.apply(request, context).unwrap();
+    }
+}
+
+fn item_name(index: usize) -> String {
+    format!("Item-{:05}", index)
+}
+
+fn bench_payload_parsing(c: &mut Criterion) {
+    let bytes = full_produce("Bread").write_to_bytes().unwrap();
+    let mut group = c.benchmark_group("payload");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("parse", |b| {
+        b.iter(|| ProduceConsumePayload::new(&bytes).unwrap())
+    });
+    group.finish();
+}
+
+fn bench_addresses(c: &mut Criterion) {
+    let mut group = c.benchmark_group("address");
+    group.throughput(Throughput::Elements(1));
+    let prefix = compute_namespace_prefix(DEFAULT_FAMILY_NAME);
+    group.bench_function("item", |b| b.iter(|| compute_address(&prefix, "Bread")));
+    group.bench_function("holding", |b| {
+        b.iter(|| compute_holding_address(&prefix, SIGNER, "Bread"))
+    });
+    group.finish();
+}
+
+/// Every transaction reads and writes the same item, as when one product dominates
+fn bench_hot_item(c: &mut Criterion) {
+    let mut prepared = InMemoryTransactionContext::new();
+    apply_all(&mut prepared, &[request(&full_produce("Bread"), SIGNER)]);
+    let produce = [request(&full_produce("Bread"), SIGNER)];
+    let consume = [request(
+        &action(Action_Command::CONSUME, "Bread", 1),
+        SIGNER,
+    )];
+
+    let mut group = c.benchmark_group("hot_item");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("produce", |b| {
+        b.iter_batched(
+            || prepared.checkpoint(),
+            |mut context| apply_all(&mut context, &produce),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("consume", |b| {
+        b.iter_batched(
+            || prepared.checkpoint(),
+            |mut context| apply_all(&mut context, &consume),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+/// Transactions spread over many items already in the state
+fn bench_many_items(c: &mut Criterion) {
+    let mut prepared = InMemoryTransactionContext::new();
+    let produces: Vec<TpProcessRequest> = (0..ITEMS)
+        .map(|index| request(&full_produce(&item_name(index)), SIGNER))
+        .collect();
+    apply_all(&mut prepared, &produces);
+    let consumes: Vec<TpProcessRequest> = (0..ITEMS)
+        .map(|index| {
+            request(
+                &action(Action_Command::CONSUME, &item_name(index), 1),
+                SIGNER,
+            )
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("many_items");
+    group.sample_size(20);
+    group.throughput(Throughput::Elements(ITEMS as u64));
+    group.bench_function("produce", |b| {
+        b.iter_batched(
+            || prepared.checkpoint(),
+            |mut context| apply_all(&mut context, &produces),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("consume", |b| {
+        b.iter_batched(
+            || prepared.checkpoint(),
+            |mut context| apply_all(&mut context, &consumes),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+/// A realistic sequence of single-action transactions on one item: produced, partly
+/// consumed and partly handed over to another owner with a two-phase transfer. A
+/// payload carries one action, so this is what a client sends for several actions.
+fn bench_action_sequence(c: &mut Criterion) {
+    let mut offer = action(Action_Command::OFFER_TRANSFER, "Bread", 3);
+    offer.set_recipient(RECIPIENT.to_string());
+    offer.set_transfer_id("transfer-1".to_string());
+    let mut accept = Action::new();
+    accept.set_command(Action_Command::ACCEPT_TRANSFER);
+    accept.set_transfer_id("transfer-1".to_string());
+    let actions = [
+        request(&full_produce("Bread"), SIGNER),
+        request(&action(Action_Command::CONSUME, "Bread", 4), SIGNER),
+        request(&offer, SIGNER),
+        request(&accept, RECIPIENT),
+        request(&action(Action_Command::CONSUME, "Bread", 2), RECIPIENT),
+    ];
+
+    let mut group = c.benchmark_group("action_sequence");
+    group.throughput(Throughput::Elements(actions.len() as u64));
+    group.bench_function("produce_consume_transfer", |b| {
+        b.iter_batched(
+            InMemoryTransactionContext::new,
+            |mut context| apply_all(&mut context, &actions),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_payload_parsing,
+    bench_addresses,
+    bench_hot_item,
+    bench_many_items,
+    bench_action_sequence
+);
+criterion_main!(benches);
@@ -0,0 +1,217 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// Read when no ```--config``` is given, it is fine for this file to be missing
+pub(crate) const DEFAULT_CONFIG_FILE: &str = "/etc/produce-consume/processor.toml";
+
+/// Settings of the transaction processor.
+///
+/// The precedence, from the lowest to the highest, is: the defaults, the config file,
+/// the ```PC_*``` environment variables and the command line arguments.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ProcessorConfig {
+    pub(crate) validator: ValidatorConfig,
+    pub(crate) log: LogConfig,
+    pub(crate) family: FamilyConfig,
+    pub(crate) http: HttpConfig,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ValidatorConfig {
+    /// Connect endpoint for the validator, ```PC_CONNECT```
+    pub(crate) endpoint: String,
+    /// Seconds to wait before connecting again after the first failed attempt, doubled
+    /// after every failed attempt, ```PC_RECONNECT_MIN_DELAY```
+    pub(crate) reconnect_min_delay: u64,
+    /// Cap of the delay between two attempts in seconds, ```PC_RECONNECT_MAX_DELAY```
+    pub(crate) reconnect_max_delay: u64,
+}
+
+impl Default for ValidatorConfig {
+    fn default() -> ValidatorConfig {
+        ValidatorConfig {
+            endpoint: "tcp://localhost:4004".to_string(),
+            reconnect_min_delay: 1,
+            reconnect_max_delay: 60,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct LogConfig {
+    /// One of off, error, warn, info, debug or trace, ```PC_LOG_LEVEL```
+    pub(crate) level: String,
+    /// Logs are appended to this file as well as the console, ```PC_LOG_FILE```
+    pub(crate) file: Option<String>,
+    /// The log file is rotated once it reaches this size, for example "10MB",
+    /// ```PC_LOG_MAX_SIZE```
+    pub(crate) max_size: Option<String>,
+    /// Number of rotated log files kept, ```PC_LOG_KEEP```
+    pub(crate) keep: u32,
+    /// log4rs YAML file replacing all the other log settings, ```PC_LOG_CONFIG```
+    pub(crate) config: Option<String>,
+}
+
+impl Default for LogConfig {
+    fn default() -> LogConfig {
+        LogConfig {
+            level: "warn".to_string(),
+            file: None,
+            max_size: None,
+            keep: 5,
+            config: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct FamilyConfig {
+    /// Family name the handler registers for, the namespace prefix is derived from it.
+    /// Deployments with different names share a network without sharing their state,
+    /// ```PC_FAMILY_NAME```
+    pub(crate) name: Option<String>,
+    /// Family version the handler registers for, ```PC_FAMILY_VERSION```
+    pub(crate) version: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct HttpConfig {
+    /// Address to serve ```/metrics```, ```/healthz``` and ```/readyz``` on, for example
+    /// "0.0.0.0:9100". Nothing is served if it is not set, ```PC_HTTP_ADDRESS```
+    pub(crate) address: Option<String>,
+}
+
+impl ProcessorConfig {
+    /// Reads the config file given with ```--config```, or the default one if it exists
+    pub(crate) fn load(path: Option<&str>) -> Result<ProcessorConfig, String> {
+        let path = match path {
+            Some(path) => path,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => DEFAULT_CONFIG_FILE,
+            None => return Ok(ProcessorConfig::default()),
+        };
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("Unable to read the config file {}: {}", path, err))?;
+        ProcessorConfig::parse(&content)
+            .map_err(|err| format!("Invalid config file {}: {}", path, err))
+    }
+
+    pub(crate) fn parse(content: &str) -> Result<ProcessorConfig, String> {
+        toml::from_str(content).map_err(|err| err.to_string())
+    }
+
+    /// Overrides the settings with the ```PC_*``` environment variables that are set
+    pub(crate) fn apply_env(&mut self) -> Result<(), String> {
+        self.apply_overrides(|name| env::var(name).ok())
+    }
+
+    fn apply_overrides<F>(&mut self, lookup: F) -> Result<(), String>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if let Some(endpoint) = lookup("PC_CONNECT") {
+            self.validator.endpoint = endpoint;
+        }
+        if let Some(delay) = lookup("PC_RECONNECT_MIN_DELAY") {
+            self.validator.reconnect_min_delay = delay
+                .parse()
+                .map_err(|_| format!("Invalid PC_RECONNECT_MIN_DELAY {}", delay))?;
+        }
+        if let Some(delay) = lookup("PC_RECONNECT_MAX_DELAY") {
+            self.validator.reconnect_max_delay = delay
+                .parse()
+                .map_err(|_| format!("Invalid PC_RECONNECT_MAX_DELAY {}", delay))?;
+        }
+        if let Some(level) = lookup("PC_LOG_LEVEL") {
+            self.log.level = level;
+        }
+        if let Some(file) = lookup("PC_LOG_FILE") {
+            self.log.file = Some(file);
+        }
+        if let Some(max_size) = lookup("PC_LOG_MAX_SIZE") {
+            self.log.max_size = Some(max_size);
+        }
+        if let Some(keep) = lookup("PC_LOG_KEEP") {
+            self.log.keep = keep
+                .parse()
+                .map_err(|_| format!("Invalid PC_LOG_KEEP {}", keep))?;
+        }
+        if let Some(config) = lookup("PC_LOG_CONFIG") {
+            self.log.config = Some(config);
+        }
+        if let Some(name) = lookup("PC_FAMILY_NAME") {
+            self.family.name = Some(name);
+        }
+        if let Some(version) = lookup("PC_FAMILY_VERSION") {
+            self.family.version = Some(version);
+        }
+        if let Some(address) = lookup("PC_HTTP_ADDRESS") {
+            self.http.address = Some(address);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_sections_keep_the_defaults() {
+        let config = ProcessorConfig::parse("[log]\nlevel = \"debug\"\n").unwrap();
+        assert_eq!(config.validator, ValidatorConfig::default());
+        assert_eq!(config.log.level, "debug");
+        assert_eq!(config.log.file, None);
+        assert_eq!(config.family.name, None);
+        assert_eq!(config.family.version, None);
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        assert!(ProcessorConfig::parse("[validator]\nendpiont = \"tcp://v:4004\"\n").is_err());
+    }
+
+    #[test]
+    fn environment_overrides_the_file() {
+        let mut config = ProcessorConfig::parse(
+            "[validator]\nendpoint = \"tcp://file:4004\"\n[log]\nlevel = \"info\"\n",
+        )
+        .unwrap();
+        config
+            .apply_overrides(|name| match name {
+                "PC_CONNECT" => Some("tcp://env:4004".to_string()),
+                "PC_LOG_KEEP" => Some("3".to_string()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(config.validator.endpoint, "tcp://env:4004");
+        assert_eq!(config.log.level, "info");
+        assert_eq!(config.log.keep, 3);
+        assert!(config
+            .apply_overrides(|name| match name {
+                "PC_LOG_KEEP" => Some("many".to_string()),
+                _ => None,
+            })
+            .is_err());
+    }
+}
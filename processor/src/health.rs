@@ -0,0 +1,80 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::supervisor;
+use protobuf::Message;
+use sawtooth_sdk::messages::client_block::ClientBlockListRequest;
+use sawtooth_sdk::messages::client_list_control::ClientPagingControls;
+use sawtooth_sdk::messages::validator::Message_MessageType;
+use sawtooth_sdk::messaging::stream::{MessageConnection, MessageSender};
+use sawtooth_sdk::messaging::zmq_stream::{ZmqMessageConnection, ZmqMessageSender};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Readiness of the processor, served on ```/readyz```.
+///
+/// The processor is ready while its handler is registered with the validator, the
+/// supervisor updates it on every registration and every lost connection.
+#[derive(Default)]
+pub(crate) struct Health {
+    ready: AtomicBool,
+}
+
+impl Health {
+    pub(crate) fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn set_ready(&self, ready: bool) {
+        if ready != self.ready.swap(ready, Ordering::SeqCst) {
+            info!("Processor readiness changed to {}", ready);
+        }
+    }
+}
+
+/// Connects to the validator and checks that it answers, for ```--check```
+pub(crate) fn check_validator(endpoint: &str, timeout: Duration) -> Result<(), String> {
+    let connection = ZmqMessageConnection::new(endpoint);
+    let (mut sender, _receiver) = connection.create();
+    let result = probe(&mut sender, timeout)
+        .map_err(|err| format!("Validator {} is not reachable: {}", endpoint, err));
+    sender.close();
+    result
+}
+
+/// Sends a request over the connection and waits for any answer. The request lists at
+/// most one block, it is cheap and answered by every validator.
+fn probe(sender: &mut ZmqMessageSender, timeout: Duration) -> Result<(), String> {
+    let mut paging = ClientPagingControls::new();
+    paging.set_limit(1);
+    let mut request = ClientBlockListRequest::new();
+    request.set_paging(paging);
+    let content = request
+        .write_to_bytes()
+        .map_err(|err| format!("Unable to serialize the request: {:?}", err))?;
+
+    sender
+        .send(
+            Message_MessageType::CLIENT_BLOCK_LIST_REQUEST,
+            &supervisor::correlation_id(),
+            &content,
+        )
+        .map_err(|err| format!("Unable to send: {:?}", err))
+        .and_then(|mut future| {
+            future
+                .get_timeout(timeout)
+                .map(|_| ())
+                .map_err(|err| format!("No answer within {:?}: {:?}", timeout, err))
+        })
+}
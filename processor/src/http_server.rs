@@ -0,0 +1,56 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::health::Health;
+use crate::metrics::Metrics;
+use std::sync::Arc;
+use std::thread;
+use tiny_http::{Header, Response, Server};
+
+/// Serves the status of the processor over HTTP from a background thread:
+/// ```/metrics``` in the Prometheus text format, ```/healthz``` answers as long as the
+/// process is alive and ```/readyz``` only while the processor is ready.
+pub(crate) fn start(
+    address: &str,
+    metrics: Arc<Metrics>,
+    health: Arc<Health>,
+) -> Result<(), String> {
+    let server =
+        Server::http(address).map_err(|err| format!("Unable to listen on {}: {}", address, err))?;
+    info!("Serving the metrics and health on http://{}", address);
+    thread::Builder::new()
+        .name("http-server".to_string())
+        .spawn(move || {
+            for request in server.incoming_requests() {
+                let response = match request.url() {
+                    "/metrics" => match metrics.encode() {
+                        Ok((body, content_type)) => Response::from_data(body).with_header(
+                            Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes())
+                                .unwrap(),
+                        ),
+                        Err(err) => Response::from_string(err.to_string()).with_status_code(500),
+                    },
+                    "/healthz" => Response::from_string("ok"),
+                    "/readyz" if health.is_ready() => Response::from_string("ready"),
+                    "/readyz" => Response::from_string("not ready").with_status_code(503),
+                    _ => Response::from_string("Not found").with_status_code(404),
+                };
+                if let Err(err) = request.respond(response) {
+                    warn!("Unable to send the HTTP response: {}", err);
+                }
+            }
+        })
+        .map_err(|err| format!("Unable to start the HTTP server: {}", err))?;
+    Ok(())
+}
@@ -15,6 +15,7 @@
 #![feature(rustc_private)]
 #[macro_use]
 extern crate cfg_if;
+extern crate produce_consume_core;
 extern crate std;
 
 cfg_if! {
@@ -29,4 +30,4 @@ cfg_if! {
 }
 
 pub mod produce_consume;
-pub mod proto;
+pub use produce_consume_core::proto;
@@ -0,0 +1,113 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::LogConfig;
+use log::LogLevelFilter;
+use log4rs::append::console::ConsoleAppender;
+use log4rs::append::file::FileAppender;
+use log4rs::append::rolling_file::policy::compound::roll::fixed_window::FixedWindowRoller;
+use log4rs::append::rolling_file::policy::compound::trigger::size::SizeTrigger;
+use log4rs::append::rolling_file::policy::compound::CompoundPolicy;
+use log4rs::append::rolling_file::RollingFileAppender;
+use log4rs::append::Append;
+use log4rs::config::{Appender, Config, Root};
+use log4rs::encode::pattern::PatternEncoder;
+
+const CONSOLE_PATTERN: &str = "{h({l:5.5})} | {({M}:{L}):20.20} | {m}{n}";
+const FILE_PATTERN: &str = "{d} | {l:5.5} | {({M}:{L}):20.20} | {m}{n}";
+
+/// Initializes the logger. A log4rs YAML file, if configured, takes over entirely,
+/// including the levels per module. Otherwise the logs go to the console and to the
+/// optional log file, which is rotated once it reaches the maximum size.
+pub(crate) fn init(config: &LogConfig, level: LogLevelFilter) -> Result<(), String> {
+    if let Some(path) = &config.config {
+        return log4rs::init_file(path, Default::default())
+            .map_err(|err| format!("Unable to load the log config {}: {}", path, err));
+    }
+
+    let stdout = ConsoleAppender::builder()
+        .encoder(Box::new(PatternEncoder::new(CONSOLE_PATTERN)))
+        .build();
+    let mut builder =
+        Config::builder().appender(Appender::builder().build("stdout", Box::new(stdout)));
+    let mut root = Root::builder().appender("stdout");
+    if let Some(path) = &config.file {
+        builder = builder.appender(Appender::builder().build("file", file_appender(config, path)?));
+        root = root.appender("file");
+    }
+    let log_config = builder
+        .build(root.build(level))
+        .map_err(|err| format!("Invalid log config: {:?}", err))?;
+    log4rs::init_config(log_config)
+        .map(|_| ())
+        .map_err(|err| format!("Unable to initialize the logger: {}", err))
+}
+
+/// Rolling appender if a maximum size is set, the rotated files are named after the log
+/// file followed by .0 (the most recent) up to the number of files kept.
+fn file_appender(config: &LogConfig, path: &str) -> Result<Box<dyn Append>, String> {
+    let encoder = Box::new(PatternEncoder::new(FILE_PATTERN));
+    let max_size = match &config.max_size {
+        Some(max_size) => parse_size(max_size)?,
+        None => {
+            return FileAppender::builder()
+                .encoder(encoder)
+                .build(path)
+                .map(|appender| Box::new(appender) as Box<dyn Append>)
+                .map_err(|err| format!("Unable to open the log file {}: {}", path, err))
+        }
+    };
+    let roller = FixedWindowRoller::builder()
+        .build(&format!("{}.{{}}", path), config.keep)
+        .map_err(|err| format!("Invalid log rotation for {}: {}", path, err))?;
+    let policy = CompoundPolicy::new(Box::new(SizeTrigger::new(max_size)), Box::new(roller));
+    RollingFileAppender::builder()
+        .encoder(encoder)
+        .build(path, Box::new(policy))
+        .map(|appender| Box::new(appender) as Box<dyn Append>)
+        .map_err(|err| format!("Unable to open the log file {}: {}", path, err))
+}
+
+/// Size in bytes, with an optional KB, MB or GB suffix
+pub(crate) fn parse_size(size: &str) -> Result<u64, String> {
+    let size = size.trim().to_uppercase();
+    let (number, multiplier) = if size.ends_with("GB") {
+        (&size[..size.len() - 2], 1024 * 1024 * 1024)
+    } else if size.ends_with("MB") {
+        (&size[..size.len() - 2], 1024 * 1024)
+    } else if size.ends_with("KB") {
+        (&size[..size.len() - 2], 1024)
+    } else {
+        (&size[..], 1)
+    };
+    match number.trim().parse::<u64>() {
+        Ok(value) if value > 0 => Ok(value * multiplier),
+        _ => Err(format!("Invalid log file size {}", size)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_accept_a_unit_suffix() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("10kb"), Ok(10 * 1024));
+        assert_eq!(parse_size("10 MB"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_size("1GB"), Ok(1024 * 1024 * 1024));
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("ten MB").is_err());
+    }
+}
@@ -15,6 +15,7 @@
 #![feature(rustc_private)]
 #[macro_use]
 extern crate cfg_if;
+extern crate produce_consume_core;
 extern crate std;
 
 cfg_if! {
@@ -26,21 +27,36 @@ cfg_if! {
         #[macro_use]
         extern crate log;
         extern crate sawtooth_sdk;
+        extern crate serde;
+        extern crate toml;
+
+        mod config;
+        mod health;
+        mod http_server;
+        mod logging;
+        mod metrics;
+        mod shutdown;
+        mod supervisor;
+        mod zmq_context;
 
         use clap::App;
         use clap::Arg;
         use std::process;
+        use std::sync::Arc;
+        use std::time::Duration;
         use log::LogLevelFilter;
-        use log4rs::append::console::ConsoleAppender;
-        use log4rs::config::{Appender, Config, Root};
-        use log4rs::encode::pattern::PatternEncoder;
-        use sawtooth_sdk::processor::TransactionProcessor;
+        use sawtooth_sdk::processor::handler::TransactionHandler;
+        use crate::config::ProcessorConfig;
+        use crate::health::Health;
+        use crate::metrics::{MeteredHandler, Metrics};
         use crate::produce_consume::handler::ProduceConsumeHandler;
+        use produce_consume_core::addressing::{default_family_name, VERSION};
+        use crate::supervisor::{Backoff, Supervisor};
     }
 }
 
 pub mod produce_consume;
-pub mod proto;
+pub use produce_consume_core::proto;
 
 #[cfg(target_arch = "wasm32")]
 fn main() {
@@ -60,6 +76,60 @@ fn main() {
                 .help("Connect endpoint for the Validator")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .help("Config file, /etc/produce-consume/processor.toml if it exists")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("log-config")
+                .long("log-config")
+                .help("log4rs YAML file, replaces all the other log settings")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("log-file")
+                .long("log-file")
+                .help("File the logs are appended to, as well as the console")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("log-max-size")
+                .long("log-max-size")
+                .help("Rotates the log file at this size, for example 10MB")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("log-keep")
+                .long("log-keep")
+                .help("Number of rotated log files kept, 5 by default")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("family-name")
+                .long("family-name")
+                .help("Family name to register for, the namespace is derived from it")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("http-address")
+                .long("http-address")
+                .help("Address to serve the metrics and health on, for example 0.0.0.0:9100")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("check")
+                .long("check")
+                .help("Exits with 0 if the validator answers in time, 1 otherwise")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("check-timeout")
+                .long("check-timeout")
+                .help("Seconds to wait for the validator with --check, 5 by default")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("verbose")
                 .short("v")
@@ -69,42 +139,151 @@ fn main() {
         )
         .get_matches();
 
-    let endpoint = matches
-        .value_of("connect")
-        .unwrap_or("tcp://localhost:4004");
-
-    let console_log_level;
-    match matches.occurrences_of("verbose") {
-        0 => console_log_level = LogLevelFilter::Warn,
-        1 => console_log_level = LogLevelFilter::Info,
-        2 => console_log_level = LogLevelFilter::Debug,
-        3 | _ => console_log_level = LogLevelFilter::Trace,
+    // Defaults, then the config file, the environment and the command line
+    let mut processor_config = match ProcessorConfig::load(matches.value_of("config")) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    };
+    if let Err(err) = processor_config.apply_env() {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+    if let Some(endpoint) = matches.value_of("connect") {
+        processor_config.validator.endpoint = endpoint.to_string();
+    }
+    if let Some(family_name) = matches.value_of("family-name") {
+        processor_config.family.name = Some(family_name.to_string());
+    }
+    if let Some(address) = matches.value_of("http-address") {
+        processor_config.http.address = Some(address.to_string());
+    }
+    if let Some(log_config) = matches.value_of("log-config") {
+        processor_config.log.config = Some(log_config.to_string());
+    }
+    if let Some(log_file) = matches.value_of("log-file") {
+        processor_config.log.file = Some(log_file.to_string());
+    }
+    if let Some(max_size) = matches.value_of("log-max-size") {
+        processor_config.log.max_size = Some(max_size.to_string());
+    }
+    if let Some(keep) = matches.value_of("log-keep") {
+        processor_config.log.keep = match keep.parse() {
+            Ok(keep) => keep,
+            Err(_) => {
+                eprintln!("Invalid number of log files {}", keep);
+                process::exit(1);
+            }
+        };
     }
 
-    let stdout = ConsoleAppender::builder()
-        .encoder(Box::new(PatternEncoder::new(
-            "{h({l:5.5})} | {({M}:{L}):20.20} | {m}{n}",
-        )))
-        .build();
+    if matches.is_present("check") {
+        let timeout = match matches.value_of("check-timeout").unwrap_or("5").parse() {
+            Ok(seconds) => Duration::from_secs(seconds),
+            Err(_) => {
+                eprintln!("Invalid --check-timeout");
+                process::exit(1);
+            }
+        };
+        match health::check_validator(&processor_config.validator.endpoint, timeout) {
+            Ok(()) => {
+                println!(
+                    "Validator {} is reachable",
+                    processor_config.validator.endpoint
+                );
+                process::exit(0);
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                process::exit(1);
+            }
+        }
+    }
 
-    let config = match Config::builder()
-        .appender(Appender::builder().build("stdout", Box::new(stdout)))
-        .build(Root::builder().appender("stdout").build(console_log_level))
-    {
-        Ok(x) => x,
-        Err(_) => process::exit(1),
+    let console_log_level = match matches.occurrences_of("verbose") {
+        0 => match processor_config.log.level.parse::<LogLevelFilter>() {
+            Ok(level) => level,
+            Err(_) => {
+                eprintln!("Invalid log level {}", processor_config.log.level);
+                process::exit(1);
+            }
+        },
+        1 => LogLevelFilter::Info,
+        2 => LogLevelFilter::Debug,
+        3 | _ => LogLevelFilter::Trace,
     };
 
-    match log4rs::init_config(config) {
-        Ok(_) => (),
-        Err(_) => process::exit(1),
+    if let Err(err) = logging::init(&processor_config.log, console_log_level) {
+        eprintln!("{}", err);
+        process::exit(1);
     }
 
-    let handler = ProduceConsumeHandler::new();
-    let mut processor = TransactionProcessor::new(endpoint);
+    let handler = ProduceConsumeHandler::with_family(
+        processor_config
+            .family
+            .name
+            .as_ref()
+            .map(String::as_str)
+            .unwrap_or_else(default_family_name),
+        processor_config
+            .family
+            .version
+            .as_ref()
+            .map(String::as_str)
+            .unwrap_or(VERSION),
+    );
+    let metrics = match Metrics::new() {
+        Ok(metrics) => Arc::new(metrics),
+        Err(err) => {
+            error!("Unable to create the metrics: {}", err);
+            process::exit(1);
+        }
+    };
+    let backoff = match Backoff::new(
+        Duration::from_secs(processor_config.validator.reconnect_min_delay),
+        Duration::from_secs(processor_config.validator.reconnect_max_delay),
+    ) {
+        Ok(backoff) => backoff,
+        Err(err) => {
+            error!("{}", err);
+            process::exit(1);
+        }
+    };
+    let health = Arc::new(Health::default());
+    if let Some(address) = &processor_config.http.address {
+        if let Err(err) = http_server::start(address, metrics.clone(), health.clone()) {
+            error!("{}", err);
+            process::exit(1);
+        }
+    }
+    let handler = MeteredHandler::new(handler, metrics);
 
     info!("Console logging level: {}", console_log_level);
+    info!(
+        "Registering for the family {} with the namespace {}",
+        handler.family_name(),
+        handler.namespaces().join(", ")
+    );
+
+    let shutdown = match shutdown::install() {
+        Ok(shutdown) => shutdown,
+        Err(err) => {
+            error!("{}", err);
+            process::exit(1);
+        }
+    };
 
-    processor.add_handler(&handler);
-    processor.start();
+    Supervisor::new(
+        &processor_config.validator.endpoint,
+        &handler,
+        shutdown,
+        health,
+        backoff,
+    )
+    .run();
+    // run() returns after a SIGINT or SIGTERM, log4rs writes every record through so
+    // there is nothing left to flush
+    info!("Exiting");
 }
@@ -0,0 +1,235 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::proto::action::Action;
+use crate::proto::state::Item;
+use produce_consume_core::addressing::compute_address;
+use produce_consume_core::{codec, payload};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use sawtooth_sdk::messages::processor::TpProcessRequest;
+use sawtooth_sdk::processor::handler::{
+    ApplyError, ContextError, TransactionContext, TransactionHandler,
+};
+use std::cell::Cell;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Prometheus metrics of the transaction processor
+pub(crate) struct Metrics {
+    registry: Registry,
+    applied: IntCounterVec,
+    rejected: IntCounterVec,
+    state_latency: HistogramVec,
+    quantity: IntGaugeVec,
+}
+
+impl Metrics {
+    pub(crate) fn new() -> Result<Metrics, prometheus::Error> {
+        let registry = Registry::new();
+        let applied = IntCounterVec::new(
+            Opts::new("pc_actions_applied_total", "Actions applied, by command"),
+            &["command"],
+        )?;
+        let rejected = IntCounterVec::new(
+            Opts::new(
+                "pc_actions_rejected_total",
+                "Actions rejected, by command and reason",
+            ),
+            &["command", "reason"],
+        )?;
+        let state_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "pc_state_duration_seconds",
+                "Latency of the state reads and writes through the validator",
+            ),
+            &["operation"],
+        )?;
+        let quantity = IntGaugeVec::new(
+            Opts::new(
+                "pc_item_quantity",
+                "Last quantity written for the item by a PRODUCE or CONSUME",
+            ),
+            &["identifier"],
+        )?;
+        registry.register(Box::new(applied.clone()))?;
+        registry.register(Box::new(rejected.clone()))?;
+        registry.register(Box::new(state_latency.clone()))?;
+        registry.register(Box::new(quantity.clone()))?;
+        Ok(Metrics {
+            registry,
+            applied,
+            rejected,
+            state_latency,
+            quantity,
+        })
+    }
+
+    /// The metrics in the Prometheus text format, and its content type
+    pub(crate) fn encode(&self) -> Result<(Vec<u8>, String), prometheus::Error> {
+        let encoder = TextEncoder::new();
+        let mut buffer = Vec::new();
+        encoder.encode(&self.registry.gather(), &mut buffer)?;
+        Ok((buffer, encoder.format_type().to_string()))
+    }
+
+    fn observe_state(&self, operation: &str, started: Instant) {
+        self.state_latency
+            .with_label_values(&[operation])
+            .observe(started.elapsed().as_secs_f64());
+    }
+}
+
+/// Wraps a handler to count the applied and rejected actions, and the context given to
+/// it to time the state accesses.
+pub(crate) struct MeteredHandler<H: TransactionHandler> {
+    inner: H,
+    metrics: Arc<Metrics>,
+    /// Namespace prefix of the wrapped handler, to find the item entry among the writes
+    prefix: String,
+}
+
+impl<H: TransactionHandler> MeteredHandler<H> {
+    pub(crate) fn new(inner: H, metrics: Arc<Metrics>) -> MeteredHandler<H> {
+        let prefix = inner.namespaces().into_iter().next().unwrap_or_default();
+        MeteredHandler {
+            inner,
+            metrics,
+            prefix,
+        }
+    }
+}
+
+impl<H: TransactionHandler> TransactionHandler for MeteredHandler<H> {
+    fn family_name(&self) -> String {
+        self.inner.family_name()
+    }
+
+    fn family_versions(&self) -> Vec<String> {
+        self.inner.family_versions()
+    }
+
+    fn namespaces(&self) -> Vec<String> {
+        self.inner.namespaces()
+    }
+
+    fn apply(
+        &self,
+        request: &TpProcessRequest,
+        context: &mut dyn TransactionContext,
+    ) -> Result<(), ApplyError> {
+        let action: Option<Action> = payload::decode_action(request.get_payload()).ok();
+        let command = match &action {
+            Some(action) => format!("{:?}", action.get_command()),
+            None => "UNKNOWN".to_string(),
+        };
+        let identifier = action
+            .as_ref()
+            .map(|action| action.get_identifier().to_string())
+            .unwrap_or_default();
+
+        let mut metered = MeteredContext {
+            inner: context,
+            metrics: &self.metrics,
+            item_address: if identifier.is_empty() {
+                None
+            } else {
+                Some(compute_address(&self.prefix, &identifier))
+            },
+            item_quantity: Cell::new(None),
+        };
+        let result = self.inner.apply(request, &mut metered);
+        match &result {
+            Ok(()) => {
+                self.metrics.applied.with_label_values(&[&command]).inc();
+                if let Some(quantity) = metered.item_quantity.get() {
+                    self.metrics
+                        .quantity
+                        .with_label_values(&[&identifier])
+                        .set(i64::from(quantity));
+                }
+            }
+            Err(err) => {
+                let reason = match err {
+                    _ if action.is_none() => "malformed_payload",
+                    ApplyError::InvalidTransaction(_) => "invalid_transaction",
+                    ApplyError::InternalError(_) => "internal_error",
+                };
+                self.metrics
+                    .rejected
+                    .with_label_values(&[&command, reason])
+                    .inc();
+            }
+        }
+        result
+    }
+}
+
+/// Times the reads and writes, and keeps the last quantity written for the item of the
+/// action.
+struct MeteredContext<'a> {
+    inner: &'a mut dyn TransactionContext,
+    metrics: &'a Metrics,
+    item_address: Option<String>,
+    item_quantity: Cell<Option<i32>>,
+}
+
+impl<'a> TransactionContext for MeteredContext<'a> {
+    fn get_state_entries(
+        &self,
+        addresses: &[String],
+    ) -> Result<Vec<(String, Vec<u8>)>, ContextError> {
+        let started = Instant::now();
+        let result = self.inner.get_state_entries(addresses);
+        self.metrics.observe_state("read", started);
+        result
+    }
+
+    fn set_state_entries(&self, entries: Vec<(String, Vec<u8>)>) -> Result<(), ContextError> {
+        if let Some(item_address) = &self.item_address {
+            for (address, value) in &entries {
+                if address == item_address {
+                    if let Ok(item) = codec::decode::<Item>(value) {
+                        self.item_quantity.set(Some(item.get_quantity()));
+                    }
+                }
+            }
+        }
+        let started = Instant::now();
+        let result = self.inner.set_state_entries(entries);
+        self.metrics.observe_state("write", started);
+        result
+    }
+
+    fn delete_state_entries(&self, addresses: &[String]) -> Result<Vec<String>, ContextError> {
+        let started = Instant::now();
+        let result = self.inner.delete_state_entries(addresses);
+        self.metrics.observe_state("delete", started);
+        result
+    }
+
+    fn add_receipt_data(&self, data: &[u8]) -> Result<(), ContextError> {
+        self.inner.add_receipt_data(data)
+    }
+
+    fn add_event(
+        &self,
+        event_type: String,
+        attributes: Vec<(String, String)>,
+        data: &[u8],
+    ) -> Result<(), ContextError> {
+        self.inner.add_event(event_type, attributes, data)
+    }
+}
@@ -0,0 +1,82 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::proto::block_info::BlockInfo;
+use super::super::proto::block_info::BlockInfoConfig;
+use produce_consume_core::addressing::{block_info_address, block_info_config_address};
+use produce_consume_core::codec;
+
+cfg_if! {
+    if #[cfg(target_arch = "wasm32")] {
+        use sabre_sdk::ApplyError;
+        use sabre_sdk::TransactionContext;
+    } else {
+        use sawtooth_sdk::processor::handler::ApplyError;
+        use sawtooth_sdk::processor::handler::TransactionContext;
+    }
+}
+
+/// Reads the timestamp of the latest block recorded by the BlockInfo transaction family.
+///
+/// Fails with ```InvalidTransaction``` when the BlockInfo entries are not present in
+/// the state, this happens if the block info injector is not enabled on the network.
+pub(crate) fn get_current_block_timestamp(
+    context: &mut dyn TransactionContext,
+) -> Result<u64, ApplyError> {
+    let latest_block = get_current_block_num(context)?;
+    let block_info: BlockInfo = match read_entry(context, &block_info_address(latest_block))? {
+        Some(block_info) => block_info,
+        None => {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "BlockInfo for the block {} is not available",
+                latest_block
+            )))
+        }
+    };
+    Ok(block_info.get_timestamp())
+}
+
+/// Reads the number of the latest block recorded by the BlockInfo transaction family.
+pub(crate) fn get_current_block_num(
+    context: &mut dyn TransactionContext,
+) -> Result<u64, ApplyError> {
+    let config: BlockInfoConfig = match read_entry(context, &block_info_config_address())? {
+        Some(config) => config,
+        None => {
+            return Err(ApplyError::InvalidTransaction(
+                "BlockInfo config is not available, cannot read the current block".to_string(),
+            ))
+        }
+    };
+    Ok(config.get_latest_block())
+}
+
+fn read_entry<T>(
+    context: &mut dyn TransactionContext,
+    address: &str,
+) -> Result<Option<T>, ApplyError>
+where
+    T: protobuf::Message,
+{
+    let raw_value = match context.get_state_entry(address) {
+        Ok(present) => present,
+        Err(err) => return Err(ApplyError::InternalError(err.to_string())),
+    };
+    match raw_value {
+        Some(present) => codec::decode(&present)
+            .map(Some)
+            .map_err(|err| ApplyError::InternalError(format!("Invalid BlockInfo: {}", err))),
+        None => Ok(None),
+    }
+}
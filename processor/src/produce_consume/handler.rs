@@ -13,9 +13,20 @@
 // limitations under the License.
 
 use super::super::proto::action::Action_Command;
+use super::super::proto::receipt::ConsumeReceipt;
+use super::super::proto::state::CategoryTotals;
+use super::super::proto::state::Escrow;
+use super::super::proto::state::Escrow_Status;
+use super::super::proto::state::Proposal;
+use super::super::proto::state::Proposal_Status;
+use super::block_info;
+use super::identity::SignerIdentity;
 use super::payload::ProduceConsumePayload;
-use crypto::digest::Digest;
-use crypto::sha2::Sha512;
+use super::state::ProduceConsumeState;
+use super::valuation;
+use produce_consume_core::addressing::{
+    compute_namespace_prefix, compute_records_prefix, default_family_name, VERSION,
+};
 
 cfg_if! {
     if #[cfg(target_arch = "wasm32")] {
@@ -31,9 +42,6 @@ cfg_if! {
     }
 }
 
-const PRODUCE_CONSUME: &str = "produce-consume";
-const VERSION: &str = "1.0";
-
 pub struct ProduceConsumeHandler {
     family_name: String,
     family_versions: Vec<String>,
@@ -42,10 +50,19 @@ pub struct ProduceConsumeHandler {
 
 impl ProduceConsumeHandler {
     pub fn new() -> ProduceConsumeHandler {
+        ProduceConsumeHandler::with_family(default_family_name(), VERSION)
+    }
+
+    /// Registers the handler for the given family name and version. The namespaces, and
+    /// so every address, are derived from the family name, deployments under different
+    /// names can share a network without seeing each other's state. The items are in the
+    /// first namespace, the other records in the second one.
+    pub fn with_family(family_name: &str, version: &str) -> ProduceConsumeHandler {
+        let prefix = compute_namespace_prefix(family_name);
         ProduceConsumeHandler {
-            family_name: PRODUCE_CONSUME.to_string(),
-            family_versions: vec![VERSION.to_string()],
-            namespaces: vec![get_produce_consume_prefix().to_string()],
+            family_name: family_name.to_string(),
+            family_versions: vec![version.to_string()],
+            namespaces: vec![prefix.clone(), compute_records_prefix(&prefix)],
         }
     }
 }
@@ -74,61 +91,2035 @@ impl TransactionHandler for ProduceConsumeHandler {
             Err(err) => return Err(err),
         };
 
-        // Compute address for the item
-        let address = compute_address(&payload.get_identifier());
+        // Reject the action if it is outside of its validity window
+        if payload.is_time_bound() {
+            check_validity_window(&payload, context)?;
+        }
 
-        // Get the quantity in the store
-        let raw_value: Option<Vec<u8>> = match context.get_state_entry(&address) {
-            Ok(present) => present,
-            Err(err) => return Err(ApplyError::InternalError(err.to_string())),
-        };
-        // Deserialize the value
-        let value = match raw_value {
-            Some(present) => {
-                let mut array: [u8; 4] = [0; 4];
-                array.copy_from_slice(&present[..4]);
-                i32::from_ne_bytes(array)
+        let signer = SignerIdentity::from_request(request)?;
+        let mut state = ProduceConsumeState::new(context, &self.namespaces[0]);
+        // The administrators are not limited, they must be able to lift the limits
+        if !payload.is_admin_command() {
+            check_rate_limit(&payload, &signer, &mut state)?;
+        }
+        match payload.get_command() {
+            Action_Command::PRODUCE => produce(&payload, &signer, &mut state),
+            Action_Command::CONSUME => consume(&payload, &signer, &mut state),
+            Action_Command::APPROVE => approve(&payload, &signer, &mut state),
+            Action_Command::REJECT => reject(&payload, &signer, &mut state),
+            Action_Command::SET_APPROVAL_POLICY => {
+                set_approval_policy(&payload, &signer, &mut state)
             }
-            None => 0,
-        };
-        info!("Read the value {}: {}", &payload.get_identifier(), value);
-
-        // Check for overflow scenarios
-        let new_value = match payload.get_command() {
-            Action_Command::PRODUCE => value.checked_add(payload.get_quantity()),
-            Action_Command::CONSUME => value.checked_sub(payload.get_quantity()),
-        };
-        // unwrapping is safe after none condition check
-        if new_value.is_none() || new_value.unwrap() < 0 {
-            return Err(ApplyError::InvalidTransaction(
-                "Invalid resultant quantity".to_string(),
-            ));
+            Action_Command::SET_VALUATION_POLICY => {
+                set_valuation_policy(&payload, &signer, &mut state)
+            }
+            Action_Command::OFFER_TRANSFER => offer_transfer(&payload, &signer, &mut state),
+            Action_Command::ACCEPT_TRANSFER => accept_transfer(&payload, &signer, &mut state),
+            Action_Command::DECLINE_TRANSFER => decline_transfer(&payload, &signer, &mut state),
+            Action_Command::WITHDRAW_TRANSFER => withdraw_transfer(&payload, &signer, &mut state),
+            Action_Command::SET_RATE_LIMIT => set_rate_limit(&payload, &signer, &mut state),
         }
-        info!(
-            "Computed new value {}: {}",
+    }
+}
+
+/// Adds the quantity to the item and to the holding of the signer, a new lot at the unit
+/// cost is added to the cost layers of the item.
+fn produce(
+    payload: &ProduceConsumePayload,
+    signer: &SignerIdentity,
+    state: &mut ProduceConsumeState,
+) -> Result<(), ApplyError> {
+    let identifier = payload.get_identifier();
+    update_quantity(
+        state,
+        &identifier,
+        Action_Command::PRODUCE,
+        payload.get_quantity(),
+    )?;
+    update_holding(
+        state,
+        signer.public_key(),
+        &identifier,
+        payload.get_quantity(),
+    )?;
+    update_totals(
+        state,
+        &identifier,
+        &payload.get_category(),
+        Action_Command::PRODUCE,
+        payload.get_quantity(),
+    )?;
+    let mut layers = state.get_cost_layers(&identifier)?;
+    valuation::add_lot(&mut layers, payload.get_quantity(), payload.get_unit_cost())?;
+    state.set_cost_layers(&layers)
+}
+
+/// Consumes the item right away, unless the quantity is above the threshold of the
+/// approval policy. In that case a pending proposal is stored instead.
+fn consume(
+    payload: &ProduceConsumePayload,
+    signer: &SignerIdentity,
+    state: &mut ProduceConsumeState,
+) -> Result<(), ApplyError> {
+    let policy = state.get_config()?.get_approval_policy().clone();
+    if policy.get_threshold() == 0 || payload.get_quantity() <= policy.get_threshold() {
+        return consume_item(
+            state,
+            signer.public_key(),
             &payload.get_identifier(),
-            new_value.unwrap()
+            payload.get_quantity(),
         );
+    }
+
+    let proposal_id = payload.get_proposal_id();
+    if proposal_id.is_empty() {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Consuming more than {} requires approvals, a proposal id is required",
+            policy.get_threshold()
+        )));
+    }
+    if state.get_proposal(&proposal_id)?.is_some() {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Proposal {} already exists",
+            proposal_id
+        )));
+    }
+    // Fail early, the quantity is checked again when the proposal is executed
+    if get_available(state, signer.public_key(), &payload.get_identifier())?
+        < payload.get_quantity()
+    {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Insufficient quantity of {} held by {}",
+            &payload.get_identifier(),
+            signer.public_key()
+        )));
+    }
+
+    let mut proposal = Proposal::new();
+    proposal.set_proposal_id(proposal_id.clone());
+    proposal.set_identifier(payload.get_identifier());
+    proposal.set_quantity(payload.get_quantity());
+    proposal.set_proposer(signer.public_key().to_string());
+    proposal.set_status(Proposal_Status::PENDING);
+    info!(
+        "Created the proposal {} to consume {}: {}",
+        proposal_id,
+        &payload.get_identifier(),
+        payload.get_quantity()
+    );
+    state.set_proposal(&proposal)
+}
+
+/// Records the approval of the signer, the consumption is executed once the proposal has
+/// the required number of distinct approvals.
+fn approve(
+    payload: &ProduceConsumePayload,
+    signer: &SignerIdentity,
+    state: &mut ProduceConsumeState,
+) -> Result<(), ApplyError> {
+    let policy = state.get_config()?.get_approval_policy().clone();
+    signer.authorize(policy.get_approvers(), "approver")?;
+    let mut proposal = get_pending_proposal(state, &payload.get_proposal_id())?;
+    if proposal
+        .get_approvals()
+        .iter()
+        .any(|approval| approval == signer.public_key())
+    {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Proposal {} is already approved by {}",
+            proposal.get_proposal_id(),
+            signer.public_key()
+        )));
+    }
+    proposal
+        .mut_approvals()
+        .push(signer.public_key().to_string());
+    info!(
+        "Proposal {} has {} of {} approvals",
+        proposal.get_proposal_id(),
+        proposal.get_approvals().len(),
+        policy.get_required_approvals()
+    );
+
+    if proposal.get_approvals().len() >= policy.get_required_approvals() as usize {
+        consume_item(
+            state,
+            proposal.get_proposer(),
+            proposal.get_identifier(),
+            proposal.get_quantity(),
+        )?;
+        proposal.set_status(Proposal_Status::EXECUTED);
+    }
+    state.set_proposal(&proposal)
+}
+
+fn reject(
+    payload: &ProduceConsumePayload,
+    signer: &SignerIdentity,
+    state: &mut ProduceConsumeState,
+) -> Result<(), ApplyError> {
+    let policy = state.get_config()?.get_approval_policy().clone();
+    signer.authorize(policy.get_approvers(), "approver")?;
+    let mut proposal = get_pending_proposal(state, &payload.get_proposal_id())?;
+    proposal.set_status(Proposal_Status::REJECTED);
+    info!(
+        "Proposal {} is rejected by {}",
+        proposal.get_proposal_id(),
+        signer.public_key()
+    );
+    state.set_proposal(&proposal)
+}
+
+/// Replaces the approval policy, only the administrators listed in the
+/// ```produce_consume.admins``` setting are allowed to do so.
+fn set_approval_policy(
+    payload: &ProduceConsumePayload,
+    signer: &SignerIdentity,
+    state: &mut ProduceConsumeState,
+) -> Result<(), ApplyError> {
+    check_admin(state, signer)?;
+    let policy = payload.get_approval_policy();
+    if policy.get_threshold() < 0 {
+        return Err(ApplyError::InvalidTransaction(
+            "Threshold cannot be negative".to_string(),
+        ));
+    }
+    let mut approvers = policy.get_approvers().to_vec();
+    approvers.sort();
+    approvers.dedup();
+    if approvers.len() != policy.get_approvers().len() {
+        return Err(ApplyError::InvalidTransaction(
+            "Approvers must be distinct".to_string(),
+        ));
+    }
+    if policy.get_threshold() > 0
+        && (policy.get_required_approvals() == 0
+            || policy.get_required_approvals() as usize > approvers.len())
+    {
+        return Err(ApplyError::InvalidTransaction(
+            "Required approvals must be between 1 and the number of approvers".to_string(),
+        ));
+    }
+
+    let mut config = state.get_config()?;
+    config.set_approval_policy(policy);
+    state.set_config(&config)
+}
+
+/// Replaces the valuation policy used for the consumptions that follow, the cost layers
+/// are kept as they are.
+fn set_valuation_policy(
+    payload: &ProduceConsumePayload,
+    signer: &SignerIdentity,
+    state: &mut ProduceConsumeState,
+) -> Result<(), ApplyError> {
+    check_admin(state, signer)?;
+    let mut config = state.get_config()?;
+    config.set_valuation_policy(payload.get_valuation_policy());
+    info!(
+        "Valuation policy is set to {:?}",
+        payload.get_valuation_policy()
+    );
+    state.set_config(&config)
+}
+
+/// Moves the quantity from the holding of the signer into escrow, until the recipient
+/// accepts or declines it, the signer withdraws it or it expires.
+fn offer_transfer(
+    payload: &ProduceConsumePayload,
+    signer: &SignerIdentity,
+    state: &mut ProduceConsumeState,
+) -> Result<(), ApplyError> {
+    let transfer_id = payload.get_transfer_id();
+    if payload.get_recipient() == signer.public_key() {
+        return Err(ApplyError::InvalidTransaction(
+            "Cannot transfer to the sender".to_string(),
+        ));
+    }
+    if state.get_escrow(&transfer_id)?.is_some() {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Transfer {} already exists",
+            transfer_id
+        )));
+    }
+    if payload.get_expires_at_block() != 0
+        && state.get_current_block_num()? >= payload.get_expires_at_block()
+    {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Transfer {} would be expired at block {}",
+            transfer_id,
+            payload.get_expires_at_block()
+        )));
+    }
+    take_from_holding(
+        state,
+        signer.public_key(),
+        &payload.get_identifier(),
+        payload.get_quantity(),
+    )?;
+
+    let mut escrow = Escrow::new();
+    escrow.set_transfer_id(transfer_id.clone());
+    escrow.set_identifier(payload.get_identifier());
+    escrow.set_quantity(payload.get_quantity());
+    escrow.set_sender(signer.public_key().to_string());
+    escrow.set_recipient(payload.get_recipient());
+    escrow.set_expires_at_block(payload.get_expires_at_block());
+    escrow.set_status(Escrow_Status::OFFERED);
+    info!(
+        "Offered the transfer {} of {}: {} to {}",
+        transfer_id,
+        &payload.get_identifier(),
+        payload.get_quantity(),
+        &payload.get_recipient()
+    );
+    state.set_escrow(&escrow)
+}
+
+/// Releases the escrow to the recipient, only the recipient can accept. An offer that
+/// expired is returned to the sender instead, the transaction stays valid so that the
+/// release is kept.
+fn accept_transfer(
+    payload: &ProduceConsumePayload,
+    signer: &SignerIdentity,
+    state: &mut ProduceConsumeState,
+) -> Result<(), ApplyError> {
+    let mut escrow = get_offered_escrow(state, &payload.get_transfer_id())?;
+    signer.authorize_party(
+        escrow.get_recipient(),
+        "recipient",
+        &format!("accept the transfer {}", escrow.get_transfer_id()),
+    )?;
+    if is_expired(state, &escrow)? {
+        return return_escrow(state, escrow, Escrow_Status::EXPIRED);
+    }
+    update_holding(
+        state,
+        escrow.get_recipient(),
+        escrow.get_identifier(),
+        escrow.get_quantity(),
+    )?;
+    escrow.set_status(Escrow_Status::ACCEPTED);
+    state.set_escrow(&escrow)
+}
 
-        // Either produce or consume successful, store the new state back, serialize the value
-        let new_value_bytes = new_value.unwrap().to_ne_bytes();
+/// Returns the escrow to the sender, only the recipient can decline. An offer that
+/// expired is recorded as such.
+fn decline_transfer(
+    payload: &ProduceConsumePayload,
+    signer: &SignerIdentity,
+    state: &mut ProduceConsumeState,
+) -> Result<(), ApplyError> {
+    let escrow = get_offered_escrow(state, &payload.get_transfer_id())?;
+    signer.authorize_party(
+        escrow.get_recipient(),
+        "recipient",
+        &format!("decline the transfer {}", escrow.get_transfer_id()),
+    )?;
+    let status = if is_expired(state, &escrow)? {
+        Escrow_Status::EXPIRED
+    } else {
+        Escrow_Status::DECLINED
+    };
+    return_escrow(state, escrow, status)
+}
+
+/// Returns the escrow to the sender, only the sender can withdraw. An offer that expired
+/// is recorded as such.
+fn withdraw_transfer(
+    payload: &ProduceConsumePayload,
+    signer: &SignerIdentity,
+    state: &mut ProduceConsumeState,
+) -> Result<(), ApplyError> {
+    let escrow = get_offered_escrow(state, &payload.get_transfer_id())?;
+    signer.authorize_party(
+        escrow.get_sender(),
+        "sender",
+        &format!("withdraw the transfer {}", escrow.get_transfer_id()),
+    )?;
+    let status = if is_expired(state, &escrow)? {
+        Escrow_Status::EXPIRED
+    } else {
+        Escrow_Status::WITHDRAWN
+    };
+    return_escrow(state, escrow, status)
+}
+
+fn return_escrow(
+    state: &mut ProduceConsumeState,
+    mut escrow: Escrow,
+    status: Escrow_Status,
+) -> Result<(), ApplyError> {
+    update_holding(
+        state,
+        escrow.get_sender(),
+        escrow.get_identifier(),
+        escrow.get_quantity(),
+    )?;
+    escrow.set_status(status);
+    info!(
+        "Transfer {} is returned to the sender, status is {:?}",
+        escrow.get_transfer_id(),
+        status
+    );
+    state.set_escrow(&escrow)
+}
 
-        context.set_state_entries(vec![(address, new_value_bytes.to_vec())])?;
+/// Whether the offer reached its expiry block, an offer without one never expires
+fn is_expired(state: &mut ProduceConsumeState, escrow: &Escrow) -> Result<bool, ApplyError> {
+    Ok(escrow.get_expires_at_block() != 0
+        && state.get_current_block_num()? >= escrow.get_expires_at_block())
+}
 
-        Ok(())
+fn get_offered_escrow(
+    state: &mut ProduceConsumeState,
+    transfer_id: &str,
+) -> Result<Escrow, ApplyError> {
+    let escrow = match state.get_escrow(transfer_id)? {
+        Some(escrow) => escrow,
+        None => {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "Transfer {} does not exist",
+                transfer_id
+            )))
+        }
+    };
+    if escrow.get_status() != Escrow_Status::OFFERED {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Transfer {} is not offered, status is {:?}",
+            transfer_id,
+            escrow.get_status()
+        )));
     }
+    Ok(escrow)
 }
 
-fn compute_address(identifier: &str) -> String {
-    let prefix = get_produce_consume_prefix();
-    let mut sha = Sha512::new();
-    sha.input_str(identifier);
-    let remaining = sha.result_str()[..64].to_string();
-    prefix + &remaining
+/// Replaces the rate limits, the usage already recorded in the current windows is kept
+fn set_rate_limit(
+    payload: &ProduceConsumePayload,
+    signer: &SignerIdentity,
+    state: &mut ProduceConsumeState,
+) -> Result<(), ApplyError> {
+    check_admin(state, signer)?;
+    let mut config = state.get_config()?;
+    config.set_rate_limit(payload.get_rate_limit());
+    info!("Rate limit is set to {:?}", payload.get_rate_limit());
+    state.set_config(&config)
 }
 
-fn get_produce_consume_prefix() -> String {
-    let mut sha = Sha512::new();
-    sha.input_str(PRODUCE_CONSUME);
-    sha.result_str()[..6].to_string()
+/// Records the transaction and its quantity in the usage of the signer for the current
+/// window of blocks, rejecting it if either limit is exceeded.
+fn check_rate_limit(
+    payload: &ProduceConsumePayload,
+    signer: &SignerIdentity,
+    state: &mut ProduceConsumeState,
+) -> Result<(), ApplyError> {
+    let limit = state.get_config()?.get_rate_limit().clone();
+    if limit.get_window_blocks() == 0 {
+        return Ok(());
+    }
+    let block_num = state.get_current_block_num()?;
+    let window_start = block_num - block_num % limit.get_window_blocks();
+
+    let mut usage = state.get_signer_usage(signer.public_key())?;
+    if usage.get_window_start() != window_start {
+        usage.set_window_start(window_start);
+        usage.set_transactions(0);
+        usage.set_quantity(0);
+    }
+    // Any signer can grow its usage, a count past the range is rejected rather than wrapped
+    let transactions = usage.get_transactions().checked_add(1);
+    let quantity = usage
+        .get_quantity()
+        .checked_add(payload.get_quantity() as i64);
+    match (transactions, quantity) {
+        (Some(transactions), Some(quantity)) => {
+            usage.set_transactions(transactions);
+            usage.set_quantity(quantity);
+        }
+        _ => {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "Usage of signer {} overflows in the window starting at block {}",
+                signer.public_key(),
+                window_start
+            )))
+        }
+    }
+
+    if limit.get_max_transactions() != 0 && usage.get_transactions() > limit.get_max_transactions()
+    {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Signer {} exceeded the limit of {} transactions per {} blocks, window starts at block {}",
+            signer.public_key(),
+            limit.get_max_transactions(),
+            limit.get_window_blocks(),
+            window_start
+        )));
+    }
+    if limit.get_max_quantity() != 0 && usage.get_quantity() > limit.get_max_quantity() {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Signer {} exceeded the limit of {} in quantity per {} blocks, window starts at block {}",
+            signer.public_key(),
+            limit.get_max_quantity(),
+            limit.get_window_blocks(),
+            window_start
+        )));
+    }
+    state.set_signer_usage(&usage)
+}
+
+fn check_admin(state: &mut ProduceConsumeState, signer: &SignerIdentity) -> Result<(), ApplyError> {
+    signer.authorize(&state.get_admins()?, "administrator")
+}
+
+fn get_pending_proposal(
+    state: &mut ProduceConsumeState,
+    proposal_id: &str,
+) -> Result<Proposal, ApplyError> {
+    let proposal = match state.get_proposal(proposal_id)? {
+        Some(proposal) => proposal,
+        None => {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "Proposal {} does not exist",
+                proposal_id
+            )))
+        }
+    };
+    if proposal.get_status() != Proposal_Status::PENDING {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Proposal {} is not pending, status is {:?}",
+            proposal_id,
+            proposal.get_status()
+        )));
+    }
+    Ok(proposal)
+}
+
+/// Subtracts the quantity of the item, the holding of the owner and the cost layers of
+/// the item, the cost of goods consumed is added to the transaction receipt.
+fn consume_item(
+    state: &mut ProduceConsumeState,
+    owner: &str,
+    identifier: &str,
+    quantity: i32,
+) -> Result<(), ApplyError> {
+    take_from_holding(state, owner, identifier, quantity)?;
+    update_totals(state, identifier, "", Action_Command::CONSUME, quantity)?;
+    let on_hand = update_quantity(state, identifier, Action_Command::CONSUME, quantity)?;
+    let policy = state.get_config()?.get_valuation_policy();
+    let mut layers = state.get_cost_layers(identifier)?;
+    let cost = valuation::consume(&mut layers, on_hand, quantity, policy)?;
+    state.set_cost_layers(&layers)?;
+    info!("Cost of goods consumed {}: {}", identifier, cost);
+
+    let mut receipt = ConsumeReceipt::new();
+    receipt.set_identifier(identifier.to_string());
+    receipt.set_quantity(quantity);
+    receipt.set_cost_of_goods(cost);
+    receipt.set_valuation_policy(policy);
+    state.add_receipt_data(&receipt)
+}
+
+/// Updates the running totals of the item, of its category and the global totals. The
+/// category of an item is set by its first PRODUCE and cannot be changed later on.
+fn update_totals(
+    state: &mut ProduceConsumeState,
+    identifier: &str,
+    category: &str,
+    command: Action_Command,
+    quantity: i32,
+) -> Result<(), ApplyError> {
+    let mut item = state.get_item_totals(identifier)?;
+    if !category.is_empty() && item.get_category() != category {
+        if item.get_produced() != 0 || item.get_consumed() != 0 {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "{} is already in the category {:?}",
+                identifier,
+                item.get_category()
+            )));
+        }
+        item.set_category(category.to_string());
+    }
+
+    let quantity = quantity as i64;
+    let (produced, consumed) = match command {
+        Action_Command::PRODUCE => (quantity, 0),
+        _ => (0, quantity),
+    };
+    item.set_produced(item.get_produced() + produced);
+    item.set_consumed(item.get_consumed() + consumed);
+
+    let mut category_totals = state.get_category_totals(item.get_category())?;
+    add_to_totals(&mut category_totals, produced, consumed);
+    let mut global_totals = state.get_global_totals()?;
+    add_to_totals(&mut global_totals, produced, consumed);
+
+    state.set_item_totals(&item)?;
+    state.set_category_totals(&category_totals)?;
+    state.set_global_totals(&global_totals)
+}
+
+fn add_to_totals(totals: &mut CategoryTotals, produced: i64, consumed: i64) {
+    totals.set_produced(totals.get_produced() + produced);
+    totals.set_consumed(totals.get_consumed() + consumed);
+    totals.set_on_hand(totals.get_on_hand() + produced - consumed);
+}
+
+/// Adds the change to the holding of the owner, rejecting overflows and negative results
+fn update_holding(
+    state: &mut ProduceConsumeState,
+    owner: &str,
+    identifier: &str,
+    change: i32,
+) -> Result<(), ApplyError> {
+    let held = state.get_holding(owner, identifier)?;
+    match held.checked_add(change) {
+        Some(new_held) if new_held >= 0 => state.set_holding(owner, identifier, new_held),
+        _ => Err(ApplyError::InvalidTransaction(format!(
+            "Insufficient quantity of {} held by {}",
+            identifier, owner
+        ))),
+    }
+}
+
+/// Subtracts the quantity from the holding of the owner. The quantity produced before
+/// the holdings were tracked is held by no one and any signer can take it, as before,
+/// what the holding lacks is claimed from it.
+fn take_from_holding(
+    state: &mut ProduceConsumeState,
+    owner: &str,
+    identifier: &str,
+    quantity: i32,
+) -> Result<(), ApplyError> {
+    let held = state.get_holding(owner, identifier)?;
+    if held >= quantity {
+        return state.set_holding(owner, identifier, held - quantity);
+    }
+    let mut item = state.get_item(identifier)?;
+    let claimed = quantity - held;
+    if item.get_quantity() - item.get_held() < claimed {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Insufficient quantity of {} held by {}",
+            identifier, owner
+        )));
+    }
+    info!(
+        "{} claims {} of {} held by no one",
+        owner, claimed, identifier
+    );
+    item.set_held(item.get_held() + claimed);
+    state.set_item(&item)?;
+    state.set_holding(owner, identifier, 0)
+}
+
+/// Quantity the owner can take, its holding and the quantity held by no one
+fn get_available(
+    state: &mut ProduceConsumeState,
+    owner: &str,
+    identifier: &str,
+) -> Result<i32, ApplyError> {
+    let item = state.get_item(identifier)?;
+    let held = state.get_holding(owner, identifier)?;
+    Ok(held.saturating_add(item.get_quantity() - item.get_held()))
+}
+
+/// Adds or subtracts the quantity of the item, rejecting overflows and negative results.
+/// A produced quantity is held by its producer, a consumed one was taken from a holding
+/// first. Returns the quantity before the update.
+fn update_quantity(
+    state: &mut ProduceConsumeState,
+    identifier: &str,
+    command: Action_Command,
+    quantity: i32,
+) -> Result<i32, ApplyError> {
+    // Get the quantity in the store
+    let mut item = state.get_item(identifier)?;
+    let value = item.get_quantity();
+    info!("Read the value {}: {}", identifier, value);
+
+    // Check for overflow scenarios
+    let new_value = match command {
+        Action_Command::PRODUCE => value.checked_add(quantity),
+        _ => value.checked_sub(quantity),
+    };
+    // unwrapping is safe after none condition check
+    if new_value.is_none() || new_value.unwrap() < 0 {
+        return Err(ApplyError::InvalidTransaction(
+            "Invalid resultant quantity".to_string(),
+        ));
+    }
+    info!("Computed new value {}: {}", identifier, new_value.unwrap());
+
+    // Either produce or consume successful, store the new state back
+    let held = match command {
+        Action_Command::PRODUCE => item.get_held() + quantity,
+        _ => (item.get_held() - quantity).max(0),
+    };
+    item.set_quantity(new_value.unwrap());
+    item.set_held(held);
+    state.set_item(&item)?;
+    Ok(value)
+}
+
+/// Compares the optional validity window of the action against the timestamp of the
+/// latest block known to the BlockInfo transaction family.
+fn check_validity_window(
+    payload: &ProduceConsumePayload,
+    context: &mut dyn TransactionContext,
+) -> Result<(), ApplyError> {
+    let now = block_info::get_current_block_timestamp(context)?;
+    info!("Current block timestamp is {}", now);
+    if payload.get_valid_after() != 0 && now < payload.get_valid_after() {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Action is not valid before {}, current block timestamp is {}",
+            payload.get_valid_after(),
+            now
+        )));
+    }
+    if payload.get_valid_before() != 0 && now >= payload.get_valid_before() {
+        return Err(ApplyError::InvalidTransaction(format!(
+            "Action expired at {}, current block timestamp is {}",
+            payload.get_valid_before(),
+            now
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::super::super::proto::action::Action;
+    use super::super::super::proto::block_info::BlockInfo;
+    use super::super::super::proto::block_info::BlockInfoConfig;
+    use super::super::super::proto::setting::Setting;
+    use super::super::super::proto::setting::Setting_Entry;
+    use super::super::super::proto::state::ApprovalPolicy;
+    use super::super::super::proto::state::CostLayers;
+    use super::super::super::proto::state::Escrow;
+    use super::super::super::proto::state::Holding;
+    use super::super::super::proto::state::Item;
+    use super::super::super::proto::state::ItemTotals;
+    use super::super::super::proto::state::RateLimit;
+    use super::super::super::proto::state::SignerUsage;
+    use super::super::super::proto::state::ValuationPolicy;
+    use super::super::memory_context::InMemoryTransactionContext;
+    use super::*;
+    use produce_consume_core::addressing::{
+        block_info_address, block_info_config_address, compute_address,
+        compute_category_totals_address, compute_cost_layers_address, compute_escrow_address,
+        compute_global_totals_address, compute_holding_address, compute_item_totals_address,
+        compute_proposal_address, compute_setting_address, compute_signer_usage_address,
+        ADMINS_SETTING,
+    };
+    use produce_consume_core::codec;
+    use produce_consume_core::payload;
+    use protobuf::Message;
+    use sawtooth_sdk::messages::transaction::TransactionHeader;
+
+    const SIGNER: &str = "02b4d3a9f6e1c7d8e5a2b0c9f8e7d6c5b4a3928170f6e5d4c3b2a1908f7e6d5c4b";
+    const OTHER_SIGNER: &str = "03a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f90";
+    const THIRD_SIGNER: &str = "02f0e1d2c3b4a5968778695a4b3c2d1e0f1e2d3c4b5a69788796a5b4c3d2e1f0a1";
+
+    fn action(command: Action_Command, identifier: &str, quantity: i32) -> Action {
+        let mut action = Action::new();
+        action.set_command(command);
+        action.set_identifier(identifier.to_string());
+        action.set_quantity(quantity);
+        action
+    }
+
+    fn request(payload: Vec<u8>, signer: &str) -> TpProcessRequest {
+        let mut header = TransactionHeader::new();
+        header.set_signer_public_key(signer.to_string());
+        let mut request = TpProcessRequest::new();
+        request.set_header(header);
+        request.set_payload(payload);
+        request
+    }
+
+    /// Applies the payload like the validator does, the changes of a failed transaction
+    /// are discarded.
+    fn apply_bytes(
+        context: &mut InMemoryTransactionContext,
+        payload: Vec<u8>,
+        signer: &str,
+    ) -> Result<(), ApplyError> {
+        let checkpoint = context.checkpoint();
+        let result = ProduceConsumeHandler::new().apply(&request(payload, signer), context);
+        if result.is_err() {
+            context.rollback(checkpoint);
+        }
+        result
+    }
+
+    fn apply(
+        context: &mut InMemoryTransactionContext,
+        action: &Action,
+        signer: &str,
+    ) -> Result<(), ApplyError> {
+        apply_bytes(context, action.write_to_bytes().unwrap(), signer)
+    }
+
+    fn default_prefix() -> String {
+        compute_namespace_prefix(default_family_name())
+    }
+
+    fn quantity_in(
+        context: &InMemoryTransactionContext,
+        prefix: &str,
+        identifier: &str,
+    ) -> Option<i32> {
+        context
+            .get(&compute_address(prefix, identifier))
+            .map(|value| {
+                codec::decode_item(identifier, &value)
+                    .unwrap()
+                    .get_quantity()
+            })
+    }
+
+    fn quantity(context: &InMemoryTransactionContext, identifier: &str) -> Option<i32> {
+        quantity_in(context, &default_prefix(), identifier)
+    }
+
+    fn holding(context: &InMemoryTransactionContext, owner: &str, identifier: &str) -> i32 {
+        context
+            .get(&compute_holding_address(
+                &default_prefix(),
+                owner,
+                identifier,
+            ))
+            .map(|value| codec::decode::<Holding>(&value).unwrap().get_quantity())
+            .unwrap_or(0)
+    }
+
+    /// Records the block as the latest one, the way the BlockInfo injector does
+    fn set_block(context: &mut InMemoryTransactionContext, block_num: u64, timestamp: u64) {
+        let mut config = BlockInfoConfig::new();
+        config.set_latest_block(block_num);
+        context.insert(
+            &block_info_config_address(),
+            codec::encode(&config).unwrap(),
+        );
+        let mut block_info = BlockInfo::new();
+        block_info.set_block_num(block_num);
+        block_info.set_timestamp(timestamp);
+        context.insert(
+            &block_info_address(block_num),
+            codec::encode(&block_info).unwrap(),
+        );
+    }
+
+    /// Writes the ```produce_consume.admins``` setting, as the settings family does
+    fn set_admins(context: &mut InMemoryTransactionContext, admins: &[&str]) {
+        let mut entry = Setting_Entry::new();
+        entry.set_key(ADMINS_SETTING.to_string());
+        entry.set_value(admins.join(","));
+        let mut setting = Setting::new();
+        setting.mut_entries().push(entry);
+        context.insert(
+            &compute_setting_address(ADMINS_SETTING),
+            codec::encode(&setting).unwrap(),
+        );
+    }
+
+    fn assert_invalid(result: Result<(), ApplyError>) {
+        match result {
+            Err(ApplyError::InvalidTransaction(_)) => (),
+            other => panic!("Expected an invalid transaction, got {:?}", other),
+        }
+    }
+
+    fn assert_invalid_with(result: Result<(), ApplyError>, message: &str) {
+        match result {
+            Err(ApplyError::InvalidTransaction(ref text)) if text.contains(message) => (),
+            other => panic!(
+                "Expected an invalid transaction with {:?}, got {:?}",
+                message, other
+            ),
+        }
+    }
+
+    #[test]
+    fn produce_adds_to_the_item_and_the_holding() {
+        let mut context = InMemoryTransactionContext::new();
+        apply(
+            &mut context,
+            &action(Action_Command::PRODUCE, "Bread", 10),
+            SIGNER,
+        )
+        .unwrap();
+        apply(
+            &mut context,
+            &action(Action_Command::PRODUCE, "Bread", 5),
+            SIGNER,
+        )
+        .unwrap();
+
+        assert_eq!(quantity(&context, "Bread"), Some(15));
+        assert_eq!(holding(&context, SIGNER, "Bread"), 15);
+        assert_eq!(quantity(&context, "Milk"), None);
+    }
+
+    #[test]
+    fn consume_subtracts_and_reports_the_cost_of_goods() {
+        let mut context = InMemoryTransactionContext::new();
+        let mut produce = action(Action_Command::PRODUCE, "Bread", 10);
+        produce.set_unit_cost(3);
+        apply(&mut context, &produce, SIGNER).unwrap();
+        context.clear_output();
+
+        apply(
+            &mut context,
+            &action(Action_Command::CONSUME, "Bread", 4),
+            SIGNER,
+        )
+        .unwrap();
+
+        assert_eq!(quantity(&context, "Bread"), Some(6));
+        assert_eq!(holding(&context, SIGNER, "Bread"), 6);
+        let receipts = context.receipt_data();
+        assert_eq!(receipts.len(), 1);
+        let receipt: ConsumeReceipt = codec::decode(&receipts[0]).unwrap();
+        assert_eq!(receipt.get_identifier(), "Bread");
+        assert_eq!(receipt.get_quantity(), 4);
+        assert_eq!(receipt.get_cost_of_goods(), 12);
+    }
+
+    #[test]
+    fn consume_the_whole_quantity_leaves_zero() {
+        let mut context = InMemoryTransactionContext::new();
+        apply(
+            &mut context,
+            &action(Action_Command::PRODUCE, "Bread", 3),
+            SIGNER,
+        )
+        .unwrap();
+        apply(
+            &mut context,
+            &action(Action_Command::CONSUME, "Bread", 3),
+            SIGNER,
+        )
+        .unwrap();
+
+        assert_eq!(quantity(&context, "Bread"), Some(0));
+        assert_eq!(holding(&context, SIGNER, "Bread"), 0);
+    }
+
+    #[test]
+    fn consume_below_zero_is_rejected() {
+        let mut context = InMemoryTransactionContext::new();
+        apply(
+            &mut context,
+            &action(Action_Command::PRODUCE, "Bread", 3),
+            SIGNER,
+        )
+        .unwrap();
+        let before = context.entries();
+
+        assert_invalid(apply(
+            &mut context,
+            &action(Action_Command::CONSUME, "Bread", 4),
+            SIGNER,
+        ));
+        assert_invalid(apply(
+            &mut context,
+            &action(Action_Command::CONSUME, "Milk", 1),
+            SIGNER,
+        ));
+        assert_eq!(context.entries(), before);
+    }
+
+    #[test]
+    fn consume_from_the_holding_of_another_owner_is_rejected() {
+        let mut context = InMemoryTransactionContext::new();
+        apply(
+            &mut context,
+            &action(Action_Command::PRODUCE, "Bread", 3),
+            SIGNER,
+        )
+        .unwrap();
+
+        assert_invalid(apply(
+            &mut context,
+            &action(Action_Command::CONSUME, "Bread", 1),
+            OTHER_SIGNER,
+        ));
+        assert_eq!(quantity(&context, "Bread"), Some(3));
+    }
+
+    #[test]
+    fn produce_above_the_maximum_quantity_is_rejected() {
+        let mut context = InMemoryTransactionContext::new();
+        apply(
+            &mut context,
+            &action(Action_Command::PRODUCE, "Bread", i32::max_value()),
+            SIGNER,
+        )
+        .unwrap();
+
+        assert_invalid(apply(
+            &mut context,
+            &action(Action_Command::PRODUCE, "Bread", 1),
+            OTHER_SIGNER,
+        ));
+        assert_eq!(quantity(&context, "Bread"), Some(i32::max_value()));
+        assert_eq!(holding(&context, OTHER_SIGNER, "Bread"), 0);
+    }
+
+    #[test]
+    fn malformed_payloads_are_rejected() {
+        let mut context = InMemoryTransactionContext::new();
+
+        assert_invalid(apply_bytes(&mut context, vec![0xff, 0xff, 0xff], SIGNER));
+        assert_invalid(apply(
+            &mut context,
+            &action(Action_Command::PRODUCE, "", 1),
+            SIGNER,
+        ));
+        assert_invalid(apply(
+            &mut context,
+            &action(Action_Command::PRODUCE, "Bread", 0),
+            SIGNER,
+        ));
+        assert_invalid(apply(
+            &mut context,
+            &action(Action_Command::PRODUCE, "Bread", -5),
+            SIGNER,
+        ));
+        let mut negative_cost = action(Action_Command::PRODUCE, "Bread", 1);
+        negative_cost.set_unit_cost(-1);
+        assert_invalid(apply(&mut context, &negative_cost, SIGNER));
+
+        assert!(context.entries().is_empty());
+        assert!(context.receipt_data().is_empty());
+        assert!(context.events().is_empty());
+    }
+
+    #[test]
+    fn unsigned_transactions_are_rejected() {
+        let mut context = InMemoryTransactionContext::new();
+        assert_invalid(apply(
+            &mut context,
+            &action(Action_Command::PRODUCE, "Bread", 1),
+            "",
+        ));
+        assert!(context.entries().is_empty());
+    }
+
+    #[test]
+    fn families_with_different_names_do_not_share_state() {
+        let mut context = InMemoryTransactionContext::new();
+        let staging = ProduceConsumeHandler::with_family("produce-consume-staging", VERSION);
+        assert_eq!(staging.family_name(), "produce-consume-staging");
+        let staging_prefix = staging.namespaces()[0].clone();
+        assert_ne!(staging_prefix, default_prefix());
+        let staging_namespaces = staging.namespaces();
+
+        let payload = action(Action_Command::PRODUCE, "Bread", 10)
+            .write_to_bytes()
+            .unwrap();
+        staging
+            .apply(&request(payload, SIGNER), &mut context)
+            .unwrap();
+
+        assert_eq!(quantity_in(&context, &staging_prefix, "Bread"), Some(10));
+        assert_eq!(quantity(&context, "Bread"), None);
+        assert!(context.entries().keys().all(|address| staging_namespaces
+            .iter()
+            .any(|namespace| address.starts_with(namespace))));
+    }
+
+    #[test]
+    fn item_entry_carries_the_identifier() {
+        let mut context = InMemoryTransactionContext::new();
+        apply(
+            &mut context,
+            &action(Action_Command::PRODUCE, "Bread", 10),
+            SIGNER,
+        )
+        .unwrap();
+
+        let address = compute_address(&default_prefix(), "Bread");
+        let item =
+            codec::decode_item_at(&default_prefix(), &address, &context.get(&address).unwrap())
+                .unwrap();
+        assert_eq!(item.get_identifier(), "Bread");
+        assert_eq!(item.get_quantity(), 10);
+    }
+
+    #[test]
+    fn former_item_entry_is_read_and_rewritten() {
+        let mut context = InMemoryTransactionContext::new();
+        // The quantity alone, as written before the identifier was stored
+        let address = compute_address(&default_prefix(), "Bread");
+        context.insert(&address, codec::encode_quantity(10));
+
+        apply(
+            &mut context,
+            &action(Action_Command::CONSUME, "Bread", 4),
+            SIGNER,
+        )
+        .unwrap();
+
+        let item = codec::decode::<Item>(&context.get(&address).unwrap()).unwrap();
+        assert_eq!(item.get_identifier(), "Bread");
+        assert_eq!(item.get_quantity(), 6);
+    }
+
+    fn rate_limit(window_blocks: u64, max_transactions: u32, max_quantity: i64) -> Action {
+        let mut limit = RateLimit::new();
+        limit.set_window_blocks(window_blocks);
+        limit.set_max_transactions(max_transactions);
+        limit.set_max_quantity(max_quantity);
+        payload::set_rate_limit(limit)
+    }
+
+    #[test]
+    fn transactions_over_the_limit_are_rejected() {
+        let mut context = InMemoryTransactionContext::new();
+        set_admins(&mut context, &[OTHER_SIGNER]);
+        set_block(&mut context, 12, 1_000);
+        apply(&mut context, &rate_limit(10, 2, 0), OTHER_SIGNER).unwrap();
+
+        apply(&mut context, &payload::produce("Bread", 1, 0, ""), SIGNER).unwrap();
+        apply(&mut context, &payload::produce("Bread", 1, 0, ""), SIGNER).unwrap();
+        assert_invalid_with(
+            apply(&mut context, &payload::produce("Bread", 1, 0, ""), SIGNER),
+            &format!(
+                "Signer {} exceeded the limit of 2 transactions per 10 blocks, \
+                 window starts at block 10",
+                SIGNER
+            ),
+        );
+        assert_eq!(quantity(&context, "Bread"), Some(2));
+        // The usage of every signer is counted apart
+        apply(
+            &mut context,
+            &payload::produce("Bread", 1, 0, ""),
+            OTHER_SIGNER,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn quantity_over_the_limit_is_rejected() {
+        let mut context = InMemoryTransactionContext::new();
+        set_admins(&mut context, &[OTHER_SIGNER]);
+        set_block(&mut context, 5, 1_000);
+        apply(&mut context, &rate_limit(10, 0, 10), OTHER_SIGNER).unwrap();
+
+        apply(&mut context, &payload::produce("Bread", 6, 0, ""), SIGNER).unwrap();
+        assert_invalid_with(
+            apply(&mut context, &payload::consume("Bread", 5, ""), SIGNER),
+            &format!(
+                "Signer {} exceeded the limit of 10 in quantity per 10 blocks, \
+                 window starts at block 0",
+                SIGNER
+            ),
+        );
+        apply(&mut context, &payload::consume("Bread", 4, ""), SIGNER).unwrap();
+        assert_eq!(quantity(&context, "Bread"), Some(2));
+    }
+
+    #[test]
+    fn usage_past_its_range_is_rejected() {
+        let mut context = InMemoryTransactionContext::new();
+        set_admins(&mut context, &[OTHER_SIGNER]);
+        set_block(&mut context, 5, 1_000);
+        apply(&mut context, &rate_limit(10, 0, 0), OTHER_SIGNER).unwrap();
+        let address = compute_signer_usage_address(&default_prefix(), SIGNER);
+        let mut usage = SignerUsage::new();
+        usage.set_signer(SIGNER.to_string());
+        usage.set_quantity(i64::MAX - 1);
+        context.insert(&address, codec::encode(&usage).unwrap());
+
+        assert_invalid_with(
+            apply(&mut context, &payload::produce("Bread", 2, 0, ""), SIGNER),
+            &format!(
+                "Usage of signer {} overflows in the window starting at block 0",
+                SIGNER
+            ),
+        );
+        apply(&mut context, &payload::produce("Bread", 1, 0, ""), SIGNER).unwrap();
+        let usage: SignerUsage = codec::decode(&context.get(&address).unwrap()).unwrap();
+        assert_eq!(usage.get_quantity(), i64::MAX);
+        assert_eq!(usage.get_transactions(), 1);
+
+        let mut usage = SignerUsage::new();
+        usage.set_signer(SIGNER.to_string());
+        usage.set_transactions(u32::MAX);
+        context.insert(&address, codec::encode(&usage).unwrap());
+        assert_invalid(apply(
+            &mut context,
+            &payload::produce("Bread", 1, 0, ""),
+            SIGNER,
+        ));
+    }
+
+    #[test]
+    fn usage_is_reset_when_the_window_rolls_over() {
+        let mut context = InMemoryTransactionContext::new();
+        set_admins(&mut context, &[OTHER_SIGNER]);
+        set_block(&mut context, 18, 1_000);
+        apply(&mut context, &rate_limit(10, 1, 0), OTHER_SIGNER).unwrap();
+
+        apply(&mut context, &payload::produce("Bread", 1, 0, ""), SIGNER).unwrap();
+        set_block(&mut context, 19, 1_005);
+        assert_invalid(apply(
+            &mut context,
+            &payload::produce("Bread", 1, 0, ""),
+            SIGNER,
+        ));
+        set_block(&mut context, 20, 1_010);
+        apply(&mut context, &payload::produce("Bread", 1, 0, ""), SIGNER).unwrap();
+        assert_eq!(quantity(&context, "Bread"), Some(2));
+    }
+
+    #[test]
+    fn admin_commands_are_not_limited() {
+        let mut context = InMemoryTransactionContext::new();
+        set_admins(&mut context, &[SIGNER]);
+        set_block(&mut context, 1, 1_000);
+        apply(&mut context, &rate_limit(10, 1, 0), SIGNER).unwrap();
+        apply(&mut context, &payload::produce("Bread", 1, 0, ""), SIGNER).unwrap();
+        assert_invalid(apply(
+            &mut context,
+            &payload::produce("Bread", 1, 0, ""),
+            SIGNER,
+        ));
+
+        // The administrator can still lift the limit once it is exhausted
+        apply(&mut context, &rate_limit(0, 0, 0), SIGNER).unwrap();
+        apply(&mut context, &payload::produce("Bread", 1, 0, ""), SIGNER).unwrap();
+        assert_eq!(quantity(&context, "Bread"), Some(2));
+    }
+
+    #[test]
+    fn only_administrators_set_the_rate_limit() {
+        let mut context = InMemoryTransactionContext::new();
+        set_admins(&mut context, &[OTHER_SIGNER]);
+        assert_invalid(apply(&mut context, &rate_limit(10, 1, 0), SIGNER));
+        // Only the setting is left
+        assert_eq!(context.entries().len(), 1);
+    }
+
+    fn time_bound(valid_after: u64, valid_before: u64) -> Action {
+        let mut action = payload::produce("Bread", 1, 0, "");
+        action.set_valid_after(valid_after);
+        action.set_valid_before(valid_before);
+        action
+    }
+
+    #[test]
+    fn actions_apply_within_their_validity_window() {
+        let mut context = InMemoryTransactionContext::new();
+        set_block(&mut context, 7, 1_000);
+
+        apply(&mut context, &time_bound(1_000, 0), SIGNER).unwrap();
+        apply(&mut context, &time_bound(0, 1_001), SIGNER).unwrap();
+        apply(&mut context, &time_bound(900, 1_100), SIGNER).unwrap();
+        assert_eq!(quantity(&context, "Bread"), Some(3));
+    }
+
+    #[test]
+    fn actions_outside_of_their_validity_window_are_rejected() {
+        let mut context = InMemoryTransactionContext::new();
+        set_block(&mut context, 7, 1_000);
+
+        assert_invalid_with(
+            apply(&mut context, &time_bound(1_001, 0), SIGNER),
+            "Action is not valid before 1001, current block timestamp is 1000",
+        );
+        // The upper bound is excluded
+        assert_invalid_with(
+            apply(&mut context, &time_bound(0, 1_000), SIGNER),
+            "Action expired at 1000, current block timestamp is 1000",
+        );
+        assert_eq!(quantity(&context, "Bread"), None);
+    }
+
+    #[test]
+    fn time_bound_actions_require_the_block_info() {
+        let mut context = InMemoryTransactionContext::new();
+        assert_invalid_with(
+            apply(&mut context, &time_bound(1, 0), SIGNER),
+            "BlockInfo config is not available",
+        );
+
+        // The config names a block whose entry is missing
+        let mut config = BlockInfoConfig::new();
+        config.set_latest_block(3);
+        context.insert(
+            &block_info_config_address(),
+            codec::encode(&config).unwrap(),
+        );
+        assert_invalid_with(
+            apply(&mut context, &time_bound(1, 0), SIGNER),
+            "BlockInfo for the block 3 is not available",
+        );
+
+        // Actions without a window do not read the BlockInfo
+        apply(&mut context, &payload::produce("Bread", 1, 0, ""), SIGNER).unwrap();
+    }
+
+    /// Consumptions above the threshold need two of the approvers, OTHER_SIGNER and
+    /// THIRD_SIGNER. OTHER_SIGNER is also the administrator.
+    fn with_approval_policy(context: &mut InMemoryTransactionContext, threshold: i32) {
+        set_admins(context, &[OTHER_SIGNER]);
+        let mut policy = ApprovalPolicy::new();
+        policy.set_threshold(threshold);
+        policy.set_required_approvals(2);
+        policy.set_approvers(vec![OTHER_SIGNER.to_string(), THIRD_SIGNER.to_string()].into());
+        apply(context, &payload::set_approval_policy(policy), OTHER_SIGNER).unwrap();
+    }
+
+    fn proposal(context: &InMemoryTransactionContext, proposal_id: &str) -> Option<Proposal> {
+        context
+            .get(&compute_proposal_address(&default_prefix(), proposal_id))
+            .map(|value| codec::decode(&value).unwrap())
+    }
+
+    #[test]
+    fn consume_up_to_the_threshold_applies_right_away() {
+        let mut context = InMemoryTransactionContext::new();
+        with_approval_policy(&mut context, 5);
+        apply(&mut context, &payload::produce("Bread", 10, 0, ""), SIGNER).unwrap();
+
+        apply(&mut context, &payload::consume("Bread", 5, ""), SIGNER).unwrap();
+        assert_eq!(quantity(&context, "Bread"), Some(5));
+    }
+
+    #[test]
+    fn consume_above_the_threshold_becomes_a_proposal() {
+        let mut context = InMemoryTransactionContext::new();
+        with_approval_policy(&mut context, 5);
+        apply(&mut context, &payload::produce("Bread", 10, 0, ""), SIGNER).unwrap();
+
+        assert_invalid_with(
+            apply(&mut context, &payload::consume("Bread", 6, ""), SIGNER),
+            "Consuming more than 5 requires approvals, a proposal id is required",
+        );
+        assert_invalid_with(
+            apply(
+                &mut context,
+                &payload::consume("Bread", 11, "large"),
+                SIGNER,
+            ),
+            "Insufficient quantity of Bread",
+        );
+        apply(&mut context, &payload::consume("Bread", 6, "large"), SIGNER).unwrap();
+        assert_invalid_with(
+            apply(&mut context, &payload::consume("Bread", 6, "large"), SIGNER),
+            "Proposal large already exists",
+        );
+
+        let pending = proposal(&context, "large").unwrap();
+        assert_eq!(pending.get_status(), Proposal_Status::PENDING);
+        assert_eq!(pending.get_proposer(), SIGNER);
+        assert_eq!(pending.get_identifier(), "Bread");
+        assert_eq!(pending.get_quantity(), 6);
+        assert_eq!(quantity(&context, "Bread"), Some(10));
+    }
+
+    #[test]
+    fn proposal_executes_with_the_required_distinct_approvals() {
+        let mut context = InMemoryTransactionContext::new();
+        with_approval_policy(&mut context, 5);
+        apply(&mut context, &payload::produce("Bread", 10, 2, ""), SIGNER).unwrap();
+        apply(&mut context, &payload::consume("Bread", 6, "large"), SIGNER).unwrap();
+        context.clear_output();
+
+        apply(&mut context, &payload::approve("large"), OTHER_SIGNER).unwrap();
+        assert_invalid_with(
+            apply(&mut context, &payload::approve("large"), OTHER_SIGNER),
+            &format!("Proposal large is already approved by {}", OTHER_SIGNER),
+        );
+        assert_invalid_with(
+            apply(&mut context, &payload::approve("large"), SIGNER),
+            &format!("{} is not an authorized approver", SIGNER),
+        );
+        assert_eq!(quantity(&context, "Bread"), Some(10));
+        assert!(context.receipt_data().is_empty());
+
+        apply(&mut context, &payload::approve("large"), THIRD_SIGNER).unwrap();
+        let executed = proposal(&context, "large").unwrap();
+        assert_eq!(executed.get_status(), Proposal_Status::EXECUTED);
+        assert_eq!(
+            executed.get_approvals().to_vec(),
+            vec![OTHER_SIGNER.to_string(), THIRD_SIGNER.to_string()]
+        );
+        // The holding of the proposer is consumed, not the one of the last approver
+        assert_eq!(quantity(&context, "Bread"), Some(4));
+        assert_eq!(holding(&context, SIGNER, "Bread"), 4);
+        let receipt: ConsumeReceipt = codec::decode(&context.receipt_data()[0]).unwrap();
+        assert_eq!(receipt.get_quantity(), 6);
+        assert_eq!(receipt.get_cost_of_goods(), 12);
+
+        assert_invalid_with(
+            apply(&mut context, &payload::approve("large"), THIRD_SIGNER),
+            "Proposal large is not pending, status is EXECUTED",
+        );
+    }
+
+    #[test]
+    fn rejected_proposal_cannot_be_approved() {
+        let mut context = InMemoryTransactionContext::new();
+        with_approval_policy(&mut context, 5);
+        apply(&mut context, &payload::produce("Bread", 10, 0, ""), SIGNER).unwrap();
+        apply(&mut context, &payload::consume("Bread", 6, "large"), SIGNER).unwrap();
+
+        assert_invalid(apply(&mut context, &payload::reject("large"), SIGNER));
+        apply(&mut context, &payload::reject("large"), THIRD_SIGNER).unwrap();
+        assert_eq!(
+            proposal(&context, "large").unwrap().get_status(),
+            Proposal_Status::REJECTED
+        );
+        assert_invalid_with(
+            apply(&mut context, &payload::approve("large"), OTHER_SIGNER),
+            "Proposal large is not pending, status is REJECTED",
+        );
+        assert_invalid_with(
+            apply(&mut context, &payload::approve("unknown"), OTHER_SIGNER),
+            "Proposal unknown does not exist",
+        );
+        assert_eq!(quantity(&context, "Bread"), Some(10));
+    }
+
+    #[test]
+    fn approval_policy_is_validated() {
+        let mut context = InMemoryTransactionContext::new();
+        set_admins(&mut context, &[OTHER_SIGNER]);
+        let policy = |threshold: i32, required_approvals: u32, approvers: &[&str]| {
+            let mut policy = ApprovalPolicy::new();
+            policy.set_threshold(threshold);
+            policy.set_required_approvals(required_approvals);
+            policy.set_approvers(
+                approvers
+                    .iter()
+                    .map(|approver| approver.to_string())
+                    .collect::<Vec<String>>()
+                    .into(),
+            );
+            payload::set_approval_policy(policy)
+        };
+
+        assert_invalid_with(
+            apply(&mut context, &policy(5, 1, &[SIGNER]), SIGNER),
+            "is not an authorized administrator",
+        );
+        assert_invalid_with(
+            apply(&mut context, &policy(-1, 1, &[SIGNER]), OTHER_SIGNER),
+            "Threshold cannot be negative",
+        );
+        assert_invalid_with(
+            apply(&mut context, &policy(5, 1, &[SIGNER, SIGNER]), OTHER_SIGNER),
+            "Approvers must be distinct",
+        );
+        assert_invalid_with(
+            apply(&mut context, &policy(5, 2, &[SIGNER]), OTHER_SIGNER),
+            "Required approvals must be between 1 and the number of approvers",
+        );
+        assert_invalid(apply(&mut context, &policy(5, 0, &[SIGNER]), OTHER_SIGNER));
+        apply(&mut context, &policy(5, 1, &[SIGNER]), OTHER_SIGNER).unwrap();
+        // A zero threshold disables the approvals, there is nothing to check
+        apply(&mut context, &policy(0, 0, &[]), OTHER_SIGNER).unwrap();
+    }
+
+    fn consume_receipt(context: &InMemoryTransactionContext) -> ConsumeReceipt {
+        let receipts = context.receipt_data();
+        assert_eq!(receipts.len(), 1);
+        codec::decode(&receipts[0]).unwrap()
+    }
+
+    /// Quantity and value of each lot of the item
+    fn lots(context: &InMemoryTransactionContext, identifier: &str) -> Vec<(i32, i64)> {
+        let layers: CostLayers = context
+            .get(&compute_cost_layers_address(&default_prefix(), identifier))
+            .map(|value| codec::decode(&value).unwrap())
+            .unwrap_or_default();
+        layers
+            .get_lots()
+            .iter()
+            .map(|lot| (lot.get_quantity(), lot.get_value()))
+            .collect()
+    }
+
+    /// Two lots of Bread, 10 at 2 then 10 at 5
+    fn produce_two_lots(context: &mut InMemoryTransactionContext) {
+        apply(context, &payload::produce("Bread", 10, 2, ""), SIGNER).unwrap();
+        apply(context, &payload::produce("Bread", 10, 5, ""), SIGNER).unwrap();
+        assert_eq!(lots(context, "Bread"), vec![(10, 20), (10, 50)]);
+        context.clear_output();
+    }
+
+    #[test]
+    fn fifo_consumes_the_oldest_lots_first() {
+        let mut context = InMemoryTransactionContext::new();
+        produce_two_lots(&mut context);
+
+        apply(&mut context, &payload::consume("Bread", 15, ""), SIGNER).unwrap();
+        let receipt = consume_receipt(&context);
+        assert_eq!(receipt.get_identifier(), "Bread");
+        assert_eq!(receipt.get_quantity(), 15);
+        assert_eq!(receipt.get_cost_of_goods(), 45);
+        assert_eq!(receipt.get_valuation_policy(), ValuationPolicy::FIFO);
+        assert_eq!(lots(&context, "Bread"), vec![(5, 25)]);
+    }
+
+    #[test]
+    fn weighted_average_consumes_at_the_average_cost() {
+        let mut context = InMemoryTransactionContext::new();
+        set_admins(&mut context, &[OTHER_SIGNER]);
+        assert_invalid(apply(
+            &mut context,
+            &payload::set_valuation_policy(ValuationPolicy::WEIGHTED_AVERAGE),
+            SIGNER,
+        ));
+        apply(
+            &mut context,
+            &payload::set_valuation_policy(ValuationPolicy::WEIGHTED_AVERAGE),
+            OTHER_SIGNER,
+        )
+        .unwrap();
+        produce_two_lots(&mut context);
+
+        // 15 of 20 units worth 70, 52.5 is rounded
+        apply(&mut context, &payload::consume("Bread", 15, ""), SIGNER).unwrap();
+        let receipt = consume_receipt(&context);
+        assert_eq!(receipt.get_cost_of_goods(), 53);
+        assert_eq!(
+            receipt.get_valuation_policy(),
+            ValuationPolicy::WEIGHTED_AVERAGE
+        );
+        assert_eq!(lots(&context, "Bread"), vec![(5, 17)]);
+
+        // What is left is consumed at its remaining value, nothing is lost to rounding
+        context.clear_output();
+        apply(&mut context, &payload::consume("Bread", 5, ""), SIGNER).unwrap();
+        assert_eq!(consume_receipt(&context).get_cost_of_goods(), 17);
+        assert!(lots(&context, "Bread").is_empty());
+    }
+
+    #[test]
+    fn changing_the_policy_keeps_the_lots() {
+        let mut context = InMemoryTransactionContext::new();
+        set_admins(&mut context, &[OTHER_SIGNER]);
+        produce_two_lots(&mut context);
+        apply(&mut context, &payload::consume("Bread", 5, ""), SIGNER).unwrap();
+        assert_eq!(lots(&context, "Bread"), vec![(5, 10), (10, 50)]);
+
+        apply(
+            &mut context,
+            &payload::set_valuation_policy(ValuationPolicy::WEIGHTED_AVERAGE),
+            OTHER_SIGNER,
+        )
+        .unwrap();
+        assert_eq!(lots(&context, "Bread"), vec![(5, 10), (10, 50)]);
+        context.clear_output();
+        apply(&mut context, &payload::consume("Bread", 3, ""), SIGNER).unwrap();
+        assert_eq!(consume_receipt(&context).get_cost_of_goods(), 12);
+    }
+
+    fn item(context: &InMemoryTransactionContext, identifier: &str) -> Item {
+        let address = compute_address(&default_prefix(), identifier);
+        codec::decode_item(identifier, &context.get(&address).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn former_quantity_is_held_by_no_one() {
+        let mut context = InMemoryTransactionContext::new();
+        let address = compute_address(&default_prefix(), "Bread");
+        context.insert(&address, codec::encode_quantity(10));
+
+        // Any signer can consume it, even without a holding
+        apply(
+            &mut context,
+            &payload::consume("Bread", 4, ""),
+            OTHER_SIGNER,
+        )
+        .unwrap();
+        assert_eq!(item(&context, "Bread").get_quantity(), 6);
+        assert_eq!(item(&context, "Bread").get_held(), 0);
+        // It has no cost layer, it is consumed at zero cost
+        assert_eq!(consume_receipt(&context).get_cost_of_goods(), 0);
+
+        // The holding is used first, then the quantity held by no one
+        apply(&mut context, &payload::produce("Bread", 5, 3, ""), SIGNER).unwrap();
+        assert_eq!(item(&context, "Bread").get_held(), 5);
+        apply(&mut context, &payload::consume("Bread", 7, ""), SIGNER).unwrap();
+        assert_eq!(holding(&context, SIGNER, "Bread"), 0);
+        assert_eq!(item(&context, "Bread").get_quantity(), 4);
+        assert_eq!(item(&context, "Bread").get_held(), 0);
+
+        apply(&mut context, &payload::produce("Bread", 2, 0, ""), SIGNER).unwrap();
+        assert_invalid_with(
+            apply(
+                &mut context,
+                &payload::consume("Bread", 5, ""),
+                OTHER_SIGNER,
+            ),
+            &format!("Insufficient quantity of Bread held by {}", OTHER_SIGNER),
+        );
+        apply(
+            &mut context,
+            &payload::consume("Bread", 4, ""),
+            OTHER_SIGNER,
+        )
+        .unwrap();
+        assert_eq!(item(&context, "Bread").get_quantity(), 2);
+        assert_eq!(holding(&context, SIGNER, "Bread"), 2);
+    }
+
+    #[test]
+    fn former_quantity_can_be_offered() {
+        let mut context = InMemoryTransactionContext::new();
+        let address = compute_address(&default_prefix(), "Bread");
+        context.insert(&address, codec::encode_quantity(10));
+
+        apply(
+            &mut context,
+            &payload::offer_transfer("transfer", "Bread", 4, OTHER_SIGNER, 0),
+            SIGNER,
+        )
+        .unwrap();
+        // The escrow is held, it cannot be consumed by anyone else
+        assert_eq!(item(&context, "Bread").get_held(), 4);
+        assert_invalid(apply(
+            &mut context,
+            &payload::consume("Bread", 7, ""),
+            THIRD_SIGNER,
+        ));
+        apply(
+            &mut context,
+            &payload::accept_transfer("transfer"),
+            OTHER_SIGNER,
+        )
+        .unwrap();
+        assert_eq!(holding(&context, OTHER_SIGNER, "Bread"), 4);
+        assert_eq!(item(&context, "Bread").get_quantity(), 10);
+    }
+
+    fn escrow(context: &InMemoryTransactionContext, transfer_id: &str) -> Escrow {
+        let address = compute_escrow_address(&default_prefix(), transfer_id);
+        codec::decode(&context.get(&address).unwrap()).unwrap()
+    }
+
+    /// SIGNER produces 10 Bread and offers 4 of them to OTHER_SIGNER
+    fn offer(context: &mut InMemoryTransactionContext, expires_at_block: u64) {
+        apply(context, &payload::produce("Bread", 10, 0, ""), SIGNER).unwrap();
+        apply(
+            context,
+            &payload::offer_transfer("transfer", "Bread", 4, OTHER_SIGNER, expires_at_block),
+            SIGNER,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn offered_quantity_is_locked_in_escrow() {
+        let mut context = InMemoryTransactionContext::new();
+        offer(&mut context, 0);
+
+        let offered = escrow(&context, "transfer");
+        assert_eq!(offered.get_status(), Escrow_Status::OFFERED);
+        assert_eq!(offered.get_sender(), SIGNER);
+        assert_eq!(offered.get_recipient(), OTHER_SIGNER);
+        assert_eq!(offered.get_quantity(), 4);
+        assert_eq!(holding(&context, SIGNER, "Bread"), 6);
+        assert_eq!(holding(&context, OTHER_SIGNER, "Bread"), 0);
+        // The item is unchanged, the quantity is still on hand
+        assert_eq!(quantity(&context, "Bread"), Some(10));
+        assert_invalid(apply(
+            &mut context,
+            &payload::consume("Bread", 7, ""),
+            SIGNER,
+        ));
+        assert_invalid(apply(
+            &mut context,
+            &payload::consume("Bread", 1, ""),
+            OTHER_SIGNER,
+        ));
+    }
+
+    #[test]
+    fn invalid_offers_are_rejected() {
+        let mut context = InMemoryTransactionContext::new();
+        offer(&mut context, 0);
+        set_block(&mut context, 20, 1_000);
+
+        assert_invalid_with(
+            apply(
+                &mut context,
+                &payload::offer_transfer("self", "Bread", 1, SIGNER, 0),
+                SIGNER,
+            ),
+            "Cannot transfer to the sender",
+        );
+        assert_invalid_with(
+            apply(
+                &mut context,
+                &payload::offer_transfer("transfer", "Bread", 1, OTHER_SIGNER, 0),
+                SIGNER,
+            ),
+            "Transfer transfer already exists",
+        );
+        assert_invalid_with(
+            apply(
+                &mut context,
+                &payload::offer_transfer("late", "Bread", 1, OTHER_SIGNER, 20),
+                SIGNER,
+            ),
+            "Transfer late would be expired at block 20",
+        );
+        assert_invalid_with(
+            apply(
+                &mut context,
+                &payload::offer_transfer("large", "Bread", 7, OTHER_SIGNER, 0),
+                SIGNER,
+            ),
+            "Insufficient quantity of Bread",
+        );
+    }
+
+    #[test]
+    fn accepted_transfer_moves_to_the_recipient() {
+        let mut context = InMemoryTransactionContext::new();
+        offer(&mut context, 0);
+
+        assert_invalid_with(
+            apply(&mut context, &payload::accept_transfer("transfer"), SIGNER),
+            "Only the recipient can accept the transfer transfer",
+        );
+        apply(
+            &mut context,
+            &payload::accept_transfer("transfer"),
+            OTHER_SIGNER,
+        )
+        .unwrap();
+        assert_eq!(
+            escrow(&context, "transfer").get_status(),
+            Escrow_Status::ACCEPTED
+        );
+        assert_eq!(holding(&context, SIGNER, "Bread"), 6);
+        assert_eq!(holding(&context, OTHER_SIGNER, "Bread"), 4);
+        assert_invalid_with(
+            apply(
+                &mut context,
+                &payload::accept_transfer("transfer"),
+                OTHER_SIGNER,
+            ),
+            "Transfer transfer is not offered, status is ACCEPTED",
+        );
+
+        apply(
+            &mut context,
+            &payload::consume("Bread", 4, ""),
+            OTHER_SIGNER,
+        )
+        .unwrap();
+        assert_eq!(quantity(&context, "Bread"), Some(6));
+    }
+
+    #[test]
+    fn declined_or_withdrawn_transfer_returns_to_the_sender() {
+        let mut context = InMemoryTransactionContext::new();
+        offer(&mut context, 0);
+        assert_invalid_with(
+            apply(&mut context, &payload::decline_transfer("transfer"), SIGNER),
+            "Only the recipient can decline the transfer transfer",
+        );
+        apply(
+            &mut context,
+            &payload::decline_transfer("transfer"),
+            OTHER_SIGNER,
+        )
+        .unwrap();
+        assert_eq!(
+            escrow(&context, "transfer").get_status(),
+            Escrow_Status::DECLINED
+        );
+        assert_eq!(holding(&context, SIGNER, "Bread"), 10);
+
+        apply(
+            &mut context,
+            &payload::offer_transfer("again", "Bread", 3, OTHER_SIGNER, 0),
+            SIGNER,
+        )
+        .unwrap();
+        assert_invalid_with(
+            apply(
+                &mut context,
+                &payload::withdraw_transfer("again"),
+                OTHER_SIGNER,
+            ),
+            "Only the sender can withdraw the transfer again",
+        );
+        apply(&mut context, &payload::withdraw_transfer("again"), SIGNER).unwrap();
+        assert_eq!(
+            escrow(&context, "again").get_status(),
+            Escrow_Status::WITHDRAWN
+        );
+        assert_eq!(holding(&context, SIGNER, "Bread"), 10);
+        assert_eq!(holding(&context, OTHER_SIGNER, "Bread"), 0);
+        assert_invalid_with(
+            apply(&mut context, &payload::withdraw_transfer("unknown"), SIGNER),
+            "Transfer unknown does not exist",
+        );
+    }
+
+    #[test]
+    fn expired_offer_is_returned_by_any_response() {
+        let mut context = InMemoryTransactionContext::new();
+        set_block(&mut context, 10, 1_000);
+        offer(&mut context, 12);
+        set_block(&mut context, 12, 1_010);
+
+        // The acceptance is valid, it records the expiry instead of the transfer
+        apply(
+            &mut context,
+            &payload::accept_transfer("transfer"),
+            OTHER_SIGNER,
+        )
+        .unwrap();
+        assert_eq!(
+            escrow(&context, "transfer").get_status(),
+            Escrow_Status::EXPIRED
+        );
+        assert_eq!(holding(&context, SIGNER, "Bread"), 10);
+        assert_eq!(holding(&context, OTHER_SIGNER, "Bread"), 0);
+
+        for (transfer_id, response, signer) in vec![
+            (
+                "declined",
+                payload::decline_transfer("declined"),
+                OTHER_SIGNER,
+            ),
+            ("withdrawn", payload::withdraw_transfer("withdrawn"), SIGNER),
+        ] {
+            set_block(&mut context, 12, 1_010);
+            apply(
+                &mut context,
+                &payload::offer_transfer(transfer_id, "Bread", 2, OTHER_SIGNER, 13),
+                SIGNER,
+            )
+            .unwrap();
+            assert_eq!(holding(&context, SIGNER, "Bread"), 8);
+            set_block(&mut context, 13, 1_020);
+            apply(&mut context, &response, signer).unwrap();
+            assert_eq!(
+                escrow(&context, transfer_id).get_status(),
+                Escrow_Status::EXPIRED
+            );
+            assert_eq!(holding(&context, SIGNER, "Bread"), 10);
+        }
+    }
+
+    #[test]
+    fn offer_is_accepted_before_its_expiry() {
+        let mut context = InMemoryTransactionContext::new();
+        set_block(&mut context, 10, 1_000);
+        offer(&mut context, 12);
+        set_block(&mut context, 11, 1_005);
+
+        apply(
+            &mut context,
+            &payload::accept_transfer("transfer"),
+            OTHER_SIGNER,
+        )
+        .unwrap();
+        assert_eq!(
+            escrow(&context, "transfer").get_status(),
+            Escrow_Status::ACCEPTED
+        );
+        assert_eq!(holding(&context, OTHER_SIGNER, "Bread"), 4);
+    }
+
+    fn item_totals(context: &InMemoryTransactionContext, identifier: &str) -> (String, i64, i64) {
+        let address = compute_item_totals_address(&default_prefix(), identifier);
+        let totals: ItemTotals = codec::decode(&context.get(&address).unwrap()).unwrap();
+        (
+            totals.get_category().to_string(),
+            totals.get_produced(),
+            totals.get_consumed(),
+        )
+    }
+
+    /// Produced, consumed and on hand totals stored at the address
+    fn totals_at(context: &InMemoryTransactionContext, address: &str) -> (i64, i64, i64) {
+        let totals: CategoryTotals = codec::decode(&context.get(address).unwrap()).unwrap();
+        (
+            totals.get_produced(),
+            totals.get_consumed(),
+            totals.get_on_hand(),
+        )
+    }
+
+    fn category_totals(context: &InMemoryTransactionContext, category: &str) -> (i64, i64, i64) {
+        totals_at(
+            context,
+            &compute_category_totals_address(&default_prefix(), category),
+        )
+    }
+
+    #[test]
+    fn totals_follow_the_items_and_their_categories() {
+        let mut context = InMemoryTransactionContext::new();
+        apply(
+            &mut context,
+            &payload::produce("Bread", 10, 0, "Bakery"),
+            SIGNER,
+        )
+        .unwrap();
+        apply(
+            &mut context,
+            &payload::produce("Rolls", 3, 0, "Bakery"),
+            SIGNER,
+        )
+        .unwrap();
+        apply(
+            &mut context,
+            &payload::produce("Milk", 5, 0, "Dairy"),
+            SIGNER,
+        )
+        .unwrap();
+        apply(&mut context, &payload::produce("Salt", 2, 0, ""), SIGNER).unwrap();
+        apply(&mut context, &payload::consume("Bread", 4, ""), SIGNER).unwrap();
+        apply(&mut context, &payload::consume("Milk", 5, ""), SIGNER).unwrap();
+
+        assert_eq!(
+            item_totals(&context, "Bread"),
+            ("Bakery".to_string(), 10, 4)
+        );
+        assert_eq!(item_totals(&context, "Milk"), ("Dairy".to_string(), 5, 5));
+        assert_eq!(item_totals(&context, "Salt"), ("".to_string(), 2, 0));
+        assert_eq!(category_totals(&context, "Bakery"), (13, 4, 9));
+        assert_eq!(category_totals(&context, "Dairy"), (5, 5, 0));
+        assert_eq!(category_totals(&context, ""), (2, 0, 2));
+        assert_eq!(
+            totals_at(&context, &compute_global_totals_address(&default_prefix())),
+            (20, 9, 11)
+        );
+    }
+
+    #[test]
+    fn category_is_set_by_the_first_produce() {
+        let mut context = InMemoryTransactionContext::new();
+        apply(
+            &mut context,
+            &payload::produce("Bread", 10, 0, "Bakery"),
+            SIGNER,
+        )
+        .unwrap();
+
+        assert_invalid_with(
+            apply(
+                &mut context,
+                &payload::produce("Bread", 1, 0, "Dairy"),
+                SIGNER,
+            ),
+            "Bread is already in the category \"Bakery\"",
+        );
+        // Without a category the item stays in its own
+        apply(&mut context, &payload::produce("Bread", 1, 0, ""), SIGNER).unwrap();
+        assert_eq!(
+            item_totals(&context, "Bread"),
+            ("Bakery".to_string(), 11, 0)
+        );
+        assert_eq!(category_totals(&context, "Bakery"), (11, 0, 11));
+        assert!(context
+            .get(&compute_category_totals_address(&default_prefix(), "Dairy"))
+            .is_none());
+    }
+
+    #[test]
+    fn rejected_consumptions_leave_the_totals() {
+        let mut context = InMemoryTransactionContext::new();
+        apply(
+            &mut context,
+            &payload::produce("Bread", 3, 0, "Bakery"),
+            SIGNER,
+        )
+        .unwrap();
+        assert_invalid(apply(
+            &mut context,
+            &payload::consume("Bread", 4, ""),
+            SIGNER,
+        ));
+        assert_eq!(item_totals(&context, "Bread"), ("Bakery".to_string(), 3, 0));
+        assert_eq!(category_totals(&context, "Bakery"), (3, 0, 3));
+    }
+
+    #[test]
+    fn only_executed_consumptions_add_receipt_data() {
+        let mut context = InMemoryTransactionContext::new();
+        with_approval_policy(&mut context, 5);
+        let commands = vec![
+            (payload::produce("Bread", 20, 2, "Bakery"), SIGNER, false),
+            (payload::consume("Bread", 2, ""), SIGNER, true),
+            (payload::consume("Bread", 6, "large"), SIGNER, false),
+            (payload::approve("large"), OTHER_SIGNER, false),
+            (payload::approve("large"), THIRD_SIGNER, true),
+            (
+                payload::offer_transfer("transfer", "Bread", 3, OTHER_SIGNER, 0),
+                SIGNER,
+                false,
+            ),
+            (payload::accept_transfer("transfer"), OTHER_SIGNER, false),
+            (
+                payload::set_valuation_policy(ValuationPolicy::WEIGHTED_AVERAGE),
+                OTHER_SIGNER,
+                false,
+            ),
+            (payload::consume("Bread", 3, ""), OTHER_SIGNER, true),
+        ];
+        for (action, signer, consumes) in commands {
+            context.clear_output();
+            apply(&mut context, &action, signer).unwrap();
+            assert_eq!(
+                context.receipt_data().len(),
+                if consumes { 1 } else { 0 },
+                "{:?}",
+                action.get_command()
+            );
+            // The validator emits the state changes, the handler adds no event of its own
+            assert!(context.events().is_empty());
+        }
+        assert_eq!(quantity(&context, "Bread"), Some(9));
+    }
+
+    #[test]
+    fn failed_transactions_leave_no_output() {
+        let mut context = InMemoryTransactionContext::new();
+        with_approval_policy(&mut context, 5);
+        apply(
+            &mut context,
+            &payload::produce("Bread", 3, 2, "Bakery"),
+            SIGNER,
+        )
+        .unwrap();
+        context.clear_output();
+        let before = context.entries();
+
+        let failures = vec![
+            (payload::consume("Bread", 4, ""), SIGNER),
+            (payload::consume("Bread", 2, ""), OTHER_SIGNER),
+            (payload::approve("unknown"), OTHER_SIGNER),
+            (payload::reject("unknown"), THIRD_SIGNER),
+            (payload::set_rate_limit(RateLimit::new()), SIGNER),
+            (
+                payload::offer_transfer("transfer", "Bread", 4, OTHER_SIGNER, 0),
+                SIGNER,
+            ),
+            (payload::accept_transfer("unknown"), OTHER_SIGNER),
+            (payload::decline_transfer("unknown"), OTHER_SIGNER),
+            (payload::withdraw_transfer("unknown"), SIGNER),
+        ];
+        for (action, signer) in failures {
+            assert_invalid(apply(&mut context, &action, signer));
+            assert_eq!(context.entries(), before, "{:?}", action.get_command());
+            assert!(context.receipt_data().is_empty());
+            assert!(context.events().is_empty());
+        }
+    }
 }
@@ -0,0 +1,159 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+cfg_if! {
+    if #[cfg(target_arch = "wasm32")] {
+        use sabre_sdk::ApplyError;
+        use sabre_sdk::TpProcessRequest;
+    } else {
+        use sawtooth_sdk::processor::handler::ApplyError;
+        use sawtooth_sdk::messages::processor::TpProcessRequest;
+    }
+}
+
+/// Identity of the signer of the transaction being applied.
+///
+/// The native transaction processor takes it from the transaction header, the Sabre
+/// build from the signer given to the wasm ```entrypoint```. Both fill the header of
+/// their ```TpProcessRequest```, so the identity is always read from the request and
+/// every authorization decision goes through this type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignerIdentity {
+    public_key: String,
+}
+
+impl SignerIdentity {
+    pub fn new(public_key: &str) -> Result<SignerIdentity, ApplyError> {
+        if public_key.is_empty() {
+            return Err(ApplyError::InvalidTransaction(
+                "Transaction has no signer".to_string(),
+            ));
+        }
+        Ok(SignerIdentity {
+            public_key: public_key.to_string(),
+        })
+    }
+
+    pub fn from_request(request: &TpProcessRequest) -> Result<SignerIdentity, ApplyError> {
+        SignerIdentity::new(request.get_header().get_signer_public_key())
+    }
+
+    /// Public key of the signer in hex
+    pub fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    /// Checks that the signer is one of the allowed public keys, the role is used in
+    /// the error message only.
+    pub fn authorize(&self, allowed: &[String], role: &str) -> Result<(), ApplyError> {
+        if !allowed.iter().any(|key| key == &self.public_key) {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "{} is not an authorized {}",
+                self.public_key, role
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks that the signer is the given party of a record, for example the recipient
+    /// of a transfer.
+    pub fn authorize_party(&self, party: &str, role: &str, action: &str) -> Result<(), ApplyError> {
+        if party != self.public_key {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "Only the {} can {}",
+                role, action
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use sawtooth_sdk::messages::transaction::TransactionHeader;
+
+    fn request_signed_by(public_key: &str) -> TpProcessRequest {
+        let mut header = TransactionHeader::new();
+        header.set_signer_public_key(public_key.to_string());
+        let mut request = TpProcessRequest::new();
+        request.set_header(header);
+        request
+    }
+
+    #[test]
+    fn identity_is_read_from_the_transaction_header() {
+        let request = request_signed_by("02abcdef");
+        let identity = SignerIdentity::from_request(&request).unwrap();
+        assert_eq!(identity.public_key(), "02abcdef");
+    }
+
+    #[test]
+    fn request_without_signer_is_invalid() {
+        let request = request_signed_by("");
+        match SignerIdentity::from_request(&request) {
+            Err(ApplyError::InvalidTransaction(_)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn authorize_accepts_listed_keys_only() {
+        let identity = SignerIdentity::new("02abcdef").unwrap();
+        let allowed = vec!["03123456".to_string(), "02abcdef".to_string()];
+        assert!(identity.authorize(&allowed, "approver").is_ok());
+        assert!(identity.authorize(&allowed[..1], "approver").is_err());
+        assert!(identity.authorize(&[], "administrator").is_err());
+    }
+
+    #[test]
+    fn authorize_party_compares_the_public_key() {
+        let identity = SignerIdentity::new("02abcdef").unwrap();
+        assert!(identity
+            .authorize_party("02abcdef", "recipient", "accept the transfer")
+            .is_ok());
+        assert!(identity
+            .authorize_party("03123456", "recipient", "accept the transfer")
+            .is_err());
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use sabre_sdk::Header;
+
+    #[test]
+    fn identity_is_read_from_the_entrypoint_signer() {
+        let mut header = Header::new("02abcdef".to_string());
+        let request = TpProcessRequest::new(vec![], &mut header, "signature".to_string());
+        let identity = SignerIdentity::from_request(&request).unwrap();
+        assert_eq!(identity.public_key(), "02abcdef");
+    }
+
+    #[test]
+    fn entrypoint_without_signer_is_invalid() {
+        let mut header = Header::new(String::new());
+        let request = TpProcessRequest::new(vec![], &mut header, "signature".to_string());
+        assert!(SignerIdentity::from_request(&request).is_err());
+    }
+
+    #[test]
+    fn authorize_accepts_listed_keys_only() {
+        let identity = SignerIdentity::new("02abcdef").unwrap();
+        let allowed = vec!["02abcdef".to_string()];
+        assert!(identity.authorize(&allowed, "approver").is_ok());
+        assert!(identity.authorize(&[], "approver").is_err());
+    }
+}
@@ -0,0 +1,192 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sawtooth_sdk::processor::handler::ContextError;
+use sawtooth_sdk::processor::handler::TransactionContext;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+/// An event added by the handler, its type, attributes and opaque data
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedEvent {
+    pub event_type: String,
+    pub attributes: Vec<(String, String)>,
+    pub data: Vec<u8>,
+}
+
+/// A ```TransactionContext``` backed by memory instead of a validator.
+///
+/// Records the state entries, the events and the receipt data so that the handler can
+/// be applied and checked without a network. Like the validator, a failed transaction
+/// must not leave its changes behind, ```checkpoint``` and ```rollback``` can be used
+/// around the call to ```apply``` for this.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryTransactionContext {
+    state: RefCell<BTreeMap<String, Vec<u8>>>,
+    events: RefCell<Vec<RecordedEvent>>,
+    receipt_data: RefCell<Vec<Vec<u8>>>,
+}
+
+impl InMemoryTransactionContext {
+    pub fn new() -> InMemoryTransactionContext {
+        InMemoryTransactionContext::default()
+    }
+
+    /// Value of the state entry at the address, if any
+    pub fn get(&self, address: &str) -> Option<Vec<u8>> {
+        self.state.borrow().get(address).cloned()
+    }
+
+    /// Sets the state entry directly, for example the settings or the BlockInfo entries
+    /// that other transaction families would write.
+    pub fn insert(&self, address: &str, value: Vec<u8>) {
+        self.state.borrow_mut().insert(address.to_string(), value);
+    }
+
+    /// All the state entries, ordered by address
+    pub fn entries(&self) -> BTreeMap<String, Vec<u8>> {
+        self.state.borrow().clone()
+    }
+
+    pub fn events(&self) -> Vec<RecordedEvent> {
+        self.events.borrow().clone()
+    }
+
+    pub fn receipt_data(&self) -> Vec<Vec<u8>> {
+        self.receipt_data.borrow().clone()
+    }
+
+    /// Forgets the events and receipt data, the state is kept. Call it between
+    /// transactions to look at the output of a single one.
+    pub fn clear_output(&self) {
+        self.events.borrow_mut().clear();
+        self.receipt_data.borrow_mut().clear();
+    }
+
+    /// Copy of the state, events and receipt data to return to with ```rollback```
+    pub fn checkpoint(&self) -> InMemoryTransactionContext {
+        self.clone()
+    }
+
+    pub fn rollback(&mut self, checkpoint: InMemoryTransactionContext) {
+        *self = checkpoint;
+    }
+}
+
+impl TransactionContext for InMemoryTransactionContext {
+    fn get_state_entries(
+        &self,
+        addresses: &[String],
+    ) -> Result<Vec<(String, Vec<u8>)>, ContextError> {
+        let state = self.state.borrow();
+        Ok(addresses
+            .iter()
+            .filter_map(|address| {
+                state
+                    .get(address)
+                    .map(|value| (address.clone(), value.clone()))
+            })
+            .collect())
+    }
+
+    fn set_state_entries(&self, entries: Vec<(String, Vec<u8>)>) -> Result<(), ContextError> {
+        let mut state = self.state.borrow_mut();
+        for (address, value) in entries {
+            state.insert(address, value);
+        }
+        Ok(())
+    }
+
+    fn delete_state_entries(&self, addresses: &[String]) -> Result<Vec<String>, ContextError> {
+        let mut state = self.state.borrow_mut();
+        Ok(addresses
+            .iter()
+            .filter(|address| state.remove(*address).is_some())
+            .cloned()
+            .collect())
+    }
+
+    fn add_receipt_data(&self, data: &[u8]) -> Result<(), ContextError> {
+        self.receipt_data.borrow_mut().push(data.to_vec());
+        Ok(())
+    }
+
+    fn add_event(
+        &self,
+        event_type: String,
+        attributes: Vec<(String, String)>,
+        data: &[u8],
+    ) -> Result<(), ContextError> {
+        self.events.borrow_mut().push(RecordedEvent {
+            event_type,
+            attributes,
+            data: data.to_vec(),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_state_events_and_receipt_data() {
+        let context = InMemoryTransactionContext::new();
+        context
+            .set_state_entries(vec![
+                ("ab".to_string(), vec![1]),
+                ("cd".to_string(), vec![2]),
+            ])
+            .unwrap();
+        context
+            .add_event("produce_consume/test".to_string(), vec![], &[3])
+            .unwrap();
+        context.add_receipt_data(&[4]).unwrap();
+
+        assert_eq!(
+            context
+                .get_state_entries(&["ab".to_string(), "ef".to_string()])
+                .unwrap(),
+            vec![("ab".to_string(), vec![1])]
+        );
+        assert_eq!(
+            context
+                .delete_state_entries(&["cd".to_string(), "ef".to_string()])
+                .unwrap(),
+            vec!["cd".to_string()]
+        );
+        assert_eq!(context.entries().len(), 1);
+        assert_eq!(context.events()[0].event_type, "produce_consume/test");
+        assert_eq!(context.receipt_data(), vec![vec![4]]);
+
+        context.clear_output();
+        assert!(context.events().is_empty());
+        assert!(context.receipt_data().is_empty());
+        assert_eq!(context.get("ab"), Some(vec![1]));
+    }
+
+    #[test]
+    fn rollback_returns_to_the_checkpoint() {
+        let mut context = InMemoryTransactionContext::new();
+        context.insert("ab", vec![1]);
+        let checkpoint = context.checkpoint();
+        context.insert("ab", vec![2]);
+        context.add_receipt_data(&[3]).unwrap();
+
+        context.rollback(checkpoint);
+        assert_eq!(context.get("ab"), Some(vec![1]));
+        assert!(context.receipt_data().is_empty());
+    }
+}
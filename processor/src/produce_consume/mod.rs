@@ -12,8 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub(crate) mod block_info;
 pub mod handler;
-pub(crate) mod payload;
+pub mod identity;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod memory_context;
+pub mod payload;
+pub mod state;
+pub(crate) mod valuation;
 
 cfg_if! {
     if #[cfg(target_arch = "wasm32")] {
@@ -14,6 +14,10 @@
 
 use super::super::proto::action::Action;
 use super::super::proto::action::Action_Command;
+use super::super::proto::state::ApprovalPolicy;
+use super::super::proto::state::RateLimit;
+use super::super::proto::state::ValuationPolicy;
+use produce_consume_core::payload;
 
 cfg_if! {
     if #[cfg(target_arch = "wasm32")] {
@@ -24,23 +28,47 @@ cfg_if! {
 }
 
 #[derive(Debug)]
-pub(crate) struct ProduceConsumePayload {
+pub struct ProduceConsumePayload {
     command: Action_Command,
     identifier: String,
     quantity: i32,
+    valid_after: u64,
+    valid_before: u64,
+    proposal_id: String,
+    approval_policy: ApprovalPolicy,
+    unit_cost: i64,
+    valuation_policy: ValuationPolicy,
+    transfer_id: String,
+    recipient: String,
+    expires_at_block: u64,
+    category: String,
+    rate_limit: RateLimit,
 }
 
 impl ProduceConsumePayload {
-    pub(crate) fn new(raw_bytes: &[u8]) -> Result<ProduceConsumePayload, ApplyError> {
+    pub fn new(raw_bytes: &[u8]) -> Result<ProduceConsumePayload, ApplyError> {
         warn!("Payload in raw is {:?}", &raw_bytes);
-        let parsed_payload: Action = match parse_from(raw_bytes) {
-            Ok(result) => result,
-            Err(e) => return Err(e),
-        };
+        let parsed_payload: Action = payload::decode_action(raw_bytes).map_err(|err| {
+            warn!("Invalid error: Failed to parse the payload: {}", err);
+            ApplyError::InvalidTransaction(format!("Invalid payload: {}", err))
+        })?;
+        payload::validate(&parsed_payload)
+            .map_err(|err| ApplyError::InvalidTransaction(err.to_string()))?;
         Ok(ProduceConsumePayload {
             command: parsed_payload.get_command(),
             identifier: parsed_payload.get_identifier().to_string(),
             quantity: parsed_payload.get_quantity(),
+            valid_after: parsed_payload.get_valid_after(),
+            valid_before: parsed_payload.get_valid_before(),
+            proposal_id: parsed_payload.get_proposal_id().to_string(),
+            approval_policy: parsed_payload.get_approval_policy().clone(),
+            unit_cost: parsed_payload.get_unit_cost(),
+            valuation_policy: parsed_payload.get_valuation_policy(),
+            transfer_id: parsed_payload.get_transfer_id().to_string(),
+            recipient: parsed_payload.get_recipient().to_string(),
+            expires_at_block: parsed_payload.get_expires_at_block(),
+            category: parsed_payload.get_category().to_string(),
+            rate_limit: parsed_payload.get_rate_limit().clone(),
         })
     }
 
@@ -55,14 +83,63 @@ impl ProduceConsumePayload {
     pub(crate) fn get_quantity(&self) -> i32 {
         return self.quantity;
     }
-}
 
-fn parse_from<T>(data: &[u8]) -> Result<T, ApplyError>
-where
-    T: protobuf::Message,
-{
-    protobuf::parse_from_bytes(data).map_err(|err| {
-        warn!("Invalid error: Failed to parse the payload: {:?}", err);
-        ApplyError::InvalidTransaction(format!("Failed to unmarshal payload: {:?}", err))
-    })
+    pub(crate) fn get_valid_after(&self) -> u64 {
+        return self.valid_after;
+    }
+
+    pub(crate) fn get_valid_before(&self) -> u64 {
+        return self.valid_before;
+    }
+
+    pub(crate) fn get_proposal_id(&self) -> String {
+        return self.proposal_id.clone();
+    }
+
+    pub(crate) fn get_approval_policy(&self) -> ApprovalPolicy {
+        return self.approval_policy.clone();
+    }
+
+    pub(crate) fn get_unit_cost(&self) -> i64 {
+        return self.unit_cost;
+    }
+
+    pub(crate) fn get_valuation_policy(&self) -> ValuationPolicy {
+        return self.valuation_policy;
+    }
+
+    pub(crate) fn get_transfer_id(&self) -> String {
+        return self.transfer_id.clone();
+    }
+
+    pub(crate) fn get_recipient(&self) -> String {
+        return self.recipient.clone();
+    }
+
+    pub(crate) fn get_expires_at_block(&self) -> u64 {
+        return self.expires_at_block;
+    }
+
+    pub(crate) fn get_category(&self) -> String {
+        return self.category.clone();
+    }
+
+    pub(crate) fn get_rate_limit(&self) -> RateLimit {
+        return self.rate_limit.clone();
+    }
+
+    /// Returns true for the commands reserved to the administrators
+    pub(crate) fn is_admin_command(&self) -> bool {
+        match self.command {
+            Action_Command::SET_APPROVAL_POLICY
+            | Action_Command::SET_VALUATION_POLICY
+            | Action_Command::SET_RATE_LIMIT => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true if the action carries either of the validity window bounds
+    pub(crate) fn is_time_bound(&self) -> bool {
+        self.valid_after != 0 || self.valid_before != 0
+    }
 }
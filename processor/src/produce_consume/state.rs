@@ -0,0 +1,321 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::proto::setting::Setting;
+use super::super::proto::state::CategoryTotals;
+use super::super::proto::state::Config;
+use super::super::proto::state::CostLayers;
+use super::super::proto::state::Escrow;
+use super::super::proto::state::Holding;
+use super::super::proto::state::Item;
+use super::super::proto::state::ItemTotals;
+use super::super::proto::state::Proposal;
+use super::super::proto::state::SignerUsage;
+use super::block_info;
+use produce_consume_core::addressing::{
+    compute_address, compute_category_totals_address, compute_config_address,
+    compute_cost_layers_address, compute_escrow_address, compute_global_totals_address,
+    compute_holding_address, compute_item_totals_address, compute_proposal_address,
+    compute_setting_address, compute_signer_usage_address, ADMINS_SETTING,
+};
+use produce_consume_core::codec;
+
+cfg_if! {
+    if #[cfg(target_arch = "wasm32")] {
+        use sabre_sdk::ApplyError;
+        use sabre_sdk::TransactionContext;
+    } else {
+        use sawtooth_sdk::processor::handler::ApplyError;
+        use sawtooth_sdk::processor::handler::TransactionContext;
+    }
+}
+
+/// Wraps the ```TransactionContext``` with typed accessors for the entries in the
+/// produce-consume namespace given by its prefix.
+pub(crate) struct ProduceConsumeState<'a> {
+    context: &'a mut dyn TransactionContext,
+    prefix: String,
+}
+
+impl<'a> ProduceConsumeState<'a> {
+    pub(crate) fn new(
+        context: &'a mut dyn TransactionContext,
+        prefix: &str,
+    ) -> ProduceConsumeState<'a> {
+        ProduceConsumeState {
+            context,
+            prefix: prefix.to_string(),
+        }
+    }
+
+    /// Item in the store, with a zero quantity if the item was never produced
+    pub(crate) fn get_item(&mut self, identifier: &str) -> Result<Item, ApplyError> {
+        match self.get_entry(&compute_address(&self.prefix, identifier))? {
+            Some(present) => codec::decode_item(identifier, &present).map_err(|err| {
+                ApplyError::InternalError(format!("{} stored for {}", err, identifier))
+            }),
+            None => {
+                let mut item = Item::new();
+                item.set_identifier(identifier.to_string());
+                Ok(item)
+            }
+        }
+    }
+
+    /// The identifier is written along with the quantity, former entries are rewritten
+    /// this way by their next PRODUCE or CONSUME
+    pub(crate) fn set_item(&mut self, item: &Item) -> Result<(), ApplyError> {
+        self.set_entry(
+            compute_address(&self.prefix, item.get_identifier()),
+            serialize_entry(item)?,
+        )
+    }
+
+    /// The family configuration, defaults if the administrators never set it
+    pub(crate) fn get_config(&mut self) -> Result<Config, ApplyError> {
+        match self.get_entry(&compute_config_address(&self.prefix))? {
+            Some(present) => parse_entry(&present),
+            None => Ok(Config::new()),
+        }
+    }
+
+    pub(crate) fn set_config(&mut self, config: &Config) -> Result<(), ApplyError> {
+        self.set_entry(
+            compute_config_address(&self.prefix),
+            serialize_entry(config)?,
+        )
+    }
+
+    pub(crate) fn get_proposal(
+        &mut self,
+        proposal_id: &str,
+    ) -> Result<Option<Proposal>, ApplyError> {
+        match self.get_entry(&compute_proposal_address(&self.prefix, proposal_id))? {
+            Some(present) => parse_entry(&present).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn set_proposal(&mut self, proposal: &Proposal) -> Result<(), ApplyError> {
+        self.set_entry(
+            compute_proposal_address(&self.prefix, proposal.get_proposal_id()),
+            serialize_entry(proposal)?,
+        )
+    }
+
+    /// Cost layers of the item, without any lot if the item was never produced with a cost
+    pub(crate) fn get_cost_layers(&mut self, identifier: &str) -> Result<CostLayers, ApplyError> {
+        match self.get_entry(&compute_cost_layers_address(&self.prefix, identifier))? {
+            Some(present) => parse_entry(&present),
+            None => {
+                let mut layers = CostLayers::new();
+                layers.set_identifier(identifier.to_string());
+                Ok(layers)
+            }
+        }
+    }
+
+    pub(crate) fn set_cost_layers(&mut self, layers: &CostLayers) -> Result<(), ApplyError> {
+        self.set_entry(
+            compute_cost_layers_address(&self.prefix, layers.get_identifier()),
+            serialize_entry(layers)?,
+        )
+    }
+
+    /// Quantity of the item held by the owner, zero if the owner never held it
+    pub(crate) fn get_holding(&mut self, owner: &str, identifier: &str) -> Result<i32, ApplyError> {
+        match self.get_entry(&compute_holding_address(&self.prefix, owner, identifier))? {
+            Some(present) => parse_entry::<Holding>(&present).map(|holding| holding.get_quantity()),
+            None => Ok(0),
+        }
+    }
+
+    pub(crate) fn set_holding(
+        &mut self,
+        owner: &str,
+        identifier: &str,
+        quantity: i32,
+    ) -> Result<(), ApplyError> {
+        let mut holding = Holding::new();
+        holding.set_owner(owner.to_string());
+        holding.set_identifier(identifier.to_string());
+        holding.set_quantity(quantity);
+        self.set_entry(
+            compute_holding_address(&self.prefix, owner, identifier),
+            serialize_entry(&holding)?,
+        )
+    }
+
+    pub(crate) fn get_escrow(&mut self, transfer_id: &str) -> Result<Option<Escrow>, ApplyError> {
+        match self.get_entry(&compute_escrow_address(&self.prefix, transfer_id))? {
+            Some(present) => parse_entry(&present).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn set_escrow(&mut self, escrow: &Escrow) -> Result<(), ApplyError> {
+        self.set_entry(
+            compute_escrow_address(&self.prefix, escrow.get_transfer_id()),
+            serialize_entry(escrow)?,
+        )
+    }
+
+    pub(crate) fn get_item_totals(&mut self, identifier: &str) -> Result<ItemTotals, ApplyError> {
+        match self.get_entry(&compute_item_totals_address(&self.prefix, identifier))? {
+            Some(present) => parse_entry(&present),
+            None => {
+                let mut totals = ItemTotals::new();
+                totals.set_identifier(identifier.to_string());
+                Ok(totals)
+            }
+        }
+    }
+
+    pub(crate) fn set_item_totals(&mut self, totals: &ItemTotals) -> Result<(), ApplyError> {
+        self.set_entry(
+            compute_item_totals_address(&self.prefix, totals.get_identifier()),
+            serialize_entry(totals)?,
+        )
+    }
+
+    pub(crate) fn get_category_totals(
+        &mut self,
+        category: &str,
+    ) -> Result<CategoryTotals, ApplyError> {
+        match self.get_entry(&compute_category_totals_address(&self.prefix, category))? {
+            Some(present) => parse_entry(&present),
+            None => {
+                let mut totals = CategoryTotals::new();
+                totals.set_category(category.to_string());
+                Ok(totals)
+            }
+        }
+    }
+
+    pub(crate) fn set_category_totals(
+        &mut self,
+        totals: &CategoryTotals,
+    ) -> Result<(), ApplyError> {
+        self.set_entry(
+            compute_category_totals_address(&self.prefix, totals.get_category()),
+            serialize_entry(totals)?,
+        )
+    }
+
+    pub(crate) fn get_global_totals(&mut self) -> Result<CategoryTotals, ApplyError> {
+        match self.get_entry(&compute_global_totals_address(&self.prefix))? {
+            Some(present) => parse_entry(&present),
+            None => Ok(CategoryTotals::new()),
+        }
+    }
+
+    pub(crate) fn set_global_totals(&mut self, totals: &CategoryTotals) -> Result<(), ApplyError> {
+        self.set_entry(
+            compute_global_totals_address(&self.prefix),
+            serialize_entry(totals)?,
+        )
+    }
+
+    pub(crate) fn get_signer_usage(&mut self, signer: &str) -> Result<SignerUsage, ApplyError> {
+        match self.get_entry(&compute_signer_usage_address(&self.prefix, signer))? {
+            Some(present) => parse_entry(&present),
+            None => {
+                let mut usage = SignerUsage::new();
+                usage.set_signer(signer.to_string());
+                Ok(usage)
+            }
+        }
+    }
+
+    pub(crate) fn set_signer_usage(&mut self, usage: &SignerUsage) -> Result<(), ApplyError> {
+        self.set_entry(
+            compute_signer_usage_address(&self.prefix, usage.get_signer()),
+            serialize_entry(usage)?,
+        )
+    }
+
+    /// Number of the latest block, read from the BlockInfo namespace
+    pub(crate) fn get_current_block_num(&mut self) -> Result<u64, ApplyError> {
+        block_info::get_current_block_num(self.context)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn add_receipt_data<T>(&mut self, message: &T) -> Result<(), ApplyError>
+    where
+        T: protobuf::Message,
+    {
+        self.context.add_receipt_data(&serialize_entry(message)?)?;
+        Ok(())
+    }
+
+    /// Sabre does not carry receipt data back to the validator, the contract leaves the
+    /// receipt out rather than failing the transaction
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn add_receipt_data<T>(&mut self, _message: &T) -> Result<(), ApplyError>
+    where
+        T: protobuf::Message,
+    {
+        Ok(())
+    }
+
+    /// Public keys listed in the ```produce_consume.admins``` setting
+    pub(crate) fn get_admins(&mut self) -> Result<Vec<String>, ApplyError> {
+        let raw_value = match self.get_entry(&compute_setting_address(ADMINS_SETTING))? {
+            Some(present) => present,
+            None => return Ok(vec![]),
+        };
+        let setting: Setting = parse_entry(&raw_value)?;
+        let admins = setting
+            .get_entries()
+            .iter()
+            .find(|entry| entry.get_key() == ADMINS_SETTING)
+            .map(|entry| {
+                entry
+                    .get_value()
+                    .split(',')
+                    .map(|key| key.trim().to_string())
+                    .filter(|key| !key.is_empty())
+                    .collect()
+            })
+            .unwrap_or_else(Vec::new);
+        Ok(admins)
+    }
+
+    fn get_entry(&mut self, address: &str) -> Result<Option<Vec<u8>>, ApplyError> {
+        self.context
+            .get_state_entry(address)
+            .map_err(|err| ApplyError::InternalError(err.to_string()))
+    }
+
+    fn set_entry(&mut self, address: String, value: Vec<u8>) -> Result<(), ApplyError> {
+        self.context.set_state_entries(vec![(address, value)])?;
+        Ok(())
+    }
+}
+
+fn parse_entry<T>(data: &[u8]) -> Result<T, ApplyError>
+where
+    T: protobuf::Message,
+{
+    codec::decode(data)
+        .map_err(|err| ApplyError::InternalError(format!("Invalid state entry: {}", err)))
+}
+
+fn serialize_entry<T>(message: &T) -> Result<Vec<u8>, ApplyError>
+where
+    T: protobuf::Message,
+{
+    codec::encode(message)
+        .map_err(|err| ApplyError::InternalError(format!("Invalid state entry: {}", err)))
+}
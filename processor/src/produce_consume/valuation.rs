@@ -0,0 +1,215 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::proto::state::CostLayers;
+use super::super::proto::state::Lot;
+use super::super::proto::state::ValuationPolicy;
+use protobuf::RepeatedField;
+
+cfg_if! {
+    if #[cfg(target_arch = "wasm32")] {
+        use sabre_sdk::ApplyError;
+    } else {
+        use sawtooth_sdk::processor::handler::ApplyError;
+    }
+}
+
+/// Appends a newly produced lot to the cost layers
+pub(crate) fn add_lot(
+    layers: &mut CostLayers,
+    quantity: i32,
+    unit_cost: i64,
+) -> Result<(), ApplyError> {
+    let value = (quantity as i64)
+        .checked_mul(unit_cost)
+        .ok_or_else(invalid_value)?;
+    let mut lot = Lot::new();
+    lot.set_quantity(quantity);
+    lot.set_value(value);
+    layers.mut_lots().push(lot);
+    Ok(())
+}
+
+/// Removes the consumed quantity from the cost layers and returns the cost of the goods
+/// consumed under the given policy.
+///
+/// ```on_hand``` is the quantity of the item before the consumption. Quantity produced
+/// before cost layers were tracked has no lot, it is treated as the oldest lot at zero cost.
+pub(crate) fn consume(
+    layers: &mut CostLayers,
+    on_hand: i32,
+    quantity: i32,
+    policy: ValuationPolicy,
+) -> Result<i64, ApplyError> {
+    let layered: i64 = layers
+        .get_lots()
+        .iter()
+        .map(|lot| lot.get_quantity() as i64)
+        .sum();
+    if (on_hand as i64) > layered {
+        let mut uncosted = Lot::new();
+        uncosted.set_quantity((on_hand as i64 - layered) as i32);
+        layers.mut_lots().insert(0, uncosted);
+    }
+
+    match policy {
+        ValuationPolicy::FIFO => consume_fifo(layers, quantity),
+        ValuationPolicy::WEIGHTED_AVERAGE => consume_weighted_average(layers, quantity),
+    }
+}
+
+/// Consumes the oldest lots first, a partially consumed lot is charged pro rata
+fn consume_fifo(layers: &mut CostLayers, quantity: i32) -> Result<i64, ApplyError> {
+    let mut remaining = quantity;
+    let mut cost: i64 = 0;
+    let mut lots: Vec<Lot> = Vec::new();
+    for lot in layers.get_lots() {
+        if remaining == 0 {
+            lots.push(lot.clone());
+            continue;
+        }
+        if lot.get_quantity() <= remaining {
+            remaining -= lot.get_quantity();
+            cost = cost
+                .checked_add(lot.get_value())
+                .ok_or_else(invalid_value)?;
+            continue;
+        }
+        let taken = prorate(lot.get_value(), remaining, lot.get_quantity());
+        let mut left = lot.clone();
+        left.set_quantity(lot.get_quantity() - remaining);
+        left.set_value(lot.get_value() - taken);
+        lots.push(left);
+        cost = cost.checked_add(taken).ok_or_else(invalid_value)?;
+        remaining = 0;
+    }
+    if remaining != 0 {
+        return Err(ApplyError::InvalidTransaction(
+            "Invalid resultant quantity".to_string(),
+        ));
+    }
+    layers.set_lots(RepeatedField::from_vec(lots));
+    Ok(cost)
+}
+
+/// Consumes at the average unit cost, the remaining quantity is merged into a single lot
+fn consume_weighted_average(layers: &mut CostLayers, quantity: i32) -> Result<i64, ApplyError> {
+    let total_quantity: i64 = layers
+        .get_lots()
+        .iter()
+        .map(|lot| lot.get_quantity() as i64)
+        .sum();
+    let total_value = layers
+        .get_lots()
+        .iter()
+        .try_fold(0i64, |total, lot| total.checked_add(lot.get_value()))
+        .ok_or_else(invalid_value)?;
+    if (quantity as i64) > total_quantity {
+        return Err(ApplyError::InvalidTransaction(
+            "Invalid resultant quantity".to_string(),
+        ));
+    }
+    let cost = prorate(total_value, quantity, total_quantity as i32);
+
+    let mut lots = Vec::new();
+    if (quantity as i64) < total_quantity {
+        let mut merged = Lot::new();
+        merged.set_quantity((total_quantity - quantity as i64) as i32);
+        merged.set_value(total_value - cost);
+        lots.push(merged);
+    }
+    layers.set_lots(RepeatedField::from_vec(lots));
+    Ok(cost)
+}
+
+/// Values are summed in i64, a sum past its range is rejected rather than wrapped
+fn invalid_value() -> ApplyError {
+    ApplyError::InvalidTransaction("Invalid resultant lot value".to_string())
+}
+
+/// Share of the value for the part of the quantity, rounded to the nearest unit
+fn prorate(value: i64, part: i32, whole: i32) -> i64 {
+    if whole == 0 {
+        return 0;
+    }
+    let numerator = value as i128 * part as i128;
+    let whole = whole as i128;
+    ((numerator + whole / 2) / whole) as i64
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    fn layers(lots: &[(i32, i64)]) -> CostLayers {
+        let mut layers = CostLayers::new();
+        for (quantity, value) in lots {
+            let mut lot = Lot::new();
+            lot.set_quantity(*quantity);
+            lot.set_value(*value);
+            layers.mut_lots().push(lot);
+        }
+        layers
+    }
+
+    fn is_invalid(result: Result<i64, ApplyError>) -> bool {
+        match result {
+            Err(ApplyError::InvalidTransaction(_)) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn lot_value_past_the_range_is_rejected() {
+        let mut layers = CostLayers::new();
+        assert!(add_lot(&mut layers, 2, i64::MAX / 2 + 1).is_err());
+        assert!(layers.get_lots().is_empty());
+        add_lot(&mut layers, 1, i64::MAX).unwrap();
+        assert_eq!(layers.get_lots()[0].get_value(), i64::MAX);
+    }
+
+    #[test]
+    fn fifo_cost_past_the_range_is_rejected() {
+        let mut lots = layers(&[(1, i64::MAX - 1), (1, 2)]);
+        assert!(is_invalid(consume(&mut lots, 2, 2, ValuationPolicy::FIFO)));
+
+        let mut lots = layers(&[(1, i64::MAX - 1), (2, 4)]);
+        assert!(is_invalid(consume(&mut lots, 3, 2, ValuationPolicy::FIFO)));
+
+        let mut lots = layers(&[(1, i64::MAX - 2), (1, 2)]);
+        assert_eq!(
+            consume(&mut lots, 2, 2, ValuationPolicy::FIFO).unwrap(),
+            i64::MAX
+        );
+    }
+
+    #[test]
+    fn weighted_average_value_past_the_range_is_rejected() {
+        let mut lots = layers(&[(1, i64::MAX), (1, 1)]);
+        assert!(is_invalid(consume(
+            &mut lots,
+            2,
+            1,
+            ValuationPolicy::WEIGHTED_AVERAGE
+        )));
+        assert_eq!(lots.get_lots().len(), 2);
+
+        let mut lots = layers(&[(1, i64::MAX - 1), (1, 1)]);
+        assert_eq!(
+            consume(&mut lots, 2, 1, ValuationPolicy::WEIGHTED_AVERAGE).unwrap(),
+            i64::MAX / 2 + 1
+        );
+        assert_eq!(lots.get_lots()[0].get_value(), i64::MAX / 2);
+    }
+}
@@ -0,0 +1,31 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+/// Stops the transaction processor gracefully on SIGTERM, sent by docker to stop a
+/// container, as well as SIGINT.
+///
+/// The signals only set the returned flag. The supervisor checks it between two
+/// requests: it finishes the request being applied, unregisters from the validator and
+/// returns.
+pub(crate) fn install() -> Result<Arc<AtomicBool>, String> {
+    let requested = Arc::new(AtomicBool::new(false));
+    for signal in &[signal_hook::SIGTERM, signal_hook::SIGINT] {
+        signal_hook::flag::register(*signal, requested.clone())
+            .map_err(|err| format!("Unable to handle the signal {}: {}", signal, err))?;
+    }
+    Ok(requested)
+}
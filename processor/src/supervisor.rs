@@ -0,0 +1,342 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::health::Health;
+use crate::zmq_context::ZmqTransactionContext;
+use protobuf::{Message as ProtobufMessage, RepeatedField};
+use sawtooth_sdk::messages::network::PingResponse;
+use sawtooth_sdk::messages::processor::{
+    TpProcessRequest, TpProcessResponse, TpProcessResponse_Status, TpRegisterRequest,
+    TpRegisterResponse, TpRegisterResponse_Status, TpUnregisterRequest, TpUnregisterResponse,
+};
+use sawtooth_sdk::messages::validator::{Message, Message_MessageType};
+use sawtooth_sdk::messaging::stream::{MessageConnection, MessageReceiver, MessageSender};
+use sawtooth_sdk::messaging::zmq_stream::{ZmqMessageConnection, ZmqMessageSender};
+use sawtooth_sdk::processor::handler::{ApplyError, TransactionHandler};
+use std::cmp;
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time to wait for the answer to a registration or an unregistration
+const REGISTER_TIMEOUT: Duration = Duration::from_secs(5);
+/// The shutdown flag is checked at least this often
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+static NEXT_CORRELATION_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Correlation id of a message sent to the validator, unique within the process
+pub(crate) fn correlation_id() -> String {
+    format!(
+        "{}-{}",
+        process::id(),
+        NEXT_CORRELATION_ID.fetch_add(1, Ordering::SeqCst)
+    )
+}
+
+/// Delays between the connection attempts, doubled after every failed attempt up to the
+/// maximum and back to the minimum once the handler is registered.
+#[derive(Debug)]
+pub(crate) struct Backoff {
+    min: Duration,
+    max: Duration,
+    next: Duration,
+}
+
+impl Backoff {
+    pub(crate) fn new(min: Duration, max: Duration) -> Result<Backoff, String> {
+        if min == Duration::from_secs(0) || max < min {
+            return Err(format!(
+                "Invalid reconnect delays, the minimum {:?} must be positive and not above \
+                 the maximum {:?}",
+                min, max
+            ));
+        }
+        Ok(Backoff {
+            min,
+            max,
+            next: min,
+        })
+    }
+
+    /// Delay before the next attempt
+    pub(crate) fn next_delay(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = cmp::min(self.next * 2, self.max);
+        delay
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.next = self.min;
+    }
+}
+
+/// How a registered session with the validator ended
+enum SessionEnd {
+    Shutdown,
+    Lost(String),
+}
+
+/// Keeps the handler registered with the validator.
+///
+/// Every connection attempt registers the handler, the requests are then applied until
+/// the connection is lost, after which the supervisor waits for the backoff delay and
+/// connects again. A shutdown request unregisters the handler after the request being
+/// applied and returns.
+///
+/// This is the loop of ```TransactionProcessor::start``` with a delay between the
+/// attempts. ```start``` cannot be wrapped instead: it only returns on Ctrl-C, it
+/// reconnects on its own without any delay and without checking the registration
+/// response, and every call installs a Ctrl-C handler, which panics the second time.
+/// Its transaction context is private to the SDK, hence ```ZmqTransactionContext```.
+pub(crate) struct Supervisor<'a> {
+    endpoint: String,
+    handler: &'a dyn TransactionHandler,
+    shutdown: Arc<AtomicBool>,
+    health: Arc<Health>,
+    backoff: Backoff,
+}
+
+impl<'a> Supervisor<'a> {
+    pub(crate) fn new(
+        endpoint: &str,
+        handler: &'a dyn TransactionHandler,
+        shutdown: Arc<AtomicBool>,
+        health: Arc<Health>,
+        backoff: Backoff,
+    ) -> Supervisor<'a> {
+        Supervisor {
+            endpoint: endpoint.to_string(),
+            handler,
+            shutdown,
+            health,
+            backoff,
+        }
+    }
+
+    /// Runs until a shutdown is requested
+    pub(crate) fn run(&mut self) {
+        let mut attempt: u32 = 0;
+        while !self.shutdown_requested() {
+            attempt += 1;
+            info!(
+                "Connecting to the validator {}, attempt {}",
+                self.endpoint, attempt
+            );
+            let connection = ZmqMessageConnection::new(&self.endpoint);
+            let (mut sender, receiver) = connection.create();
+            match self.register(&mut sender) {
+                Ok(()) => {
+                    info!(
+                        "Registered {} {:?} with the validator {}",
+                        self.handler.family_name(),
+                        self.handler.family_versions(),
+                        self.endpoint
+                    );
+                    attempt = 0;
+                    self.backoff.reset();
+                    self.health.set_ready(true);
+                    let end = self.serve(&mut sender, &receiver);
+                    self.health.set_ready(false);
+                    sender.close();
+                    match end {
+                        SessionEnd::Shutdown => return,
+                        SessionEnd::Lost(reason) => {
+                            warn!("Lost the validator {}: {}", self.endpoint, reason)
+                        }
+                    }
+                }
+                Err(err) => {
+                    sender.close();
+                    warn!("Unable to register with the validator: {}", err);
+                }
+            }
+            let delay = self.backoff.next_delay();
+            info!("Reconnecting in {:?}", delay);
+            self.wait(delay);
+        }
+    }
+
+    fn shutdown_requested(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    /// Sleeps for the delay, returns earlier if a shutdown is requested
+    fn wait(&self, delay: Duration) {
+        let started = Instant::now();
+        while !self.shutdown_requested() {
+            let elapsed = started.elapsed();
+            if elapsed >= delay {
+                break;
+            }
+            thread::sleep(cmp::min(POLL_INTERVAL, delay - elapsed));
+        }
+    }
+
+    fn register(&self, sender: &mut ZmqMessageSender) -> Result<(), String> {
+        for version in self.handler.family_versions() {
+            let mut request = TpRegisterRequest::new();
+            request.set_family(self.handler.family_name());
+            request.set_version(version.clone());
+            request.set_namespaces(RepeatedField::from_vec(self.handler.namespaces()));
+            let response: TpRegisterResponse =
+                send_and_wait(sender, Message_MessageType::TP_REGISTER_REQUEST, &request)?;
+            if response.get_status() != TpRegisterResponse_Status::OK {
+                return Err(format!(
+                    "The validator refused the version {}: {:?}",
+                    version,
+                    response.get_status()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn serve(&self, sender: &mut ZmqMessageSender, receiver: &MessageReceiver) -> SessionEnd {
+        loop {
+            // Checked between two requests, the request being applied is always answered
+            if self.shutdown_requested() {
+                self.unregister(sender);
+                return SessionEnd::Shutdown;
+            }
+            match receiver.recv_timeout(POLL_INTERVAL) {
+                Ok(Ok(message)) => self.handle(sender, &message),
+                Ok(Err(err)) => return SessionEnd::Lost(format!("{:?}", err)),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return SessionEnd::Lost("the connection was closed".to_string())
+                }
+                Err(RecvTimeoutError::Timeout) => (),
+            }
+        }
+    }
+
+    fn handle(&self, sender: &mut ZmqMessageSender, message: &Message) {
+        let (response_type, content) = match message.get_message_type() {
+            Message_MessageType::TP_PROCESS_REQUEST => (
+                Message_MessageType::TP_PROCESS_RESPONSE,
+                self.process(sender, message.get_content()).write_to_bytes(),
+            ),
+            Message_MessageType::PING_REQUEST => (
+                Message_MessageType::PING_RESPONSE,
+                PingResponse::new().write_to_bytes(),
+            ),
+            other => {
+                warn!("Ignoring the unexpected message {:?}", other);
+                return;
+            }
+        };
+        let sent = content
+            .map_err(|err| format!("{:?}", err))
+            .and_then(|content| {
+                sender
+                    .reply(response_type, message.get_correlation_id(), &content)
+                    .map_err(|err| format!("{:?}", err))
+            });
+        if let Err(err) = sent {
+            warn!("Unable to answer {:?}: {}", response_type, err);
+        }
+    }
+
+    fn process(&self, sender: &ZmqMessageSender, content: &[u8]) -> TpProcessResponse {
+        let mut response = TpProcessResponse::new();
+        let request: TpProcessRequest = match protobuf::parse_from_bytes(content) {
+            Ok(request) => request,
+            Err(err) => {
+                error!("Unable to parse the process request: {:?}", err);
+                response.set_status(TpProcessResponse_Status::INTERNAL_ERROR);
+                response.set_message(format!("Unable to parse the process request: {:?}", err));
+                return response;
+            }
+        };
+        let mut context = ZmqTransactionContext::new(request.get_context_id(), sender.clone());
+        match self.handler.apply(&request, &mut context) {
+            Ok(()) => response.set_status(TpProcessResponse_Status::OK),
+            Err(ApplyError::InvalidTransaction(message)) => {
+                info!("Invalid transaction: {}", message);
+                response.set_status(TpProcessResponse_Status::INVALID_TRANSACTION);
+                response.set_message(message);
+            }
+            Err(ApplyError::InternalError(message)) => {
+                error!("Internal error: {}", message);
+                response.set_status(TpProcessResponse_Status::INTERNAL_ERROR);
+                response.set_message(message);
+            }
+        }
+        response
+    }
+
+    fn unregister(&self, sender: &mut ZmqMessageSender) {
+        let result: Result<TpUnregisterResponse, String> = send_and_wait(
+            sender,
+            Message_MessageType::TP_UNREGISTER_REQUEST,
+            &TpUnregisterRequest::new(),
+        );
+        match result {
+            Ok(_) => info!("Unregistered from the validator {}", self.endpoint),
+            Err(err) => warn!("Unable to unregister from the validator: {}", err),
+        }
+    }
+}
+
+/// Sends the request and waits for the response for at most ```REGISTER_TIMEOUT```
+fn send_and_wait<Req, Resp>(
+    sender: &mut ZmqMessageSender,
+    message_type: Message_MessageType,
+    request: &Req,
+) -> Result<Resp, String>
+where
+    Req: ProtobufMessage,
+    Resp: ProtobufMessage,
+{
+    let content = request
+        .write_to_bytes()
+        .map_err(|err| format!("Unable to serialize {:?}: {:?}", message_type, err))?;
+    let response = sender
+        .send(message_type, &correlation_id(), &content)
+        .map_err(|err| format!("Unable to send {:?}: {:?}", message_type, err))?
+        .get_timeout(REGISTER_TIMEOUT)
+        .map_err(|err| format!("No answer to {:?}: {:?}", message_type, err))?;
+    protobuf::parse_from_bytes(response.get_content())
+        .map_err(|err| format!("Invalid answer to {:?}: {:?}", message_type, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(5)).unwrap();
+        let delays: Vec<u64> = (0..5).map(|_| backoff.next_delay().as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 5, 5]);
+    }
+
+    #[test]
+    fn backoff_starts_over_after_a_reset() {
+        let mut backoff = Backoff::new(Duration::from_secs(2), Duration::from_secs(60)).unwrap();
+        backoff.next_delay();
+        backoff.next_delay();
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn backoff_rejects_invalid_delays() {
+        assert!(Backoff::new(Duration::from_secs(0), Duration::from_secs(5)).is_err());
+        assert!(Backoff::new(Duration::from_secs(10), Duration::from_secs(5)).is_err());
+    }
+}
@@ -0,0 +1,205 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::supervisor;
+use protobuf::{Message, RepeatedField};
+use sawtooth_sdk::messages::events::{Event, Event_Attribute};
+use sawtooth_sdk::messages::state_context::{
+    TpEventAddRequest, TpEventAddResponse, TpEventAddResponse_Status, TpReceiptAddDataRequest,
+    TpReceiptAddDataResponse, TpReceiptAddDataResponse_Status, TpStateDeleteRequest,
+    TpStateDeleteResponse, TpStateDeleteResponse_Status, TpStateEntry, TpStateGetRequest,
+    TpStateGetResponse, TpStateGetResponse_Status, TpStateSetRequest, TpStateSetResponse,
+    TpStateSetResponse_Status,
+};
+use sawtooth_sdk::messages::validator::Message_MessageType;
+use sawtooth_sdk::messaging::stream::MessageSender;
+use sawtooth_sdk::messaging::zmq_stream::ZmqMessageSender;
+use sawtooth_sdk::processor::handler::{ContextError, TransactionContext};
+use std::cell::RefCell;
+use std::time::Duration;
+
+/// A request still unanswered after this long fails the transaction with an internal
+/// error, the validator retries it and the supervisor finds out whether it is still there
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The state of a single ```TpProcessRequest```, read and written through the connection
+/// to the validator the request came from.
+///
+/// The SDK keeps its own context private to ```TransactionProcessor```, the supervisor
+/// needs the same requests over the connection it manages.
+pub(crate) struct ZmqTransactionContext {
+    context_id: String,
+    sender: RefCell<ZmqMessageSender>,
+}
+
+impl ZmqTransactionContext {
+    pub(crate) fn new(context_id: &str, sender: ZmqMessageSender) -> ZmqTransactionContext {
+        ZmqTransactionContext {
+            context_id: context_id.to_string(),
+            sender: RefCell::new(sender),
+        }
+    }
+
+    /// Sends the request and waits for the response of the validator
+    fn request<Req, Resp>(
+        &self,
+        message_type: Message_MessageType,
+        request: &Req,
+    ) -> Result<Resp, ContextError>
+    where
+        Req: Message,
+        Resp: Message,
+    {
+        let content = request
+            .write_to_bytes()
+            .map_err(|err| ContextError::SerializationError(Box::new(err)))?;
+        let correlation_id = supervisor::correlation_id();
+        let mut future = self
+            .sender
+            .borrow_mut()
+            .send(message_type, &correlation_id, &content)
+            .map_err(|err| ContextError::SendError(Box::new(err)))?;
+        let response = future
+            .get_timeout(RESPONSE_TIMEOUT)
+            .map_err(|err| ContextError::ReceiveError(Box::new(err)))?;
+        protobuf::parse_from_bytes(response.get_content())
+            .map_err(|err| ContextError::SerializationError(Box::new(err)))
+    }
+}
+
+impl TransactionContext for ZmqTransactionContext {
+    fn get_state_entries(
+        &self,
+        addresses: &[String],
+    ) -> Result<Vec<(String, Vec<u8>)>, ContextError> {
+        let mut request = TpStateGetRequest::new();
+        request.set_context_id(self.context_id.clone());
+        request.set_addresses(RepeatedField::from_vec(addresses.to_vec()));
+        let mut response: TpStateGetResponse =
+            self.request(Message_MessageType::TP_STATE_GET_REQUEST, &request)?;
+        match response.get_status() {
+            TpStateGetResponse_Status::OK => Ok(response
+                .take_entries()
+                .into_iter()
+                .map(|mut entry| (entry.take_address(), entry.take_data()))
+                .collect()),
+            TpStateGetResponse_Status::AUTHORIZATION_ERROR => {
+                Err(ContextError::AuthorizationError(format!(
+                    "Tried to get unauthorized addresses {:?}",
+                    addresses
+                )))
+            }
+            status => Err(ContextError::ResponseAttributeError(format!(
+                "Unexpected state get status {:?}",
+                status
+            ))),
+        }
+    }
+
+    fn set_state_entries(&self, entries: Vec<(String, Vec<u8>)>) -> Result<(), ContextError> {
+        let state_entries = entries
+            .into_iter()
+            .map(|(address, data)| {
+                let mut entry = TpStateEntry::new();
+                entry.set_address(address);
+                entry.set_data(data);
+                entry
+            })
+            .collect();
+        let mut request = TpStateSetRequest::new();
+        request.set_context_id(self.context_id.clone());
+        request.set_entries(RepeatedField::from_vec(state_entries));
+        let response: TpStateSetResponse =
+            self.request(Message_MessageType::TP_STATE_SET_REQUEST, &request)?;
+        match response.get_status() {
+            TpStateSetResponse_Status::OK => Ok(()),
+            TpStateSetResponse_Status::AUTHORIZATION_ERROR => Err(
+                ContextError::AuthorizationError("Tried to set unauthorized addresses".to_string()),
+            ),
+            status => Err(ContextError::ResponseAttributeError(format!(
+                "Unexpected state set status {:?}",
+                status
+            ))),
+        }
+    }
+
+    fn delete_state_entries(&self, addresses: &[String]) -> Result<Vec<String>, ContextError> {
+        let mut request = TpStateDeleteRequest::new();
+        request.set_context_id(self.context_id.clone());
+        request.set_addresses(RepeatedField::from_vec(addresses.to_vec()));
+        let mut response: TpStateDeleteResponse =
+            self.request(Message_MessageType::TP_STATE_DELETE_REQUEST, &request)?;
+        match response.get_status() {
+            TpStateDeleteResponse_Status::OK => Ok(response.take_addresses().into_vec()),
+            TpStateDeleteResponse_Status::AUTHORIZATION_ERROR => {
+                Err(ContextError::AuthorizationError(format!(
+                    "Tried to delete unauthorized addresses {:?}",
+                    addresses
+                )))
+            }
+            status => Err(ContextError::ResponseAttributeError(format!(
+                "Unexpected state delete status {:?}",
+                status
+            ))),
+        }
+    }
+
+    fn add_receipt_data(&self, data: &[u8]) -> Result<(), ContextError> {
+        let mut request = TpReceiptAddDataRequest::new();
+        request.set_context_id(self.context_id.clone());
+        request.set_data(data.to_vec());
+        let response: TpReceiptAddDataResponse =
+            self.request(Message_MessageType::TP_RECEIPT_ADD_DATA_REQUEST, &request)?;
+        match response.get_status() {
+            TpReceiptAddDataResponse_Status::OK => Ok(()),
+            status => Err(ContextError::TransactionReceiptError(format!(
+                "Unable to add the receipt data, status {:?}",
+                status
+            ))),
+        }
+    }
+
+    fn add_event(
+        &self,
+        event_type: String,
+        attributes: Vec<(String, String)>,
+        data: &[u8],
+    ) -> Result<(), ContextError> {
+        let attributes = attributes
+            .into_iter()
+            .map(|(key, value)| {
+                let mut attribute = Event_Attribute::new();
+                attribute.set_key(key);
+                attribute.set_value(value);
+                attribute
+            })
+            .collect();
+        let mut event = Event::new();
+        event.set_event_type(event_type);
+        event.set_attributes(RepeatedField::from_vec(attributes));
+        event.set_data(data.to_vec());
+        let mut request = TpEventAddRequest::new();
+        request.set_context_id(self.context_id.clone());
+        request.set_event(event);
+        let response: TpEventAddResponse =
+            self.request(Message_MessageType::TP_EVENT_ADD_REQUEST, &request)?;
+        match response.get_status() {
+            TpEventAddResponse_Status::OK => Ok(()),
+            status => Err(ContextError::ResponseAttributeError(format!(
+                "Unable to add the event, status {:?}",
+                status
+            ))),
+        }
+    }
+}
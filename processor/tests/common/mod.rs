@@ -0,0 +1,248 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A stand-in validator, a ZMQ router socket speaking the processor protocol, and helpers
+//! to run the ```produce-consume``` binary against it.
+
+// Every test uses a different part of the helpers
+#![allow(dead_code)]
+
+use produce_consume::proto::action::{Action, Action_Command};
+use protobuf::Message as ProtobufMessage;
+use sawtooth_sdk::messages::processor::{
+    TpProcessRequest, TpProcessResponse, TpRegisterResponse, TpRegisterResponse_Status,
+    TpUnregisterResponse, TpUnregisterResponse_Status,
+};
+use sawtooth_sdk::messages::state_context::{
+    TpStateEntry, TpStateGetRequest, TpStateGetResponse, TpStateGetResponse_Status,
+    TpStateSetRequest, TpStateSetResponse, TpStateSetResponse_Status,
+};
+use sawtooth_sdk::messages::transaction::TransactionHeader;
+use sawtooth_sdk::messages::validator::{Message, Message_MessageType};
+use std::collections::HashMap;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Long enough for the processor to connect again after its backoff delay
+const TIMEOUT_MS: i32 = 60_000;
+
+pub struct StandInValidator {
+    socket: zmq::Socket,
+    processor: Option<Vec<u8>>,
+    pub state: HashMap<String, Vec<u8>>,
+}
+
+impl StandInValidator {
+    pub fn bind() -> (StandInValidator, String) {
+        StandInValidator::bind_to("tcp://127.0.0.1:*")
+    }
+
+    /// Binds the given endpoint, for example again after a restart of the validator
+    pub fn bind_to(endpoint: &str) -> (StandInValidator, String) {
+        let context = zmq::Context::new();
+        let socket = context.socket(zmq::ROUTER).unwrap();
+        socket.set_rcvtimeo(TIMEOUT_MS).unwrap();
+        socket.set_linger(0).unwrap();
+        socket.bind(endpoint).unwrap();
+        let endpoint = socket.get_last_endpoint().unwrap().unwrap();
+        let validator = StandInValidator {
+            socket,
+            processor: None,
+            state: HashMap::new(),
+        };
+        (validator, endpoint)
+    }
+
+    /// Next message from the processor, panics on timeout
+    pub fn receive(&mut self) -> Message {
+        let mut parts = self
+            .socket
+            .recv_multipart(0)
+            .expect("No message from the processor in time");
+        let content = parts.pop().unwrap();
+        self.processor = Some(parts.remove(0));
+        protobuf::parse_from_bytes(&content).unwrap()
+    }
+
+    pub fn send<T: ProtobufMessage>(
+        &self,
+        message_type: Message_MessageType,
+        correlation_id: &str,
+        content: &T,
+    ) {
+        let mut message = Message::new();
+        message.set_message_type(message_type);
+        message.set_correlation_id(correlation_id.to_string());
+        message.set_content(content.write_to_bytes().unwrap());
+        let identity = self.processor.as_ref().expect("No processor connected");
+        self.socket.send(&identity[..], zmq::SNDMORE).unwrap();
+        self.socket
+            .send(&message.write_to_bytes().unwrap()[..], 0)
+            .unwrap();
+    }
+
+    /// Answers the state requests, returns any other message
+    pub fn serve_state(&mut self) -> Message {
+        loop {
+            let message = self.receive();
+            match message.get_message_type() {
+                Message_MessageType::TP_STATE_GET_REQUEST => {
+                    let request: TpStateGetRequest =
+                        protobuf::parse_from_bytes(message.get_content()).unwrap();
+                    let mut response = TpStateGetResponse::new();
+                    for address in request.get_addresses() {
+                        if let Some(data) = self.state.get(address) {
+                            let mut entry = TpStateEntry::new();
+                            entry.set_address(address.clone());
+                            entry.set_data(data.clone());
+                            response.mut_entries().push(entry);
+                        }
+                    }
+                    response.set_status(TpStateGetResponse_Status::OK);
+                    self.send(
+                        Message_MessageType::TP_STATE_GET_RESPONSE,
+                        message.get_correlation_id(),
+                        &response,
+                    );
+                }
+                Message_MessageType::TP_STATE_SET_REQUEST => {
+                    let request: TpStateSetRequest =
+                        protobuf::parse_from_bytes(message.get_content()).unwrap();
+                    let mut response = TpStateSetResponse::new();
+                    for entry in request.get_entries() {
+                        self.state
+                            .insert(entry.get_address().to_string(), entry.get_data().to_vec());
+                        response
+                            .mut_addresses()
+                            .push(entry.get_address().to_string());
+                    }
+                    response.set_status(TpStateSetResponse_Status::OK);
+                    self.send(
+                        Message_MessageType::TP_STATE_SET_RESPONSE,
+                        message.get_correlation_id(),
+                        &response,
+                    );
+                }
+                _ => return message,
+            }
+        }
+    }
+
+    /// Waits for the registration of the handler and accepts it, the messages sent
+    /// before it are ignored
+    pub fn accept_registration(&mut self) {
+        let register = loop {
+            let message = self.receive();
+            if message.get_message_type() == Message_MessageType::TP_REGISTER_REQUEST {
+                break message;
+            }
+        };
+        let mut registered = TpRegisterResponse::new();
+        registered.set_status(TpRegisterResponse_Status::OK);
+        self.send(
+            Message_MessageType::TP_REGISTER_RESPONSE,
+            register.get_correlation_id(),
+            &registered,
+        );
+    }
+
+    /// Waits for the processor to unregister and accepts it
+    pub fn accept_unregistration(&mut self) {
+        let unregister = self.serve_state();
+        assert_eq!(
+            unregister.get_message_type(),
+            Message_MessageType::TP_UNREGISTER_REQUEST
+        );
+        let mut unregistered = TpUnregisterResponse::new();
+        unregistered.set_status(TpUnregisterResponse_Status::OK);
+        self.send(
+            Message_MessageType::TP_UNREGISTER_RESPONSE,
+            unregister.get_correlation_id(),
+            &unregistered,
+        );
+    }
+
+    /// Sends the request and answers the state requests until the processor responds
+    pub fn process(
+        &mut self,
+        correlation_id: &str,
+        request: &TpProcessRequest,
+    ) -> TpProcessResponse {
+        self.send(
+            Message_MessageType::TP_PROCESS_REQUEST,
+            correlation_id,
+            request,
+        );
+        let processed = self.serve_state();
+        assert_eq!(
+            processed.get_message_type(),
+            Message_MessageType::TP_PROCESS_RESPONSE
+        );
+        assert_eq!(processed.get_correlation_id(), correlation_id);
+        protobuf::parse_from_bytes(processed.get_content()).unwrap()
+    }
+}
+
+/// Starts the processor connected to the endpoint, the environment does not change its
+/// settings
+pub fn spawn_processor(endpoint: &str) -> Child {
+    Command::new(env!("CARGO_BIN_EXE_produce-consume"))
+        .args(&["--connect", endpoint])
+        .env_remove("PC_CONNECT")
+        .env_remove("PC_RECONNECT_MIN_DELAY")
+        .env_remove("PC_RECONNECT_MAX_DELAY")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap()
+}
+
+pub fn terminate(child: &Child) {
+    unsafe {
+        assert_eq!(libc::kill(child.id() as libc::pid_t, libc::SIGTERM), 0);
+    }
+}
+
+pub fn process_request() -> TpProcessRequest {
+    let mut action = Action::new();
+    action.set_command(Action_Command::PRODUCE);
+    action.set_identifier("Bread".to_string());
+    action.set_quantity(10);
+
+    let mut header = TransactionHeader::new();
+    header.set_family_name("produce-consume".to_string());
+    header.set_family_version("1.0".to_string());
+    header.set_signer_public_key(
+        "02b4d3a9f6e1c7d8e5a2b0c9f8e7d6c5b4a3928170f6e5d4c3b2a1908f7e6d5c4b".to_string(),
+    );
+    let mut request = TpProcessRequest::new();
+    request.set_header(header);
+    request.set_payload(action.write_to_bytes().unwrap());
+    request.set_signature("00".repeat(64));
+    request.set_context_id("context-1".to_string());
+    request
+}
+
+pub fn wait_for_exit(child: &mut Child) -> Option<i32> {
+    let started = Instant::now();
+    while started.elapsed() < Duration::from_millis(TIMEOUT_MS as u64) {
+        if let Some(status) = child.try_wait().unwrap() {
+            return status.code();
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    child.kill().unwrap();
+    panic!("The processor did not exit after SIGTERM");
+}
@@ -0,0 +1,48 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Restarts the stand-in validator under the ```produce-consume``` binary, the processor
+//! must register its handler again and keep applying transactions.
+
+extern crate libc;
+extern crate produce_consume;
+extern crate protobuf;
+extern crate sawtooth_sdk;
+extern crate zmq;
+
+mod common;
+
+use common::StandInValidator;
+use sawtooth_sdk::messages::processor::TpProcessResponse_Status;
+
+#[test]
+fn handler_is_registered_again_after_a_validator_restart() {
+    let (mut validator, endpoint) = StandInValidator::bind();
+    let mut child = common::spawn_processor(&endpoint);
+    validator.accept_registration();
+    let response = validator.process("before-restart", &common::process_request());
+    assert_eq!(response.get_status(), TpProcessResponse_Status::OK);
+
+    // The new validator knows nothing of the processor, like a restarted one
+    drop(validator);
+    let (mut restarted, _) = StandInValidator::bind_to(&endpoint);
+    restarted.accept_registration();
+    let response = restarted.process("after-restart", &common::process_request());
+    assert_eq!(response.get_status(), TpProcessResponse_Status::OK);
+    assert!(!restarted.state.is_empty());
+
+    common::terminate(&child);
+    restarted.accept_unregistration();
+    assert_eq!(common::wait_for_exit(&mut child), Some(0));
+}
@@ -0,0 +1,69 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Stops the ```produce-consume``` binary with SIGTERM while it is applying a transaction
+//! for a stand-in validator.
+
+extern crate libc;
+extern crate produce_consume;
+extern crate protobuf;
+extern crate sawtooth_sdk;
+extern crate zmq;
+
+mod common;
+
+use common::StandInValidator;
+use sawtooth_sdk::messages::processor::{TpProcessResponse, TpProcessResponse_Status};
+use sawtooth_sdk::messages::state_context::{TpStateGetResponse, TpStateGetResponse_Status};
+use sawtooth_sdk::messages::validator::Message_MessageType;
+
+#[test]
+fn sigterm_finishes_the_request_and_unregisters() {
+    let (mut validator, endpoint) = StandInValidator::bind();
+    let mut child = common::spawn_processor(&endpoint);
+    validator.accept_registration();
+
+    validator.send(
+        Message_MessageType::TP_PROCESS_REQUEST,
+        "process-1",
+        &common::process_request(),
+    );
+    // The handler is waiting on the state when the signal arrives
+    let first_read = validator.receive();
+    assert_eq!(
+        first_read.get_message_type(),
+        Message_MessageType::TP_STATE_GET_REQUEST
+    );
+    common::terminate(&child);
+    let mut response = TpStateGetResponse::new();
+    response.set_status(TpStateGetResponse_Status::OK);
+    validator.send(
+        Message_MessageType::TP_STATE_GET_RESPONSE,
+        first_read.get_correlation_id(),
+        &response,
+    );
+
+    let processed = validator.serve_state();
+    assert_eq!(
+        processed.get_message_type(),
+        Message_MessageType::TP_PROCESS_RESPONSE
+    );
+    assert_eq!(processed.get_correlation_id(), "process-1");
+    let result: TpProcessResponse = protobuf::parse_from_bytes(processed.get_content()).unwrap();
+    assert_eq!(result.get_status(), TpProcessResponse_Status::OK);
+    assert!(!validator.state.is_empty());
+
+    validator.accept_unregistration();
+    assert_eq!(common::wait_for_exit(&mut child), Some(0));
+}
@@ -28,6 +28,21 @@ sabre ns --create ce2292 --owner $value --url http://rest-api:8008 --key /keys/v
 
 sabre perm ce2292 produce-consume --read --write --url http://rest-api:8008 --key /keys/validator
 
+# Proposals, holdings, totals and the other records, apart from the items
+sabre ns --create 62dd1e --owner $value --url http://rest-api:8008 --key /keys/validator
+
+sabre perm 62dd1e produce-consume --read --write --url http://rest-api:8008 --key /keys/validator
+
 sabre ns --create cad11d --owner $value --url http://rest-api:8008 --key /keys/validator
 
 sabre perm cad11d produce-consume --read --url http://rest-api:8008 --key /keys/validator
+
+# Time-bound actions read the current block timestamp from the BlockInfo namespace
+sabre ns --create 00b10c --owner $value --url http://rest-api:8008 --key /keys/validator
+
+sabre perm 00b10c produce-consume --read --url http://rest-api:8008 --key /keys/validator
+
+# The administrators are read from the produce_consume.admins setting
+sabre ns --create 000000 --owner $value --url http://rest-api:8008 --key /keys/validator
+
+sabre perm 000000 produce-consume --read --url http://rest-api:8008 --key /keys/validator
@@ -0,0 +1,439 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::host_error::HostError;
+use sawtooth_sdk::processor::handler::TransactionContext;
+use std::collections::HashMap;
+use wasmi::memory_units::Pages;
+use wasmi::{
+    Error as WasmiError, Externals, FuncInstance, FuncRef, MemoryRef, ModuleImportResolver,
+    RuntimeArgs, RuntimeValue, Signature, Trap, TrapKind, ValueType,
+};
+
+const PAGE_SIZE: usize = 65536;
+
+/// Log levels understood by the ```log_buffer``` and ```log_level``` externs
+const LOG_LEVELS: [&str; 5] = ["TRACE", "DEBUG", "INFO", "WARN", "ERROR"];
+
+/// Host functions the contracts built with the sabre-sdk import from the "env" module,
+/// their index, the number of i32 parameters and whether they return an i32.
+const HOST_FUNCTIONS: [(&str, usize, bool); 15] = [
+    ("get_state", 1, true),
+    ("set_state", 1, true),
+    ("delete_state", 1, true),
+    ("add_event", 3, true),
+    ("get_ptr_len", 1, true),
+    ("get_ptr_capacity", 1, true),
+    ("alloc", 1, true),
+    ("read_byte", 1, true),
+    ("write_byte", 3, true),
+    ("get_ptr_collection_len", 1, true),
+    ("get_ptr_from_collection", 2, true),
+    ("create_collection", 1, true),
+    ("add_to_collection", 2, true),
+    ("log_buffer", 2, false),
+    ("log_level", 0, true),
+];
+
+/// Resolves the imports of the contract to the host functions of ```SabreExternals```
+pub(crate) struct SabreImportResolver;
+
+impl ModuleImportResolver for SabreImportResolver {
+    fn resolve_func(&self, field_name: &str, signature: &Signature) -> Result<FuncRef, WasmiError> {
+        let index = match HOST_FUNCTIONS
+            .iter()
+            .position(|(name, _, _)| *name == field_name)
+        {
+            Some(index) => index,
+            None => {
+                return Err(WasmiError::Instantiation(format!(
+                    "Host function {} is not supported",
+                    field_name
+                )))
+            }
+        };
+        let (_, params, returns) = HOST_FUNCTIONS[index];
+        let expected = Signature::new(
+            vec![ValueType::I32; params],
+            if returns { Some(ValueType::I32) } else { None },
+        );
+        if signature != &expected {
+            return Err(WasmiError::Instantiation(format!(
+                "Host function {} is imported with the signature {:?}, expected {:?}",
+                field_name, signature, expected
+            )));
+        }
+        Ok(FuncInstance::alloc_host(expected, index))
+    }
+}
+
+/// Implements the Sabre host functions over a ```TransactionContext```.
+///
+/// Buffers are allocated by the host in pages added to the memory of the contract, a
+/// pointer is the offset of the buffer in the memory. A collection is a list of pointers
+/// identified by its first pointer, as the sabre-sdk expects.
+pub(crate) struct SabreExternals<'a> {
+    memory: MemoryRef,
+    context: &'a mut dyn TransactionContext,
+    log_level: i32,
+    lengths: HashMap<u32, usize>,
+    collections: HashMap<u32, Vec<u32>>,
+    // Unused part of the pages added by the host, start and end offsets
+    free: (usize, usize),
+}
+
+impl<'a> SabreExternals<'a> {
+    pub(crate) fn new(
+        memory: MemoryRef,
+        context: &'a mut dyn TransactionContext,
+        log_level: i32,
+    ) -> SabreExternals<'a> {
+        SabreExternals {
+            memory,
+            context,
+            log_level,
+            lengths: HashMap::new(),
+            collections: HashMap::new(),
+            free: (0, 0),
+        }
+    }
+
+    /// Copies the data into a new buffer in the memory of the contract
+    pub(crate) fn write_data(&mut self, data: &[u8]) -> Result<u32, Trap> {
+        let ptr = self.alloc(data.len())?;
+        self.memory
+            .set(ptr, data)
+            .map_err(|err| trap(format!("Unable to write to the memory: {}", err)))?;
+        Ok(ptr)
+    }
+
+    fn alloc(&mut self, length: usize) -> Result<u32, Trap> {
+        // Every buffer takes at least a byte, pointers must be distinct
+        let size = length.max(1);
+        if self.free.1 - self.free.0 < size {
+            let pages = (size + PAGE_SIZE - 1) / PAGE_SIZE;
+            let previous = self
+                .memory
+                .grow(Pages(pages))
+                .map_err(|err| trap(format!("Unable to grow the memory: {}", err)))?;
+            self.free = (previous.0 * PAGE_SIZE, (previous.0 + pages) * PAGE_SIZE);
+        }
+        let ptr = self.free.0 as u32;
+        self.free.0 += size;
+        self.lengths.insert(ptr, length);
+        Ok(ptr)
+    }
+
+    fn read_data(&self, ptr: i32) -> Result<Vec<u8>, Trap> {
+        let length = match self.lengths.get(&(ptr as u32)) {
+            Some(length) => *length,
+            None => return Err(trap(format!("{} is not an allocated pointer", ptr))),
+        };
+        self.memory
+            .get(ptr as u32, length)
+            .map_err(|err| trap(format!("Unable to read from the memory: {}", err)))
+    }
+
+    fn read_string(&self, ptr: i32) -> Result<String, Trap> {
+        String::from_utf8(self.read_data(ptr)?)
+            .map_err(|err| trap(format!("{} is not a UTF-8 string: {}", ptr, err)))
+    }
+
+    /// Buffers of the collection, a negative head is an empty collection
+    fn read_collection(&self, head: i32) -> Result<Vec<Vec<u8>>, Trap> {
+        if head < 0 {
+            return Ok(vec![]);
+        }
+        match self.collections.get(&(head as u32)) {
+            Some(ptrs) => ptrs.iter().map(|ptr| self.read_data(*ptr as i32)).collect(),
+            None => Err(trap(format!("{} is not a collection", head))),
+        }
+    }
+
+    fn read_strings(&self, head: i32) -> Result<Vec<String>, Trap> {
+        self.read_collection(head)?
+            .into_iter()
+            .map(|data| {
+                String::from_utf8(data).map_err(|err| trap(format!("Invalid UTF-8: {}", err)))
+            })
+            .collect()
+    }
+
+    /// Copies the buffers into the memory of the contract and returns the head of their
+    /// collection, or -1 if there are none.
+    fn write_collection(&mut self, buffers: Vec<Vec<u8>>) -> Result<i32, Trap> {
+        let mut ptrs = Vec::new();
+        for buffer in buffers {
+            ptrs.push(self.write_data(&buffer)?);
+        }
+        match ptrs.first() {
+            Some(head) => {
+                let head = *head;
+                self.collections.insert(head, ptrs);
+                Ok(head as i32)
+            }
+            None => Ok(-1),
+        }
+    }
+
+    fn get_state(&mut self, head: i32) -> Result<i32, Trap> {
+        let addresses = self.read_strings(head)?;
+        let entries = self
+            .context
+            .get_state_entries(&addresses)
+            .map_err(|err| trap(format!("Unable to get the state: {}", err)))?;
+        let mut buffers = Vec::new();
+        for (address, data) in entries {
+            buffers.push(address.into_bytes());
+            buffers.push(data);
+        }
+        self.write_collection(buffers)
+    }
+
+    fn set_state(&mut self, head: i32) -> Result<i32, Trap> {
+        let buffers = self.read_collection(head)?;
+        if buffers.len() % 2 != 0 {
+            return Ok(0);
+        }
+        let mut entries = Vec::new();
+        for pair in buffers.chunks(2) {
+            let address = match String::from_utf8(pair[0].clone()) {
+                Ok(address) => address,
+                Err(_) => return Ok(0),
+            };
+            entries.push((address, pair[1].clone()));
+        }
+        match self.context.set_state_entries(entries) {
+            Ok(()) => Ok(1),
+            Err(err) => {
+                eprintln!("Unable to set the state: {}", err);
+                Ok(0)
+            }
+        }
+    }
+
+    fn delete_state(&mut self, head: i32) -> Result<i32, Trap> {
+        let addresses = self.read_strings(head)?;
+        let deleted = self
+            .context
+            .delete_state_entries(&addresses)
+            .map_err(|err| trap(format!("Unable to delete the state: {}", err)))?;
+        self.write_collection(deleted.into_iter().map(String::into_bytes).collect())
+    }
+
+    fn add_event(&mut self, event_type: i32, attributes: i32, data: i32) -> Result<i32, Trap> {
+        let event_type = self.read_string(event_type)?;
+        let attributes = self.read_strings(attributes)?;
+        if attributes.len() % 2 != 0 {
+            return Ok(-1);
+        }
+        let attributes = attributes
+            .chunks(2)
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect();
+        let data = self.read_data(data)?;
+        match self.context.add_event(event_type, attributes, &data) {
+            Ok(()) => Ok(0),
+            Err(err) => {
+                eprintln!("Unable to add the event: {}", err);
+                Ok(-1)
+            }
+        }
+    }
+
+    fn log_buffer(&self, level: i32, ptr: i32) -> Result<(), Trap> {
+        if level < self.log_level {
+            return Ok(());
+        }
+        let name = LOG_LEVELS.get(level as usize).unwrap_or(&"LOG");
+        eprintln!("{:5} | contract | {}", name, self.read_string(ptr)?);
+        Ok(())
+    }
+}
+
+impl<'a> Externals for SabreExternals<'a> {
+    fn invoke_index(
+        &mut self,
+        index: usize,
+        args: RuntimeArgs,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let result = match HOST_FUNCTIONS[index].0 {
+            "get_state" => self.get_state(args.nth_checked(0)?)?,
+            "set_state" => self.set_state(args.nth_checked(0)?)?,
+            "delete_state" => self.delete_state(args.nth_checked(0)?)?,
+            "add_event" => self.add_event(
+                args.nth_checked(0)?,
+                args.nth_checked(1)?,
+                args.nth_checked(2)?,
+            )?,
+            "get_ptr_len" | "get_ptr_capacity" => {
+                let ptr: i32 = args.nth_checked(0)?;
+                match self.lengths.get(&(ptr as u32)) {
+                    Some(length) => *length as i32,
+                    None => -1,
+                }
+            }
+            "alloc" => {
+                let length: i32 = args.nth_checked(0)?;
+                self.alloc(length as usize)? as i32
+            }
+            "read_byte" => {
+                let offset: i32 = args.nth_checked(0)?;
+                let byte = self
+                    .memory
+                    .get(offset as u32, 1)
+                    .map_err(|err| trap(format!("Unable to read from the memory: {}", err)))?;
+                i32::from(byte[0])
+            }
+            "write_byte" => {
+                let ptr: i32 = args.nth_checked(0)?;
+                let offset: i32 = args.nth_checked(1)?;
+                let byte: i32 = args.nth_checked(2)?;
+                match self.lengths.get(&(ptr as u32)) {
+                    Some(length) if (offset as usize) < *length => {
+                        self.memory
+                            .set(ptr as u32 + offset as u32, &[byte as u8])
+                            .map_err(|err| {
+                                trap(format!("Unable to write to the memory: {}", err))
+                            })?;
+                        0
+                    }
+                    _ => -1,
+                }
+            }
+            "get_ptr_collection_len" => {
+                let head: i32 = args.nth_checked(0)?;
+                match self.collections.get(&(head as u32)) {
+                    Some(ptrs) => ptrs.len() as i32,
+                    None => -1,
+                }
+            }
+            "get_ptr_from_collection" => {
+                let head: i32 = args.nth_checked(0)?;
+                let index: i32 = args.nth_checked(1)?;
+                match self
+                    .collections
+                    .get(&(head as u32))
+                    .and_then(|ptrs| ptrs.get(index as usize))
+                {
+                    Some(ptr) => *ptr as i32,
+                    None => -1,
+                }
+            }
+            "create_collection" => {
+                let head: i32 = args.nth_checked(0)?;
+                self.collections.insert(head as u32, vec![head as u32]);
+                head
+            }
+            "add_to_collection" => {
+                let head: i32 = args.nth_checked(0)?;
+                let ptr: i32 = args.nth_checked(1)?;
+                match self.collections.get_mut(&(head as u32)) {
+                    Some(ptrs) => {
+                        ptrs.push(ptr as u32);
+                        head
+                    }
+                    None => -1,
+                }
+            }
+            "log_buffer" => {
+                self.log_buffer(args.nth_checked(0)?, args.nth_checked(1)?)?;
+                return Ok(None);
+            }
+            "log_level" => self.log_level,
+            name => return Err(trap(format!("Host function {} is not supported", name))),
+        };
+        Ok(Some(RuntimeValue::I32(result)))
+    }
+}
+
+fn trap(message: String) -> Trap {
+    Trap::new(TrapKind::Host(Box::new(HostError::from(message))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use produce_consume::produce_consume::memory_context::InMemoryTransactionContext;
+    use wasmi::MemoryInstance;
+
+    fn memory() -> MemoryRef {
+        MemoryInstance::alloc(Pages(1), None).unwrap()
+    }
+
+    fn collection(externals: &mut SabreExternals, buffers: &[&[u8]]) -> i32 {
+        externals
+            .write_collection(buffers.iter().map(|buffer| buffer.to_vec()).collect())
+            .unwrap()
+    }
+
+    #[test]
+    fn set_state_writes_the_pairs_to_the_store() {
+        let mut context = InMemoryTransactionContext::new();
+        {
+            let mut externals = SabreExternals::new(memory(), &mut context, 2);
+            let head = collection(&mut externals, &[b"ab", &[1, 2], b"cd", &[3]]);
+            assert_eq!(externals.set_state(head).unwrap(), 1);
+            // An address without its value is refused
+            let head = collection(&mut externals, &[b"ef"]);
+            assert_eq!(externals.set_state(head).unwrap(), 0);
+        }
+        assert_eq!(context.get("ab"), Some(vec![1, 2]));
+        assert_eq!(context.get("cd"), Some(vec![3]));
+        assert_eq!(context.get("ef"), None);
+    }
+
+    #[test]
+    fn get_state_returns_the_entries_found() {
+        let mut context = InMemoryTransactionContext::new();
+        context.insert("ab", vec![1, 2]);
+        let mut externals = SabreExternals::new(memory(), &mut context, 2);
+
+        let head = collection(&mut externals, &[b"ab", b"cd"]);
+        let found = externals.get_state(head).unwrap();
+        assert_eq!(
+            externals.read_collection(found).unwrap(),
+            vec![b"ab".to_vec(), vec![1, 2]]
+        );
+        let head = collection(&mut externals, &[b"cd"]);
+        assert_eq!(externals.get_state(head).unwrap(), -1);
+        assert!(externals.get_state(12345).is_err());
+    }
+
+    #[test]
+    fn delete_state_returns_the_deleted_addresses() {
+        let mut context = InMemoryTransactionContext::new();
+        context.insert("ab", vec![1]);
+        context.insert("cd", vec![2]);
+        {
+            let mut externals = SabreExternals::new(memory(), &mut context, 2);
+            let head = collection(&mut externals, &[b"ab", b"ef"]);
+            let deleted = externals.delete_state(head).unwrap();
+            assert_eq!(externals.read_strings(deleted).unwrap(), vec!["ab"]);
+        }
+        assert_eq!(context.get("ab"), None);
+        assert_eq!(context.get("cd"), Some(vec![2]));
+    }
+
+    #[test]
+    fn buffers_are_distinct_and_keep_their_length() {
+        let mut context = InMemoryTransactionContext::new();
+        let mut externals = SabreExternals::new(memory(), &mut context, 2);
+        let empty = externals.write_data(&[]).unwrap();
+        let data = externals.write_data(&[7; 70000]).unwrap();
+        assert_ne!(empty, data);
+        assert_eq!(externals.read_data(empty as i32).unwrap(), Vec::<u8>::new());
+        assert_eq!(externals.read_data(data as i32).unwrap().len(), 70000);
+    }
+}
@@ -0,0 +1,56 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::error::Error;
+use std::fmt;
+use std::string::ToString;
+
+#[derive(Debug)]
+pub(crate) struct HostError {
+    inner: String,
+}
+
+impl std::fmt::Display for HostError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HostError: {:?}", &self.inner)
+    }
+}
+
+impl From<String> for HostError {
+    fn from(inner: String) -> HostError {
+        HostError { inner }
+    }
+}
+
+impl From<&'static str> for HostError {
+    fn from(inner: &'static str) -> HostError {
+        HostError {
+            inner: inner.to_string(),
+        }
+    }
+}
+
+impl Error for HostError {
+    fn description(&self) -> &str {
+        &self.inner
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        // Generic error, underlying cause isn't tracked
+        None
+    }
+}
+
+/// Lets a host function abort the execution of the contract with the error
+impl wasmi::HostError for HostError {}
@@ -0,0 +1,217 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+extern crate base64;
+extern crate clap;
+extern crate produce_consume;
+extern crate produce_consume_core;
+extern crate sawtooth_sdk;
+extern crate serde_json;
+extern crate wasmi;
+
+mod externals;
+mod host_error;
+mod runner;
+mod state_file;
+
+use crate::host_error::HostError;
+use crate::runner::Outcome;
+use clap::App;
+use clap::Arg;
+use produce_consume_core::addressing::default_family_name;
+use std::collections::BTreeMap;
+use std::fs;
+use std::process;
+
+fn main() {
+    let matches = App::new("pc-sabre-host")
+        .author("Walmart Inc.")
+        .version("1.0")
+        .about("Runs the produce-consume wasm contract locally, without a Sabre network")
+        .arg(
+            Arg::with_name("wasm")
+                .short("W")
+                .long("wasm")
+                .help("Compiled contract, produce-consume.wasm")
+                .takes_value(true)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("payload")
+                .short("P")
+                .long("payload")
+                .help("Payload file, as saved by pc-cli without --url")
+                .takes_value(true)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("signer")
+                .short("S")
+                .long("signer")
+                .help("Public key of the signer of the transaction")
+                .takes_value(true)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("signature")
+                .long("signature")
+                .help("Signature of the transaction")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("state")
+                .long("state")
+                .help("JSON file of the initial state, addresses to base64 encoded values")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .help("JSON file to write the resulting state to")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("compare")
+                .long("compare")
+                .help("Applies the payload with the native handler as well and compares")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("family_name")
+                .long("family-name")
+                .help("Family name the contract was built with, for the native handler")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .help("Logs of the contract, repeat for more")
+                .takes_value(false)
+                .multiple(true),
+        )
+        .get_matches();
+
+    let log_level = match matches.occurrences_of("verbose") {
+        0 => 3,
+        1 => 2,
+        2 => 1,
+        3 | _ => 0,
+    };
+
+    let compare = if matches.is_present("compare") {
+        Some(
+            matches
+                .value_of("family_name")
+                .unwrap_or_else(default_family_name),
+        )
+    } else {
+        None
+    };
+    // The contract does not verify the signature, a placeholder is enough
+    let default_signature = "0".repeat(128);
+    match run(
+        matches.value_of("wasm").unwrap(),
+        matches.value_of("payload").unwrap(),
+        matches.value_of("signer").unwrap(),
+        matches.value_of("signature").unwrap_or(&default_signature),
+        matches.value_of("state"),
+        matches.value_of("output"),
+        compare,
+        log_level,
+    ) {
+        Ok(true) => (),
+        Ok(false) => process::exit(1),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    }
+}
+
+/// Returns true if the contract accepts the transaction and, when comparing, the native
+/// handler registered for the given family name agrees on the outcome and on the resulting
+/// state.
+fn run(
+    wasm_file: &str,
+    payload_file: &str,
+    signer: &str,
+    signature: &str,
+    state_file: Option<&str>,
+    output_file: Option<&str>,
+    compare: Option<&str>,
+    log_level: i32,
+) -> Result<bool, HostError> {
+    let wasm = read_file(wasm_file)?;
+    let payload = read_file(payload_file)?;
+    let initial = state_file::load(state_file)?;
+
+    let mut context = initial.checkpoint();
+    let outcome = runner::run_wasm(&wasm, &payload, signer, signature, &mut context, log_level)?;
+    println!("Contract: {:?}", outcome);
+    print_changes(&initial.entries(), &context.entries());
+    if let Some(path) = output_file {
+        state_file::save(path, &context.entries())?;
+    }
+    let family_name = match compare {
+        Some(family_name) => family_name,
+        None => return Ok(outcome == Outcome::Accepted),
+    };
+
+    let mut native_context = initial.checkpoint();
+    let native_outcome = runner::run_native(
+        &payload,
+        signer,
+        signature,
+        family_name,
+        &mut native_context,
+    );
+    println!("Native handler: {:?}", native_outcome);
+    let same_outcome = match (&outcome, &native_outcome) {
+        (Outcome::Accepted, Outcome::Accepted) => true,
+        (Outcome::Rejected(_), Outcome::Rejected(_)) => true,
+        _ => false,
+    };
+    if !same_outcome {
+        println!("MISMATCH: the contract and the native handler disagree on the outcome");
+    }
+    let same_state = context.entries() == native_context.entries();
+    if !same_state {
+        println!("MISMATCH: the resulting states differ, contract -> native handler");
+        print_changes(&context.entries(), &native_context.entries());
+    }
+    Ok(outcome == Outcome::Accepted && same_outcome && same_state)
+}
+
+/// Prints the addresses added (+), changed (~) and removed (-) between the two states
+fn print_changes(before: &BTreeMap<String, Vec<u8>>, after: &BTreeMap<String, Vec<u8>>) {
+    for (address, data) in after {
+        match before.get(address) {
+            None => println!("+ {} ({} bytes)", address, data.len()),
+            Some(previous) if previous != data => {
+                println!("~ {} ({} bytes)", address, data.len())
+            }
+            Some(_) => (),
+        }
+    }
+    for address in before.keys() {
+        if !after.contains_key(address) {
+            println!("- {}", address);
+        }
+    }
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, HostError> {
+    fs::read(path).map_err(|err| HostError::from(format!("Unable to read {}: {}", path, err)))
+}
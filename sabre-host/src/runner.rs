@@ -0,0 +1,216 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::externals::{SabreExternals, SabreImportResolver};
+use crate::host_error::HostError;
+use produce_consume::produce_consume::handler::ProduceConsumeHandler;
+use produce_consume::produce_consume::memory_context::InMemoryTransactionContext;
+use produce_consume_core::addressing::VERSION;
+use sawtooth_sdk::messages::processor::TpProcessRequest;
+use sawtooth_sdk::messages::transaction::TransactionHeader;
+use sawtooth_sdk::processor::handler::TransactionHandler;
+use wasmi::{ImportsBuilder, Module, ModuleInstance, NopExternals, RuntimeValue};
+
+/// Result of applying a transaction, the state changes of a rejected transaction are
+/// discarded like the validator does.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Outcome {
+    Accepted,
+    Rejected(String),
+}
+
+/// Loads the contract and calls its ```entrypoint``` with the payload and the signer.
+///
+/// The return value of the entrypoint follows the sabre-sdk, 1 means the transaction is
+/// valid, anything else that it is rejected.
+pub(crate) fn run_wasm(
+    wasm: &[u8],
+    payload: &[u8],
+    signer: &str,
+    signature: &str,
+    context: &mut InMemoryTransactionContext,
+    log_level: i32,
+) -> Result<Outcome, HostError> {
+    let module = Module::from_buffer(wasm)
+        .map_err(|err| HostError::from(format!("Unable to load the contract: {}", err)))?;
+    let instance = ModuleInstance::new(
+        &module,
+        &ImportsBuilder::new().with_resolver("env", &SabreImportResolver),
+    )
+    .map_err(|err| HostError::from(format!("Unable to instantiate the contract: {}", err)))?
+    .run_start(&mut NopExternals)
+    .map_err(|err| HostError::from(format!("Unable to start the contract: {:?}", err)))?;
+    let memory = match instance
+        .export_by_name("memory")
+        .and_then(|export| export.as_memory().cloned())
+    {
+        Some(memory) => memory,
+        None => return Err(HostError::from("The contract does not export its memory")),
+    };
+
+    let checkpoint = context.checkpoint();
+    let result = {
+        let mut externals = SabreExternals::new(memory, context, log_level);
+        let mut args = Vec::new();
+        for data in &[payload, signer.as_bytes(), signature.as_bytes()] {
+            let ptr = externals
+                .write_data(data)
+                .map_err(|err| HostError::from(format!("{:?}", err)))?;
+            args.push(RuntimeValue::I32(ptr as i32));
+        }
+        instance.invoke_export("entrypoint", &args, &mut externals)
+    };
+    let outcome = match result {
+        Ok(Some(RuntimeValue::I32(1))) => Outcome::Accepted,
+        Ok(Some(RuntimeValue::I32(code))) => {
+            Outcome::Rejected(format!("entrypoint returned {}", code))
+        }
+        Ok(value) => {
+            return Err(HostError::from(format!(
+                "Unexpected return value of the entrypoint {:?}",
+                value
+            )))
+        }
+        Err(err) => Outcome::Rejected(format!("contract trapped: {}", err)),
+    };
+    if outcome != Outcome::Accepted {
+        context.rollback(checkpoint);
+    }
+    Ok(outcome)
+}
+
+/// Applies the payload with the native handler, to compare with the contract. The family
+/// name must be the one the contract was compiled with for the addresses to match.
+pub(crate) fn run_native(
+    payload: &[u8],
+    signer: &str,
+    signature: &str,
+    family_name: &str,
+    context: &mut InMemoryTransactionContext,
+) -> Outcome {
+    let mut header = TransactionHeader::new();
+    header.set_signer_public_key(signer.to_string());
+    let mut request = TpProcessRequest::new();
+    request.set_header(header);
+    request.set_payload(payload.to_vec());
+    request.set_signature(signature.to_string());
+
+    let checkpoint = context.checkpoint();
+    let handler = ProduceConsumeHandler::with_family(family_name, VERSION);
+    match handler.apply(&request, context) {
+        Ok(()) => Outcome::Accepted,
+        Err(err) => {
+            context.rollback(checkpoint);
+            Outcome::Rejected(err.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use produce_consume_core::addressing::default_family_name;
+    use produce_consume_core::payload;
+    use std::env;
+    use std::fs;
+
+    const SIGNER: &str = "02b4d3a9f6e1c7d8e5a2b0c9f8e7d6c5b4a3928170f6e5d4c3b2a1908f7e6d5c4b";
+    const SIGNATURE: &str = "00";
+
+    /// The contract built by `cargo build --target wasm32-unknown-unknown --release` in
+    /// the processor, `PC_WASM` can name another build
+    fn contract() -> Vec<u8> {
+        let path = env::var("PC_WASM").unwrap_or_else(|_| {
+            format!(
+                "{}/../processor/target/wasm32-unknown-unknown/release/produce-consume.wasm",
+                env!("CARGO_MANIFEST_DIR")
+            )
+        });
+        fs::read(&path).unwrap_or_else(|err| panic!("Unable to read {}: {}", path, err))
+    }
+
+    fn encoded(action: &produce_consume_core::proto::action::Action) -> Vec<u8> {
+        payload::encode_action(action).unwrap()
+    }
+
+    // Needs the wasm build of the processor, run it with cargo test -- --ignored
+    #[test]
+    #[ignore]
+    fn contract_writes_what_the_native_handler_writes() {
+        let wasm = contract();
+        let mut contract_context = InMemoryTransactionContext::new();
+        let mut native_context = InMemoryTransactionContext::new();
+        let payloads = vec![
+            encoded(&payload::produce("Bread", 10, 3, "Bakery")),
+            encoded(&payload::consume("Bread", 4, "")),
+            // Rejected by both, nothing is written
+            encoded(&payload::consume("Bread", 7, "")),
+        ];
+        for (index, payload) in payloads.iter().enumerate() {
+            let outcome =
+                run_wasm(&wasm, payload, SIGNER, SIGNATURE, &mut contract_context, 4).unwrap();
+            let native_outcome = run_native(
+                payload,
+                SIGNER,
+                SIGNATURE,
+                default_family_name(),
+                &mut native_context,
+            );
+            assert_eq!(outcome == Outcome::Accepted, index < 2);
+            assert_eq!(native_outcome == Outcome::Accepted, index < 2);
+            assert_eq!(contract_context.entries(), native_context.entries());
+        }
+        assert!(!contract_context.entries().is_empty());
+        // Only the native handler has a receipt, the cost of goods of the CONSUME
+        assert!(contract_context.receipt_data().is_empty());
+        assert_eq!(native_context.receipt_data().len(), 1);
+    }
+
+    #[test]
+    fn invalid_contract_is_an_error() {
+        let mut context = InMemoryTransactionContext::new();
+        assert!(run_wasm(b"not wasm", &[], SIGNER, SIGNATURE, &mut context, 4).is_err());
+        assert!(context.entries().is_empty());
+    }
+
+    #[test]
+    fn native_handler_discards_a_rejected_transaction() {
+        let mut context = InMemoryTransactionContext::new();
+        let produce = encoded(&payload::produce("Bread", 3, 0, ""));
+        assert_eq!(
+            run_native(
+                &produce,
+                SIGNER,
+                SIGNATURE,
+                default_family_name(),
+                &mut context
+            ),
+            Outcome::Accepted
+        );
+        let before = context.entries();
+
+        let consume = encoded(&payload::consume("Bread", 4, ""));
+        match run_native(
+            &consume,
+            SIGNER,
+            SIGNATURE,
+            default_family_name(),
+            &mut context,
+        ) {
+            Outcome::Rejected(_) => (),
+            other => panic!("Expected a rejection, got {:?}", other),
+        }
+        assert_eq!(context.entries(), before);
+    }
+}
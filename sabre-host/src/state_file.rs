@@ -0,0 +1,55 @@
+// Copyright 2019 Walmart Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::host_error::HostError;
+use produce_consume::produce_consume::memory_context::InMemoryTransactionContext;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+
+/// Reads a JSON object of addresses to base64 encoded values into a new context, for
+/// example the settings, BlockInfo or produce-consume entries copied from a network.
+pub(crate) fn load(path: Option<&str>) -> Result<InMemoryTransactionContext, HostError> {
+    let context = InMemoryTransactionContext::new();
+    let path = match path {
+        Some(path) => path,
+        None => return Ok(context),
+    };
+    let content = fs::read_to_string(path)
+        .map_err(|err| HostError::from(format!("Unable to read {}: {}", path, err)))?;
+    let entries: Map<String, Value> = serde_json::from_str(&content)
+        .map_err(|err| HostError::from(format!("Unable to parse {}: {}", path, err)))?;
+    for (address, value) in entries {
+        let data = value
+            .as_str()
+            .and_then(|encoded| base64::decode(encoded).ok())
+            .ok_or_else(|| {
+                HostError::from(format!("The value of {} is not base64 encoded", address))
+            })?;
+        context.insert(&address, data);
+    }
+    Ok(context)
+}
+
+/// Writes the entries in the format read by ```load```
+pub(crate) fn save(path: &str, entries: &BTreeMap<String, Vec<u8>>) -> Result<(), HostError> {
+    let encoded: Map<String, Value> = entries
+        .iter()
+        .map(|(address, data)| (address.clone(), Value::String(base64::encode(data))))
+        .collect();
+    let content = serde_json::to_string_pretty(&Value::Object(encoded))
+        .map_err(|err| HostError::from(err.to_string()))?;
+    fs::write(path, content)
+        .map_err(|err| HostError::from(format!("Unable to write {}: {}", path, err)))
+}
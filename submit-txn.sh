@@ -15,5 +15,5 @@
 # limitations under the License.
 
 sabre exec --contract produce-consume:1.0 \
- --payload default.batch --inputs ce2292 --inputs cad11d --outputs ce2292 \
+ --payload default.batch --inputs ce2292 --inputs 62dd1e --inputs cad11d --inputs 00b10c --inputs 000000 --outputs ce2292 --outputs 62dd1e \
  --url http://rest-api:8008 --key /keys/validator